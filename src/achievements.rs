@@ -0,0 +1,262 @@
+use crate::events::{GameEvent, NEAR_MISS};
+use crate::storage;
+use crate::window::SCREEN_WIDTH;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+pub const ACHIEVEMENTS_FILE: &str = "achievements.json";
+const SAVE_FILE: &str = "achievements.json";
+/// How many frames an unlock toast stays on screen.
+const TOAST_FRAMES: u32 = 180;
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Medal {
+    Bronze,
+    Silver,
+    Gold,
+    Platinum,
+}
+
+impl Medal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Medal::Bronze => "Bronze",
+            Medal::Silver => "Silver",
+            Medal::Gold => "Gold",
+            Medal::Platinum => "Platinum",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// Reach this score in a single run.
+    Score(i64),
+    /// Scrape past this many pipes in a single run.
+    NearMisses(u32),
+    /// Play this many runs in total.
+    Runs(u32),
+    /// Bump into the top of the screen.
+    HitCeiling,
+    /// Stay alive for this many seconds in a single run.
+    SurviveSeconds(u64),
+}
+
+impl Condition {
+    /// Whether this event can change if the condition is met, so the rest can be skipped.
+    fn affected_by(&self, event: &GameEvent) -> bool {
+        match (self, event) {
+            (Condition::Score(_), GameEvent::Scored(_)) => true,
+            (Condition::NearMisses(_), GameEvent::PipeCleared { .. }) => true,
+            (Condition::Runs(_), GameEvent::RunStarted { .. }) => true,
+            (Condition::HitCeiling, GameEvent::HitCeiling) => true,
+            (Condition::SurviveSeconds(_), GameEvent::Survived(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AchievementData {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MedalData {
+    pub medal: Medal,
+    pub score: i64,
+}
+
+/// Everything that can be unlocked, as described in the achievements file.
+#[derive(Deserialize, Debug)]
+pub struct AchievementList {
+    pub medals: Vec<MedalData>,
+    pub achievements: Vec<AchievementData>,
+}
+
+impl AchievementList {
+    pub fn parse_achievements_json(achievements_file: &Path) -> Self {
+        use std::fs::File;
+        use std::io::BufReader;
+
+        let file = File::open(achievements_file).expect("Couldn't find the achievements file");
+        let buf_reader = BufReader::new(file);
+        serde_json::from_reader(buf_reader).expect("Couldn't parse the achievements file")
+    }
+
+    /// The best medal a score is worth, if any.
+    pub fn medal_for(&self, score: i128) -> Option<Medal> {
+        self.medals
+            .iter()
+            .filter(|m| score >= m.score as i128)
+            .max_by_key(|m| m.score)
+            .map(|m| m.medal)
+    }
+}
+
+/// What has been unlocked so far. This is what gets saved.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Unlocked {
+    pub achievements: HashSet<String>,
+    pub runs: u32,
+    /// How many times each medal has been won, by medal name.
+    pub medals: HashMap<String, u32>,
+}
+
+/// Progress towards the achievements during the current run.
+#[derive(Debug, Default)]
+struct RunProgress {
+    score: i128,
+    near_misses: u32,
+    hit_ceiling: bool,
+    survived_secs: u64,
+}
+
+/// Watches the game events and unlocks achievements and medals.
+pub struct Achievements {
+    list: AchievementList,
+    pub unlocked: Unlocked,
+    run: RunProgress,
+    toasts: VecDeque<String>,
+    toast_frames: u32,
+}
+
+impl Achievements {
+    /// Reads the achievement list from the resources folder.
+    pub fn new(resources: &Path) -> Self {
+        Self {
+            list: AchievementList::parse_achievements_json(&resources.join(ACHIEVEMENTS_FILE)),
+            unlocked: storage::load(SAVE_FILE),
+            run: RunProgress::default(),
+            toasts: VecDeque::new(),
+            toast_frames: 0,
+        }
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted { .. } => {
+                self.run = RunProgress::default();
+                self.unlocked.runs += 1;
+            }
+            GameEvent::Scored(score) => self.run.score = *score,
+            GameEvent::PipeCleared { clearance } if *clearance < NEAR_MISS => {
+                self.run.near_misses += 1
+            }
+            GameEvent::HitCeiling => self.run.hit_ceiling = true,
+            GameEvent::Survived(time) => self.run.survived_secs = time.as_secs(),
+            _ => {}
+        }
+        self.check_unlocks(event);
+
+        if let GameEvent::RunStarted { .. } = event {
+            self.save();
+        }
+    }
+
+    /// Awards the medal for a finished run and returns it.
+    pub fn finish_run(&mut self, score: i128) -> Option<Medal> {
+        let medal = self.list.medal_for(score);
+        if let Some(medal) = medal {
+            *self.unlocked.medals.entry(medal.name().to_string()).or_insert(0) += 1;
+        }
+        self.save();
+        medal
+    }
+
+    /// Only looks at the achievements this event could have unlocked.
+    fn check_unlocks(&mut self, event: &GameEvent) {
+        let newly_unlocked: Vec<AchievementData> = self
+            .list
+            .achievements
+            .iter()
+            .filter(|a| a.condition.affected_by(event))
+            .filter(|a| !self.unlocked.achievements.contains(&a.id))
+            .filter(|a| self.is_met(&a.condition))
+            .cloned()
+            .collect();
+
+        for achievement in newly_unlocked {
+            self.toasts.push_back(format!("Achievement unlocked: {}", achievement.name));
+            self.unlocked.achievements.insert(achievement.id);
+        }
+    }
+
+    fn is_met(&self, condition: &Condition) -> bool {
+        match *condition {
+            Condition::Score(score) => self.run.score >= score as i128,
+            Condition::NearMisses(count) => self.run.near_misses >= count,
+            Condition::Runs(count) => self.unlocked.runs >= count,
+            Condition::HitCeiling => self.run.hit_ceiling,
+            Condition::SurviveSeconds(secs) => self.run.survived_secs >= secs,
+        }
+    }
+
+    fn save(&self) {
+        storage::save(SAVE_FILE, &self.unlocked);
+    }
+
+    /// Counts down the toast currently on screen and moves onto the next one.
+    pub fn update(&mut self) {
+        if self.toast_frames > 0 {
+            self.toast_frames -= 1;
+            if self.toast_frames == 0 {
+                self.toasts.pop_front();
+            }
+        } else if !self.toasts.is_empty() {
+            self.toast_frames = TOAST_FRAMES;
+        }
+    }
+
+    pub fn draw_toast(&self, ctx: &mut Context) -> GameResult {
+        let toast = match self.toasts.front() {
+            Some(toast) if self.toast_frames > 0 => toast,
+            _ => return Ok(()),
+        };
+        let text = Text::new(toast.as_str());
+        let x = (SCREEN_WIDTH - text.width(ctx) as f32) / 2.0;
+        graphics::draw(ctx, &text, (Point2::new(x, 40.0), graphics::WHITE))
+    }
+
+    /// The name of the achievement with this id.
+    pub fn name_of(&self, id: &str) -> Option<&str> {
+        self.list.achievements.iter().find(|a| a.id == id).map(|a| a.name.as_str())
+    }
+
+    /// Draws every achievement, greyed out if it is still locked.
+    pub fn draw_gallery(&self, ctx: &mut Context) -> GameResult {
+        let locked_colour = graphics::Color::new(1.0, 1.0, 1.0, 0.4);
+        let mut y = 80.0;
+
+        let medals = [Medal::Bronze, Medal::Silver, Medal::Gold, Medal::Platinum]
+            .iter()
+            .map(|m| format!("{}: {}", m.name(), self.unlocked.medals.get(m.name()).unwrap_or(&0)))
+            .collect::<Vec<String>>()
+            .join("   ");
+        let header = Text::new(format!("Runs: {}   {}", self.unlocked.runs, medals));
+        graphics::draw(ctx, &header, (Point2::new(40.0, y), graphics::WHITE))?;
+        y += 40.0;
+
+        for achievement in &self.list.achievements {
+            let colour = if self.unlocked.achievements.contains(&achievement.id) {
+                graphics::WHITE
+            } else {
+                locked_colour
+            };
+            let text = Text::new(format!("{} - {}", achievement.name, achievement.description));
+            graphics::draw(ctx, &text, (Point2::new(40.0, y), colour))?;
+            y += 24.0;
+        }
+
+        Ok(())
+    }
+}
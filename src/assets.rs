@@ -0,0 +1,113 @@
+use crate::achievements::{AchievementList, ACHIEVEMENTS_FILE};
+use crate::atlas::Atlas;
+use crate::collectibles::POWER_UPS;
+use crate::cosmetics::{Catalogue, COSMETICS_FILE};
+use crate::level::{Campaign, LEVELS_DIR};
+use crate::settings::Settings;
+use crate::theme::{ThemeList, THEMES_FILE};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Every sprite the game asks the atlas for, apart from the power ups.
+const SPRITES: [&str; 9] = [
+    "crab0.png",
+    "crab1.png",
+    "pipe_bottom.png",
+    "pipe_top.png",
+    "floor_tile.png",
+    "jellyfish.png",
+    "seagull0.png",
+    "seagull1.png",
+    "pearl.png",
+];
+
+/// Checks everything the game loads from the resources folder is there and readable,
+/// so mistakes turn up here instead of as a panic halfway through a run.
+/// Returns what is wrong, or nothing if it all looks fine.
+pub fn validate(resources: &Path, settings_file: &Path) -> Vec<String> {
+    let mut problems = vec![];
+
+    let catalogue = Catalogue::load(&resources.join(COSMETICS_FILE)).unwrap_or_else(|e| {
+        problems.push(format!("{}: {}", COSMETICS_FILE, e));
+        Catalogue { cosmetics: vec![] }
+    });
+    let themes = ThemeList::load(resources).unwrap_or_else(|e| {
+        problems.push(format!("{}: {}", THEMES_FILE, e));
+        ThemeList { themes: vec![] }
+    });
+    // Every theme's atlas needs every sprite, in the same place as in the first theme's.
+    let mut first: Option<Atlas> = None;
+    for theme in &themes.themes {
+        let atlas = match Atlas::load(&resources.join(&theme.atlas)) {
+            Ok(atlas) => atlas,
+            Err(e) => {
+                problems.push(format!("{}: {}", theme.atlas, e));
+                continue;
+            }
+        };
+        let power_ups = POWER_UPS.iter().map(|p| p.sprite_name());
+        let layers = theme.layers.iter().map(|layer| layer.sprite.as_str());
+        for sprite in SPRITES.iter().cloned().chain(power_ups).chain(catalogue.atlas_frames()).chain(layers) {
+            if !atlas.has_sprite(sprite) {
+                problems.push(format!("{} has no sprite called {}", theme.atlas, sprite));
+            }
+        }
+        match &first {
+            Some(first) if !atlas.same_layout(first) => problems.push(format!(
+                "The {} theme's {} doesn't match the first theme's atlas",
+                theme.name, theme.atlas
+            )),
+            Some(_) => {}
+            None => first = Some(atlas),
+        }
+        let sounds = &theme.sounds;
+        let files = [&theme.image, &sounds.score, &sounds.ouch, &sounds.begin];
+        for file in files.iter().cloned().chain(&theme.music) {
+            // ggez looks for these from the top of the resources folder.
+            let file = file.trim_start_matches('/');
+            if let Err(e) = std::fs::metadata(resources.join(file)) {
+                problems.push(format!("The {} theme's {}: {}", theme.name, file, e));
+            }
+        }
+    }
+    // Cosmetics can come from atlases of their own, which need their image alongside them.
+    let others: BTreeSet<&String> =
+        catalogue.cosmetics.iter().filter_map(|cosmetic| cosmetic.atlas.as_ref()).collect();
+    for name in others {
+        let file = format!("{}.json", name);
+        match Atlas::load(&resources.join(&file)) {
+            Ok(other) => {
+                let frames = catalogue.cosmetics.iter().filter(|cosmetic| cosmetic.atlas.as_ref() == Some(name));
+                for frame in frames.flat_map(|cosmetic| cosmetic.frames.iter()) {
+                    if !other.has_sprite(frame) {
+                        problems.push(format!("{} has no sprite called {}", file, frame));
+                    }
+                }
+            }
+            Err(e) => problems.push(format!("{}: {}", file, e)),
+        }
+        if let Err(e) = std::fs::metadata(resources.join(format!("{}.png", name))) {
+            problems.push(format!("{}.png: {}", name, e));
+        }
+    }
+
+    // Missing settings are fine, the defaults get used. Broken ones aren't.
+    if settings_file.exists() {
+        if let Err(e) = parse::<Settings>(settings_file) {
+            problems.push(format!("{}: {}", settings_file.display(), e));
+        }
+    }
+    if let Err(e) = parse::<AchievementList>(&resources.join(ACHIEVEMENTS_FILE)) {
+        problems.push(format!("{}: {}", ACHIEVEMENTS_FILE, e));
+    }
+    if let Err(e) = Campaign::load(&resources.join(LEVELS_DIR)) {
+        problems.push(e.to_string());
+    }
+
+    problems
+}
+
+fn parse<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
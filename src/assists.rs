@@ -0,0 +1,55 @@
+use crate::pipe::VERTICAL_GAP;
+use serde::{Deserialize, Serialize};
+
+const MIN_SPEED: f32 = 0.5;
+const MAX_GAP: f32 = 1.5;
+
+/// Ways of making the game easier to play. Runs with any of them on except reduced motion
+/// are marked as assisted on the leaderboards.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Assists {
+    /// How fast the game runs, from 0.5 to 1.0. Slows the pipes and the crab's falling alike.
+    pub speed: f32,
+    /// How much taller the gaps in the endless course are, from 1.0 to 1.5.
+    pub gap: f32,
+    /// Holding the flap key makes the crab rise gently instead of flapping.
+    pub hold_to_float: bool,
+    /// Pipes can't hurt the crab, and it bounces off the ground.
+    pub invincible: bool,
+    /// No tilting crab, screen shake or flashes.
+    pub reduced_motion: bool,
+}
+
+impl Default for Assists {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            gap: 1.0,
+            hold_to_float: false,
+            invincible: false,
+            reduced_motion: false,
+        }
+    }
+}
+
+impl Assists {
+    /// The same assists, kept within their limits.
+    pub fn clamped(&self) -> Self {
+        Self {
+            speed: self.speed.max(MIN_SPEED).min(1.0),
+            gap: self.gap.max(1.0).min(MAX_GAP),
+            ..self.clone()
+        }
+    }
+
+    /// Whether any of them change how the game plays. Reduced motion only changes how it looks.
+    pub fn any(&self) -> bool {
+        self.speed < 1.0 || self.gap > 1.0 || self.hold_to_float || self.invincible
+    }
+
+    /// How tall the gaps in the endless course are.
+    pub fn gap_height(&self) -> f32 {
+        VERTICAL_GAP * self.gap
+    }
+}
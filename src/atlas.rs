@@ -1,20 +1,25 @@
 use ggez::graphics::{self};
 use ggez::nalgebra::{Point2, Vector2};
-use serde::{Deserialize};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+/// The texture atlas description in the resources folder.
+pub const ATLAS_FILE: &str = "texture_atlas.json";
+/// The image the atlas describes, as ggez finds it in the resources folder.
+pub const ATLAS_IMAGE: &str = "/texture_atlas.png";
+
 #[derive(Deserialize, Debug)]
 struct Meta {
     size: AtlasSize,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq)]
 struct AtlasSize {
     w: i32,
     h: i32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 struct JsonRect {
     x: i32,
     y: i32,
@@ -22,7 +27,7 @@ struct JsonRect {
     h: i32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 struct SpriteData {
     filename: String,
     frame: JsonRect,
@@ -44,6 +49,24 @@ impl Atlas {
         serde_json::from_reader(buf_reader).expect("Couldn't create texture atlas")
     }
 
+    /// Like `parse_atlas_json`, but says what went wrong instead of panicking.
+    pub fn load(texture_atlas_file: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(texture_atlas_file).map_err(|e| e.to_string())?;
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    /// Whether both atlases have the same sprites in the same places,
+    /// so either image can be drawn with the other's sprites.
+    pub fn same_layout(&self, other: &Atlas) -> bool {
+        self.meta.size == other.meta.size
+            && self.frames.len() == other.frames.len()
+            && self.frames.iter().all(|frame| other.frames.contains(frame))
+    }
+
+    pub fn has_sprite(&self, sprite_name: &str) -> bool {
+        self.frames.iter().any(|d| d.filename == sprite_name)
+    }
+
     /// Returns a sprite from the Atlas.
     pub fn create_sprite(&self, sprite_name: &str) -> Sprite {
         let width = self.meta.size.w as f32;
@@ -68,10 +91,12 @@ impl Atlas {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Sprite {
     /// The square that we want to cut out of the texture atlas.
+    #[serde(with = "crate::shapes::rect")]
     pub rect: graphics::Rect,
+    #[serde(with = "crate::shapes::vector")]
     pub scale: Vector2<f32>,
     pub width: f32,
     pub height: f32,
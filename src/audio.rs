@@ -5,39 +5,71 @@ use ggez::{
 use ggez::audio::SoundSource;
 use rand::distributions::OpenClosed01;
 use rand::{thread_rng, Rng};
+use crate::theme::Sounds;
 
 
+/// The sound effects of one theme.
+struct SoundSet {
+    score_sound: Source,
+    ouch_sound: Source,
+    begin_sound: Source,
+}
+
+/// Plays the sound effects of whichever theme is showing.
 pub struct Player {
-    pub score_sound: Source,
-    pub ouch_sound: Source,
-    pub begin_sound: Source,
+    sets: Vec<SoundSet>,
+    current: usize,
+}
+
+/// Loads a theme's sound, or the game's own one in its place if it can't be loaded.
+fn load(ctx: &mut Context, path: &str, fallback: &str) -> Source {
+    Source::new(ctx, path).unwrap_or_else(|e| {
+        println!("Couldn't load {}, using {} instead: {}", path, fallback, e);
+        Source::new(ctx, fallback).unwrap_or_else(|e| panic!("Couldn't load {}: {}", fallback, e))
+    })
 }
 
 impl Player {
-    pub fn new(ctx: &mut Context) -> Self {
-        let score_sound =  Source::new(ctx, "/score_point.wav").unwrap();
-        let ouch_sound =  Source::new(ctx, "/ouch.wav").unwrap();
-        let begin_sound = Source::new(ctx, "/begin_game.wav").unwrap();
+    /// Loads the sounds of every theme, in the order of the themes.
+    /// Sounds a theme is missing are swapped for the game's own.
+    pub fn new<'a>(ctx: &mut Context, themes: impl Iterator<Item = &'a Sounds>) -> Self {
+        let default = Sounds::default();
+        let sets = themes
+            .map(|sounds| SoundSet {
+                score_sound: load(ctx, &sounds.score, &default.score),
+                ouch_sound: load(ctx, &sounds.ouch, &default.ouch),
+                begin_sound: load(ctx, &sounds.begin, &default.begin),
+            })
+            .collect();
 
         Self {
-            score_sound,
-            ouch_sound,
-            begin_sound,
+            sets,
+            current: 0,
         }
     }
 
+    /// Plays the sounds of the theme with this index from now on.
+    pub fn use_theme(&mut self, theme: usize) {
+        self.current = theme.min(self.sets.len() - 1);
+    }
+
+    fn set(&mut self) -> &mut SoundSet {
+        &mut self.sets[self.current]
+    }
+
     pub fn begin(&mut self){
-        self.begin_sound.play_detached();
+        self.set().begin_sound.play_detached();
     }
 
     pub fn ouch(&mut self){
-        self.ouch_sound.play_detached();
+        self.set().ouch_sound.play_detached();
     }
 
     pub fn score(&mut self) {
         let pitch: f32 = thread_rng().sample(OpenClosed01);
-        self.score_sound.set_pitch(1.0 + pitch);
+        let score_sound = &mut self.set().score_sound;
+        score_sound.set_pitch(1.0 + pitch);
 
-        self.score_sound.play_detached();
+        score_sound.play_detached();
     }
-}
\ No newline at end of file
+}
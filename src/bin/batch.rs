@@ -0,0 +1,253 @@
+//! Plays lots of games without a window, spread over several threads,
+//! and prints how fast they ran and how they went.
+//! `--min-rate` makes it fail when the update loop gets slower, so it can be used as a benchmark.
+
+use flappybird::atlas::{Atlas, ATLAS_FILE};
+use flappybird::cli::parse_number;
+use flappybird::pipe::DEFAULT_SEED;
+use flappybird::settings::{Settings, SETTINGS_FILE};
+use flappybird::sim::{Bot, Simulation};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+
+const HELP: &str = "\
+Plays lots of games without a window and prints how fast they ran and how they went.
+
+Usage: batch [options]
+
+Options:
+  --games <n>           How many games [default: 1000]
+  --threads <n>         How many games are played at once [default: 4]
+  --ticks <n>           The most updates each game gets [default: 36000]
+  --bot <name>          Who flaps: simple, idle, or a recorded run file [default: simple]
+  --seed <n>            The seed of the first game. Each game after it gets the next seed [default: 0]
+  --resources <dir>     Where the game's images, sounds and data are [default: ./resources]
+  --config <file>       The settings file [default: <resources>/settings.json]
+  --min-rate <n>        Fail if fewer than this many updates a second were played
+  --check-snapshots <n> At update n of each game, check saving and loading it doesn't change how it plays out
+  -h, --help            Print this
+";
+
+const DEFAULT_GAMES: u32 = 1000;
+const DEFAULT_THREADS: u32 = 4;
+const DEFAULT_TICKS: u32 = 36_000;
+/// How many bars the score histogram has.
+const HISTOGRAM_BARS: i64 = 10;
+/// How many characters wide the longest bar is.
+const HISTOGRAM_WIDTH: usize = 40;
+/// How long each game is played on for after a snapshot check.
+const SNAPSHOT_CHECK_TICKS: u32 = 600;
+
+struct BatchOptions {
+    games: u32,
+    threads: u32,
+    ticks: u32,
+    bot: String,
+    seed: u32,
+    resources: PathBuf,
+    config: Option<PathBuf>,
+    min_rate: Option<u32>,
+    check_snapshots: Option<u32>,
+}
+
+/// How one game went.
+struct Outcome {
+    score: i64,
+    ticks: u32,
+    cause: &'static str,
+    /// What went wrong when saving and loading the game, if it was checked.
+    snapshot_error: Option<String>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", HELP);
+            return;
+        }
+        Err(e) => {
+            println!("{}\n\n{}", e, HELP);
+            std::process::exit(2);
+        }
+    };
+
+    let atlas = match Atlas::load(&options.resources.join(ATLAS_FILE)) {
+        Ok(atlas) => Arc::new(atlas),
+        Err(e) => {
+            println!("Couldn't load {}: {}", ATLAS_FILE, e);
+            std::process::exit(1);
+        }
+    };
+    let settings_file = options
+        .config
+        .clone()
+        .unwrap_or_else(|| options.resources.join(SETTINGS_FILE));
+    let course = Settings::load(&settings_file).course;
+    let bot = Bot::from_name(&options.bot).unwrap_or_else(|e| {
+        println!("Couldn't load the bot {}: {}", options.bot, e);
+        std::process::exit(1);
+    });
+
+    let started = Instant::now();
+    let next_game = Arc::new(AtomicU32::new(0));
+    let (sender, outcomes) = mpsc::channel();
+    let threads: Vec<_> = (0..options.threads.max(1))
+        .map(|_| {
+            let (atlas, course, bot) = (atlas.clone(), course.clone(), bot.clone());
+            let (next_game, sender) = (next_game.clone(), sender.clone());
+            let (games, ticks, seed) = (options.games, options.ticks, options.seed);
+            let check_snapshots = options.check_snapshots;
+            std::thread::spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::SeqCst);
+                if game >= games {
+                    break;
+                }
+                let mut sim = Simulation::for_bot(&atlas, &course, seed.wrapping_add(game), &bot);
+                let mut snapshot_error = None;
+                if let Some(check_at) = check_snapshots {
+                    sim.play(&bot, check_at.min(ticks));
+                    snapshot_error = sim.check_snapshot(&bot, SNAPSHOT_CHECK_TICKS).err();
+                }
+                sim.play(&bot, ticks);
+                let outcome = Outcome {
+                    score: sim.score,
+                    ticks: sim.ticks,
+                    cause: sim.cause.map_or("still going", |cause| cause.name()),
+                    snapshot_error,
+                };
+                if sender.send(outcome).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+    let outcomes: Vec<Outcome> = outcomes.iter().collect();
+    for thread in threads {
+        thread.join().expect("A simulation thread panicked");
+    }
+    let secs = started.elapsed().as_secs_f64().max(std::f64::EPSILON);
+
+    let total_ticks: u64 = outcomes.iter().map(|o| o.ticks as u64).sum();
+    let rate = total_ticks as f64 / secs;
+    println!(
+        "{} games on {} threads: {} updates in {:.2}s, {:.0} updates a second",
+        outcomes.len(),
+        options.threads.max(1),
+        total_ticks,
+        secs,
+        rate
+    );
+    print_scores(&outcomes);
+    print_deaths(&outcomes);
+
+    let snapshot_errors: Vec<&String> = outcomes.iter().filter_map(|o| o.snapshot_error.as_ref()).collect();
+    if options.check_snapshots.is_some() {
+        println!("Snapshot checks: {} of {} failed", snapshot_errors.len(), outcomes.len());
+        for error in snapshot_errors.iter().take(5) {
+            println!("  {}", error);
+        }
+    }
+
+    let too_slow = options.min_rate.map_or(false, |min_rate| rate < min_rate as f64);
+    if too_slow {
+        println!("Slower than the minimum of {} updates a second", options.min_rate.unwrap_or(0));
+    }
+    if too_slow || !snapshot_errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn print_scores(outcomes: &[Outcome]) {
+    let mut scores: Vec<i64> = outcomes.iter().map(|o| o.score).collect();
+    if scores.is_empty() {
+        return;
+    }
+    scores.sort();
+    let percentile = |p: usize| scores[(scores.len() - 1) * p / 100];
+    let mean = scores.iter().sum::<i64>() as f64 / scores.len() as f64;
+    let max = scores[scores.len() - 1];
+    println!(
+        "Scores: min {}, median {}, mean {:.1}, 90th percentile {}, max {}",
+        scores[0],
+        percentile(50),
+        mean,
+        percentile(90),
+        max
+    );
+
+    // Each bar covers the same number of scores, at least one.
+    let bar_size = (max / HISTOGRAM_BARS + 1).max(1);
+    let mut bars = BTreeMap::new();
+    for score in &scores {
+        *bars.entry(score / bar_size).or_insert(0usize) += 1;
+    }
+    let tallest = bars.values().cloned().max().unwrap_or(1);
+    for (bar, count) in bars {
+        let low = bar * bar_size;
+        println!(
+            "  {:>5}-{:<5} {:<width$} {}",
+            low,
+            low + bar_size - 1,
+            "#".repeat(count * HISTOGRAM_WIDTH / tallest),
+            count,
+            width = HISTOGRAM_WIDTH
+        );
+    }
+}
+
+fn print_deaths(outcomes: &[Outcome]) {
+    let mut causes = BTreeMap::new();
+    for outcome in outcomes {
+        *causes.entry(outcome.cause).or_insert(0) += 1;
+    }
+    let causes: Vec<String> = causes
+        .iter()
+        .map(|(cause, count)| format!("{} {}", cause, count))
+        .collect();
+    println!("Deaths: {}", causes.join(", "));
+}
+
+/// Reads the command line, without the program name. None means help was asked for.
+fn parse(args: &[String]) -> Result<Option<BatchOptions>, String> {
+    let mut options = BatchOptions {
+        games: DEFAULT_GAMES,
+        threads: DEFAULT_THREADS,
+        ticks: DEFAULT_TICKS,
+        bot: "simple".to_string(),
+        seed: DEFAULT_SEED,
+        resources: PathBuf::from("./resources"),
+        config: None,
+        min_rate: None,
+        check_snapshots: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = arg.as_str();
+        if name == "-h" || name == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", name))?;
+        match name {
+            "--games" => options.games = parse_number(name, value)?,
+            "--threads" => options.threads = parse_number(name, value)?,
+            "--ticks" => options.ticks = parse_number(name, value)?,
+            "--bot" => options.bot = value.clone(),
+            "--seed" => options.seed = parse_number(name, value)?,
+            "--resources" => options.resources = PathBuf::from(value),
+            "--config" => options.config = Some(PathBuf::from(value)),
+            "--min-rate" => options.min_rate = Some(parse_number(name, value)?),
+            "--check-snapshots" => options.check_snapshots = Some(parse_number(name, value)?),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(Some(options))
+}
@@ -0,0 +1,188 @@
+//! A small leaderboard server for trying the online leaderboard without anything outside this repo.
+//! Runs are kept in a JSON file, and each one is played through from its recording before it's listed.
+
+use flappybird::atlas::{Atlas, ATLAS_FILE};
+use flappybird::cli::parse_number;
+use flappybird::online::{self, Entry, Request, Response, DEFAULT_PORT, MAX_TOP};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const HELP: &str = "\
+Keeps the online leaderboard: checks the runs sent to it and hands out the best ones.
+
+Usage: leaderboard_server [options]
+
+Options:
+  --port <n>            The port to listen on [default: 7878]
+  --file <file>         Where the runs are kept [default: leaderboard.json]
+  --resources <dir>     Where the game's data is, to play the runs through [default: ./resources]
+  -h, --help            Print this
+";
+
+struct ServerOptions {
+    port: u16,
+    file: PathBuf,
+    resources: PathBuf,
+}
+
+/// The runs, best first, and where they're kept.
+struct Board {
+    file: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Board {
+    fn load(file: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&file) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                println!("Couldn't read {}, starting an empty leaderboard: {}", file.display(), e);
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        Self { file, entries }
+    }
+
+    /// Lists the run and saves the board. Returns its place among the runs with or without assists like it,
+    /// counting from 1. Equal scores go after the ones already there.
+    fn add(&mut self, entry: Entry) -> usize {
+        let index = self.entries.iter().take_while(|e| e.score >= entry.score).count();
+        let place = self.entries[..index].iter().filter(|e| e.assisted == entry.assisted).count();
+        self.entries.insert(index, entry);
+        let saved = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(&self.file, text).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            println!("Couldn't save {}: {}", self.file.display(), e);
+        }
+        place + 1
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", HELP);
+            return;
+        }
+        Err(e) => {
+            println!("{}\n\n{}", e, HELP);
+            std::process::exit(2);
+        }
+    };
+
+    let atlas = match Atlas::load(&options.resources.join(ATLAS_FILE)) {
+        Ok(atlas) => Arc::new(atlas),
+        Err(e) => {
+            println!("Couldn't load {}: {}", ATLAS_FILE, e);
+            std::process::exit(1);
+        }
+    };
+    let board = Arc::new(Mutex::new(Board::load(options.file.clone())));
+    let listener = TcpListener::bind(("0.0.0.0", options.port)).unwrap_or_else(|e| {
+        println!("Couldn't listen on port {}: {}", options.port, e);
+        std::process::exit(1);
+    });
+    println!("Keeping the leaderboard in {} on port {}", options.file.display(), options.port);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let (atlas, board) = (atlas.clone(), board.clone());
+                std::thread::spawn(move || serve(stream, &atlas, &board));
+            }
+            Err(e) => println!("Couldn't take a connection: {}", e),
+        }
+    }
+}
+
+/// Answers every request on the connection until it's closed.
+fn serve(stream: TcpStream, atlas: &Atlas, board: &Mutex<Board>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            println!("Couldn't answer a connection: {}", e);
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => answer(request, atlas, board),
+            Err(e) => Response::Rejected {
+                reason: format!("Couldn't read the request: {}", e),
+            },
+        };
+        let written = serde_json::to_string(&response)
+            .map_err(|e| e.to_string())
+            .and_then(|text| writer.write_all((text + "\n").as_bytes()).map_err(|e| e.to_string()));
+        if written.is_err() {
+            return;
+        }
+    }
+}
+
+fn answer(request: Request, atlas: &Atlas, board: &Mutex<Board>) -> Response {
+    match request {
+        Request::Top { count, assisted } => {
+            let board = board.lock().expect("The leaderboard lock was poisoned");
+            let entries = board.entries.iter().filter(|entry| entry.assisted == assisted);
+            Response::Top {
+                entries: entries.take(count.min(MAX_TOP)).cloned().collect(),
+            }
+        }
+        // The run is played through before taking the lock, so slow checks don't hold up everyone else.
+        Request::Submit(submission) => match online::check(atlas, &submission) {
+            Ok(entry) => {
+                let assisted = if entry.assisted { " with assists" } else { "" };
+                println!("{} scored {} on seed {}{}", entry.name, entry.score, entry.seed, assisted);
+                let place = board.lock().expect("The leaderboard lock was poisoned").add(entry);
+                Response::Accepted { place }
+            }
+            Err(reason) => {
+                println!("Turned down a run from {}: {}", submission.name, reason);
+                Response::Rejected { reason }
+            }
+        },
+    }
+}
+
+/// Reads the command line, without the program name. None means help was asked for.
+fn parse(args: &[String]) -> Result<Option<ServerOptions>, String> {
+    let mut options = ServerOptions {
+        port: DEFAULT_PORT,
+        file: PathBuf::from("leaderboard.json"),
+        resources: PathBuf::from("./resources"),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = arg.as_str();
+        if name == "-h" || name == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", name))?;
+        match name {
+            "--port" => {
+                let port = parse_number(name, value)?;
+                if port > u16::max_value() as u32 {
+                    return Err(format!("--port needs a port number, got {}", value));
+                }
+                options.port = port as u16;
+            }
+            "--file" => options.file = PathBuf::from(value),
+            "--resources" => options.resources = PathBuf::from(value),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(Some(options))
+}
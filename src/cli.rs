@@ -0,0 +1,374 @@
+use crate::net::{Peer, DEFAULT_PORT};
+use crate::pipe::DEFAULT_SEED;
+use crate::profile;
+use crate::window::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::path::PathBuf;
+
+pub const HELP: &str = "\
+Flappy Crab (/)(;,,;)(/)!!!
+
+Usage: flappybird [command] [options]
+
+Commands:
+  play                  Play the game. This is what happens without a command.
+  replay <file>         Watch a recorded run, like saves/profiles/default/last_run.json.
+  simulate              Play one run without a window and print how it went.
+      --ticks <n>       Stop after this many updates, even if the crab is still going [default: 36000]
+      --bot <name>      Who flaps: simple, idle, or a recorded run file [default: simple]
+  bench                 Time many simulated runs.
+      --runs <n>        How many runs [default: 100]
+      --ticks <n>       The most updates each run gets [default: 36000]
+      --bot <name>      Who flaps, as for simulate [default: simple]
+  validate-assets       Check every image, sound and data file the game needs is there and readable.
+  leaderboard [server]  Print the best runs played here, or the ones on an online leaderboard server.
+  scan [seeds]          Check the courses of this many seeds can be finished [default: 1000]
+  host [port]           Race someone else's game. Waits for them to join on this port [default: 7777]
+  join <address>        Race the game hosting at this address, like 192.168.1.20 or localhost:7777
+  profiles              List the profiles.
+  export <file>         Write the profile to one file, to keep or move to another computer.
+  import <file> [name]  Add a profile from an exported file, optionally under another name.
+
+Options:
+  --resources <dir>     Where the game's images, sounds and data are [default: ./resources]
+  --config <file>       The settings file [default: <resources>/settings.json]
+  --window <w>x<h>      The size of the window [default: 800x600]
+  --fullscreen          Fill the screen
+  --seed <n>            The seed for the course [default: 0]
+  --mode <mode>         endless or campaign [default: endless]
+  --profile <name>      Who's playing. Without it, play asks and other commands use the profile played last.
+  -h, --help            Print this
+";
+
+const DEFAULT_TICKS: u32 = 36_000;
+const DEFAULT_RUNS: u32 = 100;
+const DEFAULT_SCAN_SEEDS: u32 = 1000;
+const DEFAULT_BOT: &str = "simple";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Endless,
+    Campaign,
+}
+
+/// The options every command shares.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub resources: PathBuf,
+    pub config: Option<PathBuf>,
+    pub window: (f32, f32),
+    pub fullscreen: bool,
+    pub seed: u32,
+    pub mode: Mode,
+    pub profile: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            resources: PathBuf::from("./resources"),
+            config: None,
+            window: (SCREEN_WIDTH, SCREEN_HEIGHT),
+            fullscreen: false,
+            seed: DEFAULT_SEED,
+            mode: Mode::Endless,
+            profile: None,
+        }
+    }
+}
+
+impl Options {
+    /// The settings file asked for, or else the profile's own, or else the one in the resources folder.
+    pub fn settings_file(&self) -> PathBuf {
+        self.config
+            .clone()
+            .or_else(|| self.profile.as_ref().and_then(|name| profile::settings_file(name)))
+            .unwrap_or_else(|| self.default_settings_file())
+    }
+
+    /// The settings file in the resources folder, which new profiles start with a copy of.
+    pub fn default_settings_file(&self) -> PathBuf {
+        self.resources.join(crate::settings::SETTINGS_FILE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play,
+    Replay(PathBuf),
+    Simulate { ticks: u32, bot: String },
+    Bench { runs: u32, ticks: u32, bot: String },
+    ValidateAssets,
+    Leaderboard(Option<String>),
+    Scan { seeds: u32 },
+    Versus(Peer),
+    ListProfiles,
+    ExportProfile(PathBuf),
+    ImportProfile { file: PathBuf, name: Option<String> },
+    Help,
+}
+
+/// Reads the command line, without the program name.
+pub fn parse(args: &[String]) -> Result<(Command, Options), String> {
+    let mut options = Options::default();
+    let mut positional = vec![];
+    let mut ticks = DEFAULT_TICKS;
+    let mut runs = DEFAULT_RUNS;
+    let mut bot = DEFAULT_BOT.to_string();
+    // The options only some commands take, so the others can turn them down.
+    let mut command_options = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        // Both `--seed 3` and `--seed=3` work.
+        let (name, inline_value) = match arg.find('=') {
+            Some(i) => (&arg[..i], Some(arg[i + 1..].to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match name {
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            "--fullscreen" => options.fullscreen = true,
+            "--resources" => options.resources = PathBuf::from(value()?),
+            "--config" => options.config = Some(PathBuf::from(value()?)),
+            "--window" => options.window = parse_window(&value()?)?,
+            "--seed" => options.seed = parse_number(name, &value()?)?,
+            "--mode" => {
+                options.mode = match value()?.as_str() {
+                    "endless" => Mode::Endless,
+                    "campaign" => Mode::Campaign,
+                    other => return Err(format!("Unknown mode {}, expected endless or campaign", other)),
+                }
+            }
+            "--profile" => {
+                let name = value()?;
+                profile::check_name(&name)?;
+                options.profile = Some(name);
+            }
+            "--ticks" => ticks = parse_number(name, &value()?)?,
+            "--runs" => runs = parse_number(name, &value()?)?,
+            "--bot" => bot = value()?,
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+        if let "--ticks" | "--runs" | "--bot" = name {
+            command_options.push(name);
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let word = positional.next();
+    let command = match word.as_ref().map(String::as_str) {
+        None | Some("play") => Command::Play,
+        Some("replay") => match positional.next() {
+            Some(file) => Command::Replay(PathBuf::from(file)),
+            None => return Err("replay needs a file to replay".to_string()),
+        },
+        Some("simulate") => Command::Simulate { ticks, bot },
+        Some("bench") => Command::Bench { runs, ticks, bot },
+        Some("validate-assets") => Command::ValidateAssets,
+        Some("leaderboard") => Command::Leaderboard(positional.next()),
+        Some("scan") => match positional.next() {
+            Some(seeds) => Command::Scan {
+                seeds: parse_number("scan", &seeds)?,
+            },
+            None => Command::Scan {
+                seeds: DEFAULT_SCAN_SEEDS,
+            },
+        },
+        Some("host") => match positional.next() {
+            Some(port) => Command::Versus(Peer::Host(parse_port(&port)?)),
+            None => Command::Versus(Peer::Host(DEFAULT_PORT)),
+        },
+        Some("join") => match positional.next() {
+            Some(address) => Command::Versus(Peer::Join(address)),
+            None => return Err("join needs the address of the game to join".to_string()),
+        },
+        Some("profiles") => Command::ListProfiles,
+        Some("export") => match positional.next() {
+            Some(file) => Command::ExportProfile(PathBuf::from(file)),
+            None => return Err("export needs a file to write the profile to".to_string()),
+        },
+        Some("import") => match positional.next() {
+            Some(file) => Command::ImportProfile {
+                file: PathBuf::from(file),
+                name: positional.next(),
+            },
+            None => return Err("import needs an exported profile to import".to_string()),
+        },
+        Some("help") => Command::Help,
+        Some(other) => return Err(format!("Unknown command {}", other)),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("Didn't expect {}", extra));
+    }
+    let takes: &[&str] = match command {
+        Command::Simulate { .. } => &["--ticks", "--bot"],
+        Command::Bench { .. } => &["--runs", "--ticks", "--bot"],
+        _ => &[],
+    };
+    if let Some(option) = command_options.iter().find(|option| !takes.contains(option)) {
+        return Err(format!("{} doesn't work with {}", option, word.unwrap_or_else(|| "play".to_string())));
+    }
+    Ok((command, options))
+}
+
+pub fn parse_number(name: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a whole number, got {}", name, value))
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .map_err(|_| format!("host needs a port number, got {}", value))
+}
+
+/// Reads a window size like `1024x768`.
+fn parse_window(value: &str) -> Result<(f32, f32), String> {
+    let mut sides = value.split('x').map(|side| side.parse::<f32>());
+    match (sides.next(), sides.next(), sides.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0.0 && height > 0.0 => Ok((width, height)),
+        _ => Err(format!("--window needs a size like 1024x768, got {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<(Command, Options), String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    fn command(line: &str) -> Command {
+        parse_str(line).unwrap().0
+    }
+
+    #[test]
+    fn no_command_plays() {
+        let (command, options) = parse_str("").unwrap();
+        assert_eq!(command, Command::Play);
+        assert_eq!(options.seed, DEFAULT_SEED);
+        assert_eq!(options.mode, Mode::Endless);
+        assert!(!options.fullscreen);
+    }
+
+    #[test]
+    fn shared_options() {
+        let (command, options) =
+            parse_str("play --seed 7 --window=1024x768 --fullscreen --mode campaign --resources res --config a.json")
+                .unwrap();
+        assert_eq!(command, Command::Play);
+        assert_eq!(options.seed, 7);
+        assert_eq!(options.window, (1024.0, 768.0));
+        assert!(options.fullscreen);
+        assert_eq!(options.mode, Mode::Campaign);
+        assert_eq!(options.resources, PathBuf::from("res"));
+        assert_eq!(options.config, Some(PathBuf::from("a.json")));
+    }
+
+    #[test]
+    fn options_can_come_before_the_command() {
+        assert_eq!(command("--seed 3 scan 20"), Command::Scan { seeds: 20 });
+    }
+
+    #[test]
+    fn simulate_and_bench_options() {
+        assert_eq!(
+            command("simulate"),
+            Command::Simulate {
+                ticks: DEFAULT_TICKS,
+                bot: DEFAULT_BOT.to_string()
+            }
+        );
+        assert_eq!(
+            command("simulate --ticks 50 --bot idle"),
+            Command::Simulate {
+                ticks: 50,
+                bot: "idle".to_string()
+            }
+        );
+        assert_eq!(
+            command("bench --runs=5 --ticks=60"),
+            Command::Bench {
+                runs: 5,
+                ticks: 60,
+                bot: DEFAULT_BOT.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn options_for_other_commands_are_turned_down() {
+        assert!(parse_str("play --runs 5").is_err());
+        assert!(parse_str("--ticks 5").is_err());
+        assert!(parse_str("simulate --runs 5").is_err());
+        assert!(parse_str("scan --bot idle").is_err());
+    }
+
+    #[test]
+    fn commands_with_arguments() {
+        assert_eq!(command("replay run.json"), Command::Replay(PathBuf::from("run.json")));
+        assert_eq!(command("leaderboard"), Command::Leaderboard(None));
+        assert_eq!(command("leaderboard crabs.net"), Command::Leaderboard(Some("crabs.net".to_string())));
+        assert_eq!(command("scan"), Command::Scan { seeds: DEFAULT_SCAN_SEEDS });
+        assert_eq!(command("host"), Command::Versus(Peer::Host(DEFAULT_PORT)));
+        assert_eq!(command("host 9000"), Command::Versus(Peer::Host(9000)));
+        assert_eq!(command("join localhost:7777"), Command::Versus(Peer::Join("localhost:7777".to_string())));
+        assert_eq!(command("export crab.json"), Command::ExportProfile(PathBuf::from("crab.json")));
+        assert_eq!(
+            command("import crab.json Sam"),
+            Command::ImportProfile {
+                file: PathBuf::from("crab.json"),
+                name: Some("Sam".to_string())
+            }
+        );
+        assert_eq!(command("help"), Command::Help);
+        assert_eq!(command("validate-assets --help"), Command::Help);
+    }
+
+    #[test]
+    fn unknown_commands_and_options() {
+        assert!(parse_str("fly").is_err());
+        assert!(parse_str("--speed 3").is_err());
+        assert!(parse_str("-x").is_err());
+        assert!(parse_str("play now").is_err());
+        assert!(parse_str("scan 10 20").is_err());
+    }
+
+    #[test]
+    fn missing_values() {
+        for line in &["--seed", "--window", "--mode", "--resources", "--config", "--profile", "simulate --ticks"] {
+            let error = parse_str(line).unwrap_err();
+            assert!(error.ends_with("needs a value"), "{}: {}", line, error);
+        }
+        assert!(parse_str("replay").is_err());
+        assert!(parse_str("join").is_err());
+        assert!(parse_str("export").is_err());
+        assert!(parse_str("import").is_err());
+    }
+
+    #[test]
+    fn bad_values() {
+        assert!(parse_str("--seed -1").is_err());
+        assert!(parse_str("--seed lots").is_err());
+        assert!(parse_str("--window 800").is_err());
+        assert!(parse_str("--window 0x600").is_err());
+        assert!(parse_str("--window 800x600x2").is_err());
+        assert!(parse_str("--mode story").is_err());
+        assert!(parse_str("host 70000").is_err());
+        assert!(parse_str("scan many").is_err());
+        assert!(parse_str("--profile ../saves").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::storage;
+use crate::window::SCREEN_WIDTH;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+const WALLET_FILE: &str = "wallet.json";
+/// How many updates each power up lasts for.
+const POWER_UP_TICKS: u32 = 600;
+/// How long the crab can't be hurt for after its shield breaks.
+const SHIELD_BREAK_TICKS: u32 = 60;
+/// How big the crab is while shrunk.
+const SHRUNK_SIZE: f32 = 0.6;
+/// How fast the world moves during slow motion.
+const SLOW_MOTION_SPEED: f32 = 0.5;
+/// How close a pearl has to be before the magnet pulls it in.
+pub const MAGNET_RADIUS: f32 = 60.0;
+pub const MAGNET_PULL: f32 = 2.0;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    /// Absorbs one hit.
+    Shield,
+    /// Slows down everything but the crab.
+    SlowMotion,
+    /// Pulls nearby pearls towards the crab.
+    Magnet,
+    /// Makes the crab smaller.
+    Shrink,
+}
+
+pub const POWER_UPS: [PowerUp; 4] = [
+    PowerUp::Shield,
+    PowerUp::SlowMotion,
+    PowerUp::Magnet,
+    PowerUp::Shrink,
+];
+
+impl PowerUp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::Shield => "Shield",
+            PowerUp::SlowMotion => "Slow motion",
+            PowerUp::Magnet => "Magnet",
+            PowerUp::Shrink => "Shrink",
+        }
+    }
+
+    pub fn sprite_name(&self) -> &'static str {
+        match self {
+            PowerUp::Shield => "shield.png",
+            PowerUp::SlowMotion => "slow_motion.png",
+            PowerUp::Magnet => "magnet.png",
+            PowerUp::Shrink => "shrink.png",
+        }
+    }
+}
+
+/// Something the crab can pick up.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collectible {
+    Pearl,
+    PowerUp(PowerUp),
+}
+
+/// The pearls collected over every run. This is what gets saved.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Wallet {
+    pub pearls: u64,
+}
+
+impl Wallet {
+    pub fn load() -> Self {
+        storage::load(WALLET_FILE)
+    }
+
+    pub fn save(&self) {
+        storage::save(WALLET_FILE, self);
+    }
+}
+
+/// The power ups the crab has right now and how long each has left.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PowerUps {
+    active: Vec<(PowerUp, u32)>,
+    /// Counts down after the shield breaks so the crab can get clear.
+    invulnerable: u32,
+}
+
+impl PowerUps {
+    pub fn new() -> Self {
+        PowerUps::default()
+    }
+
+    /// Starts a power up, or starts it over if it's already going.
+    pub fn activate(&mut self, power_up: PowerUp) {
+        self.active.retain(|(p, _)| *p != power_up);
+        self.active.push((power_up, POWER_UP_TICKS));
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.active.iter().any(|(p, _)| *p == power_up)
+    }
+
+    pub fn update(&mut self) {
+        for (_, ticks) in &mut self.active {
+            *ticks -= 1;
+        }
+        self.active.retain(|(_, ticks)| *ticks > 0);
+        self.invulnerable = self.invulnerable.saturating_sub(1);
+    }
+
+    /// Called when the crab hits something. Returns true if the hit was absorbed.
+    pub fn absorb_hit(&mut self) -> bool {
+        if self.invulnerable > 0 {
+            return true;
+        }
+        if self.is_active(PowerUp::Shield) {
+            self.active.retain(|(p, _)| *p != PowerUp::Shield);
+            self.invulnerable = SHIELD_BREAK_TICKS;
+            return true;
+        }
+        false
+    }
+
+    pub fn crab_size(&self) -> f32 {
+        if self.is_active(PowerUp::Shrink) {
+            SHRUNK_SIZE
+        } else {
+            1.0
+        }
+    }
+
+    /// How fast the pipes and everything else move compared to normal.
+    pub fn world_speed(&self) -> f32 {
+        if self.is_active(PowerUp::SlowMotion) {
+            SLOW_MOTION_SPEED
+        } else {
+            1.0
+        }
+    }
+
+    /// Draws the pearl counts and a bar for how long each power up has left.
+    pub fn draw_hud(&self, ctx: &mut Context, run_pearls: u64, wallet: &Wallet) -> GameResult {
+        let x = SCREEN_WIDTH - 210.0;
+        let pearls = Text::new(format!("Pearls: {} ({})", run_pearls, wallet.pearls));
+        graphics::draw(ctx, &pearls, (Point2::new(x, 10.0), graphics::WHITE))?;
+
+        for (i, (power_up, ticks)) in self.active.iter().enumerate() {
+            let y = 34.0 + 24.0 * i as f32;
+            let label = Text::new(power_up.name());
+            graphics::draw(ctx, &label, (Point2::new(x, y), graphics::WHITE))?;
+
+            let remaining = *ticks as f32 / POWER_UP_TICKS as f32;
+            let bar = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(x + 110.0, y + 4.0, 90.0 * remaining, 8.0),
+                graphics::WHITE,
+            )?;
+            graphics::draw(ctx, &bar, graphics::DrawParam::new())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::atlas::Sprite;
+use crate::events::DeathCause;
+use ggez::graphics;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+/// How far a pipe reaches past the gap. Long enough to cover any screen.
+const PIPE_REACH: f32 = 10_000.0;
+
+/// Where an entity is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transform {
+    #[serde(with = "crate::shapes::point")]
+    pub position: Point2<f32>,
+}
+
+impl Transform {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            position: Point2::new(x, y),
+        }
+    }
+}
+
+/// How far an entity moves every update while the game is being played.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Velocity(#[serde(with = "crate::shapes::vector")] pub Vector2<f32>);
+
+/// The boxes that kill the crab, relative to the entity's position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collider {
+    #[serde(with = "crate::shapes::rects")]
+    pub bounds: Vec<graphics::Rect>,
+    /// What the crab died of, if it hits this.
+    pub cause: DeathCause,
+}
+
+impl Collider {
+    pub fn new(bounds: graphics::Rect, cause: DeathCause) -> Self {
+        Self {
+            bounds: vec![bounds],
+            cause,
+        }
+    }
+
+    /// The collider moved to where the entity is.
+    pub fn at(&self, position: Point2<f32>) -> Vec<graphics::Rect> {
+        self.bounds
+            .iter()
+            .map(|bounds| {
+                let mut rect = *bounds;
+                rect.x += position.x;
+                rect.y += position.y;
+                rect
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Scorer {
+    ReadyToScore,
+    Scored,
+}
+
+/// Entities that go off the left of the screen are moved this far right to come around again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recycler {
+    pub jump_distance: f32,
+}
+
+/// A pipe coming down from above and a pipe coming up from below, with a gap between them.
+/// The entity's position is the left edge of the pipes, halfway down the gap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipePair {
+    pub gap_height: f32,
+    pub body: Sprite,
+    pub tip: Sprite,
+}
+
+impl PipePair {
+    pub fn new(gap_height: f32, body: Sprite, tip: Sprite) -> Self {
+        Self {
+            gap_height,
+            body,
+            tip,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.tip.width
+    }
+
+    /// One box above the gap and one below it.
+    pub fn collider(&self) -> Collider {
+        let half_gap = self.gap_height / 2.0;
+        Collider {
+            bounds: vec![
+                graphics::Rect::new(0.0, -half_gap - PIPE_REACH, self.width(), PIPE_REACH),
+                graphics::Rect::new(0.0, half_gap, self.width(), PIPE_REACH),
+            ],
+            cause: DeathCause::Pipe,
+        }
+    }
+
+    /// Draws as many pipe segments as it takes to reach the top and bottom of the screen.
+    pub fn draw(&self, position: Point2<f32>, world_height: f32, batch: &mut SpriteBatch, colour: graphics::Color) {
+        let half_gap = self.gap_height / 2.0;
+        let gap_top = position.y - half_gap;
+        let gap_bottom = position.y + half_gap;
+
+        let mut y = gap_bottom + self.tip.height;
+        while y < world_height {
+            batch.add(self.body.draw_params(Point2::new(position.x, y)).color(colour));
+            y += self.body.height;
+        }
+        batch.add(self.tip.draw_params(Point2::new(position.x, gap_bottom)).color(colour));
+
+        let mut y = gap_top - self.tip.height - self.body.height;
+        while y + self.body.height > 0.0 {
+            batch.add(self.body.draw_params(Point2::new(position.x, y)).color(colour));
+            y -= self.body.height;
+        }
+        let mut top_tip = self.tip.clone();
+        top_tip.scale.y = -1.0;
+        batch.add(top_tip.draw_params(Point2::new(position.x, gap_top)).color(colour));
+    }
+}
+
+/// How a scripted obstacle moves on top of its velocity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Motion {
+    /// Moves up and down.
+    Bob { amplitude: f32, speed: f32 },
+    /// Closes a pipe pair's gap down to `closed_gap` and opens it again.
+    Jaw { open_gap: f32, closed_gap: f32, speed: f32 },
+    /// Dives `depth` down and comes back up over `duration` updates.
+    Swoop { depth: f32, duration: f32 },
+}
+
+/// Plays a `Motion` one update at a time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MotionScript {
+    pub motion: Motion,
+    pub ticks: u32,
+    /// How far the script has moved the entity from where it started.
+    pub offset: f32,
+}
+
+impl MotionScript {
+    pub fn new(motion: Motion) -> Self {
+        Self {
+            motion,
+            ticks: 0,
+            offset: 0.0,
+        }
+    }
+}
+
+/// Flips through sprites to animate an entity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation {
+    pub frames: Vec<Sprite>,
+    pub ticks_per_frame: u32,
+    pub ticks: u32,
+}
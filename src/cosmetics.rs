@@ -0,0 +1,483 @@
+//! Skins, hats and trails for the crab. What there is comes from the cosmetics file in the resources folder,
+//! and what has been bought and what the crab is wearing is saved with the profile.
+//! Skins only change how the crab looks. Its hit box stays the same, so runs play out the same whatever it wears.
+
+use crate::achievements::Achievements;
+use crate::atlas::{Atlas, Sprite};
+use crate::collectibles::Wallet;
+use crate::crab::PlayerEntity;
+use crate::pipe::pipe_velocity;
+use crate::storage;
+use crate::window::{SCALE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use ggez::event::KeyCode;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawParam, FilterMode, Image, Text};
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+pub const COSMETICS_FILE: &str = "cosmetics.json";
+const SAVE_FILE: &str = "wardrobe.json";
+/// How many spots the trail has, and how many updates apart they're left.
+const TRAIL_LENGTH: usize = 10;
+const TRAIL_EVERY: u32 = 3;
+const TRAIL_ALPHA: f32 = 0.6;
+/// How much bigger the crab is in the wardrobe than in the game.
+const PREVIEW_SCALE: f32 = 2.0;
+/// How many cosmetics the wardrobe lists at once.
+const SHOWN_COSMETICS: usize = 14;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    Skin,
+    Hat,
+    Trail,
+}
+
+impl Slot {
+    fn name(&self) -> &'static str {
+        match self {
+            Slot::Skin => "skin",
+            Slot::Hat => "hat",
+            Slot::Trail => "trail",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Unlock {
+    Free,
+    /// Bought with this many pearls.
+    Pearls(u64),
+    /// Comes with the achievement with this id.
+    Achievement(String),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CosmeticData {
+    pub id: String,
+    pub name: String,
+    pub slot: Slot,
+    /// The sprites it's drawn with. Skins have two, rising then falling. Hats and trails use the first.
+    pub frames: Vec<String>,
+    /// Another atlas in the resources folder the frames are in, like `hats` for `hats.json` and `hats.png`.
+    /// Skins have to be in the game's own atlas.
+    #[serde(default)]
+    pub atlas: Option<String>,
+    #[serde(default)]
+    pub tint: Option<[u8; 3]>,
+    /// Where a hat sits, from the middle of the crab.
+    #[serde(default)]
+    pub offset: (f32, f32),
+    pub unlock: Unlock,
+}
+
+/// Everything that can be worn, as described in the cosmetics file.
+#[derive(Deserialize, Debug)]
+pub struct Catalogue {
+    pub cosmetics: Vec<CosmeticData>,
+}
+
+impl Catalogue {
+    pub fn load(cosmetics_file: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(cosmetics_file).map_err(|e| e.to_string())?;
+        let catalogue: Catalogue = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        let mut ids = HashSet::new();
+        for cosmetic in &catalogue.cosmetics {
+            if !ids.insert(&cosmetic.id) {
+                return Err(format!("There's more than one cosmetic called {}", cosmetic.id));
+            }
+            if cosmetic.frames.is_empty() {
+                return Err(format!("{} has no frames", cosmetic.id));
+            }
+            if cosmetic.slot == Slot::Skin && (cosmetic.frames.len() != 2 || cosmetic.atlas.is_some()) {
+                return Err(format!("{} needs two frames from the game's own atlas to be a skin", cosmetic.id));
+            }
+        }
+        Ok(catalogue)
+    }
+
+    /// The frames that have to be in the game's own atlas.
+    pub fn atlas_frames(&self) -> impl Iterator<Item = &str> {
+        self.cosmetics
+            .iter()
+            .filter(|cosmetic| cosmetic.atlas.is_none())
+            .flat_map(|cosmetic| cosmetic.frames.iter().map(String::as_str))
+    }
+}
+
+/// What has been bought and what the crab is wearing. This is what gets saved.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+struct Picks {
+    bought: HashSet<String>,
+    skin: Option<String>,
+    hat: Option<String>,
+    trail: Option<String>,
+}
+
+impl Picks {
+    fn worn(&self, slot: Slot) -> &Option<String> {
+        match slot {
+            Slot::Skin => &self.skin,
+            Slot::Hat => &self.hat,
+            Slot::Trail => &self.trail,
+        }
+    }
+
+    fn worn_mut(&mut self, slot: Slot) -> &mut Option<String> {
+        match slot {
+            Slot::Skin => &mut self.skin,
+            Slot::Hat => &mut self.hat,
+            Slot::Trail => &mut self.trail,
+        }
+    }
+}
+
+/// A cosmetic's sprites, ready to draw.
+#[derive(Clone)]
+struct Piece {
+    sprites: Vec<Sprite>,
+    tint: Color,
+    offset: Vector2<f32>,
+    /// The other atlas it's drawn from, if it isn't in the game's own.
+    atlas: Option<String>,
+}
+
+/// The cosmetics, what the player has and what the crab is wearing.
+pub struct Wardrobe {
+    catalogue: Vec<CosmeticData>,
+    pieces: HashMap<String, Piece>,
+    /// The crab without a skin.
+    plain: Piece,
+    /// A batch for each other atlas cosmetics are drawn from.
+    batches: HashMap<String, SpriteBatch>,
+    picks: Picks,
+    /// Where the crab has been lately, for the trail.
+    trail: VecDeque<Point2<f32>>,
+    trail_ticks: u32,
+    /// The cosmetic picked out in the wardrobe.
+    selected: usize,
+    /// What happened the last time the player tried something on, shown in the wardrobe.
+    message: Option<String>,
+}
+
+impl Wardrobe {
+    /// Reads the cosmetics file and any other atlases it uses from the resources folder.
+    /// Cosmetics that can't be drawn are left out.
+    pub fn new(ctx: &mut Context, resources: &Path, atlas: &Atlas) -> Self {
+        let catalogue = Catalogue::load(&resources.join(COSMETICS_FILE)).unwrap_or_else(|e| {
+            println!("Couldn't load {}, there's nothing to wear: {}", COSMETICS_FILE, e);
+            Catalogue { cosmetics: vec![] }
+        });
+        let mut atlases = HashMap::new();
+        let mut batches = HashMap::new();
+        let mut pieces = HashMap::new();
+        for cosmetic in &catalogue.cosmetics {
+            let other: &Atlas = match &cosmetic.atlas {
+                Some(name) if !atlases.contains_key(name) => match load_atlas(ctx, resources, name) {
+                    Ok((other, batch)) => {
+                        batches.insert(name.clone(), batch);
+                        atlases.entry(name.clone()).or_insert(other)
+                    }
+                    Err(e) => {
+                        println!("Couldn't load the {} atlas for {}: {}", name, cosmetic.id, e);
+                        continue;
+                    }
+                },
+                Some(name) => &atlases[name],
+                None => atlas,
+            };
+            match piece(other, cosmetic) {
+                Ok(piece) => {
+                    pieces.insert(cosmetic.id.clone(), piece);
+                }
+                Err(e) => println!("Can't wear {}: {}", cosmetic.id, e),
+            }
+        }
+        let plain = Piece {
+            sprites: vec![atlas.create_sprite("crab0.png"), atlas.create_sprite("crab1.png")],
+            tint: graphics::WHITE,
+            offset: Vector2::new(0.0, 0.0),
+            atlas: None,
+        };
+        Self {
+            catalogue: catalogue.cosmetics,
+            pieces,
+            plain,
+            batches,
+            picks: storage::load(SAVE_FILE),
+            trail: VecDeque::new(),
+            trail_ticks: 0,
+            selected: 0,
+            message: None,
+        }
+    }
+
+    fn is_unlocked(&self, cosmetic: &CosmeticData, achievements: &Achievements) -> bool {
+        match &cosmetic.unlock {
+            Unlock::Free => true,
+            Unlock::Pearls(_) => self.picks.bought.contains(&cosmetic.id),
+            Unlock::Achievement(id) => achievements.unlocked.achievements.contains(id),
+        }
+    }
+
+    /// Puts the crab's skin on it and starts the trail afresh. Done at the start of every run.
+    pub fn dress(&mut self, player: &mut PlayerEntity) {
+        let skin = worn(&self.picks, &self.pieces, Slot::Skin).unwrap_or(&self.plain);
+        player.player_sprites = skin.sprites.clone();
+        player.tint = skin.tint;
+        self.trail.clear();
+    }
+
+    /// Moves the trail along with the world and leaves a new spot every few updates.
+    pub fn update(&mut self, player: &PlayerEntity, speed: f32) {
+        if self.picks.trail.is_none() {
+            return;
+        }
+        for spot in &mut self.trail {
+            spot.x += pipe_velocity() * speed;
+        }
+        self.trail_ticks += 1;
+        if self.trail_ticks % TRAIL_EVERY == 0 {
+            self.trail.push_front(player.position);
+            self.trail.truncate(TRAIL_LENGTH);
+        }
+    }
+
+    /// Draws the trail, which goes behind the crab.
+    pub fn draw_trail(&mut self, batch: &mut SpriteBatch) {
+        if let Some(trail) = worn(&self.picks, &self.pieces, Slot::Trail) {
+            let batch = batch_for(&mut self.batches, batch, &trail.atlas);
+            for (i, spot) in self.trail.iter().enumerate() {
+                batch.add(trail_param(trail, *spot, i));
+            }
+        }
+    }
+
+    /// Draws the hat, which goes on top of the crab.
+    pub fn draw_hat(&mut self, player: &PlayerEntity, batch: &mut SpriteBatch) {
+        if let Some(hat) = worn(&self.picks, &self.pieces, Slot::Hat) {
+            let crab = player.draw_param();
+            let centre = Point2::new(crab.dest.x, crab.dest.y);
+            batch_for(&mut self.batches, batch, &hat.atlas).add(hat_param(hat, centre, crab.rotation, player.size));
+        }
+    }
+
+    /// Draws whatever came from other atlases since last time, on top of everything else.
+    pub fn draw_batches(&mut self, ctx: &mut Context, param: DrawParam) -> GameResult {
+        for batch in self.batches.values_mut() {
+            graphics::draw(ctx, batch, param)?;
+            batch.clear();
+        }
+        Ok(())
+    }
+
+    /// Handles a key in the wardrobe. Returns true if the crab is wearing something else now.
+    pub fn key(&mut self, keycode: KeyCode, wallet: &mut Wallet, achievements: &Achievements) -> bool {
+        match keycode {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.catalogue.len().saturating_sub(1)),
+            KeyCode::Return => return self.choose(wallet, achievements),
+            _ => {}
+        }
+        false
+    }
+
+    /// Wears the picked out cosmetic, or takes it off, buying it first if it's for sale.
+    fn choose(&mut self, wallet: &mut Wallet, achievements: &Achievements) -> bool {
+        let cosmetic = match self.catalogue.get(self.selected) {
+            Some(cosmetic) => cosmetic.clone(),
+            None => return false,
+        };
+        if !self.pieces.contains_key(&cosmetic.id) {
+            self.message = Some(format!("{} can't be drawn", cosmetic.name));
+            return false;
+        }
+        if !self.is_unlocked(&cosmetic, achievements) {
+            match &cosmetic.unlock {
+                Unlock::Pearls(price) if wallet.pearls >= *price => {
+                    wallet.pearls -= price;
+                    wallet.save();
+                    self.picks.bought.insert(cosmetic.id.clone());
+                }
+                Unlock::Pearls(price) => {
+                    self.message = Some(format!("{} costs {} pearls", cosmetic.name, price));
+                    return false;
+                }
+                Unlock::Achievement(id) => {
+                    let name = achievements.name_of(id).unwrap_or(id);
+                    self.message = Some(format!("Unlock {} to wear {}", name, cosmetic.name));
+                    return false;
+                }
+                Unlock::Free => {}
+            }
+        }
+        let wearing = self.picks.worn_mut(cosmetic.slot);
+        // Hats and trails come off again. The crab always has a skin on.
+        if wearing.as_ref() == Some(&cosmetic.id) && cosmetic.slot != Slot::Skin {
+            *wearing = None;
+            self.message = Some(format!("Took off {}", cosmetic.name));
+        } else {
+            *wearing = Some(cosmetic.id.clone());
+            self.message = Some(format!("Wearing {}", cosmetic.name));
+        }
+        storage::save(SAVE_FILE, &self.picks);
+        true
+    }
+
+    fn label(&self, cosmetic: &CosmeticData, achievements: &Achievements) -> String {
+        let state = if self.picks.worn(cosmetic.slot).as_ref() == Some(&cosmetic.id) {
+            "wearing".to_string()
+        } else if self.is_unlocked(cosmetic, achievements) {
+            "yours".to_string()
+        } else {
+            match &cosmetic.unlock {
+                Unlock::Pearls(price) => format!("{} pearls", price),
+                Unlock::Achievement(id) => format!("unlock {}", achievements.name_of(id).unwrap_or(id)),
+                Unlock::Free => "yours".to_string(),
+            }
+        };
+        format!("{} ({}): {}", cosmetic.name, cosmetic.slot.name(), state)
+    }
+
+    /// Draws the wardrobe page: what there is to wear, and the crab trying on the one picked out.
+    pub fn draw_page(
+        &mut self,
+        ctx: &mut Context,
+        batch: &mut SpriteBatch,
+        wallet: &Wallet,
+        achievements: &Achievements,
+    ) -> GameResult {
+        let header = Text::new(format!("Wardrobe   Pearls: {}", wallet.pearls));
+        graphics::draw(ctx, &header, (Point2::new(40.0, 40.0), graphics::WHITE))?;
+
+        let locked_colour = graphics::Color::new(1.0, 1.0, 1.0, 0.4);
+        let first = self.selected.saturating_sub(SHOWN_COSMETICS - 1);
+        let mut y = 80.0;
+        for (i, cosmetic) in self.catalogue.iter().enumerate().skip(first).take(SHOWN_COSMETICS) {
+            let marker = if i == self.selected { ">" } else { " " };
+            let colour = if self.is_unlocked(cosmetic, achievements) {
+                graphics::WHITE
+            } else {
+                locked_colour
+            };
+            let text = Text::new(format!("{} {}", marker, self.label(cosmetic, achievements)));
+            graphics::draw(ctx, &text, (Point2::new(40.0, y), colour))?;
+            y += 24.0;
+        }
+        if let Some(message) = &self.message {
+            let text = Text::new(message.as_str());
+            graphics::draw(ctx, &text, (Point2::new(40.0, SCREEN_HEIGHT - 60.0), graphics::WHITE))?;
+        }
+        let hint = Text::new("Up/Down: choose   Return: wear or buy   W: close");
+        graphics::draw(ctx, &hint, (Point2::new(40.0, SCREEN_HEIGHT - 30.0), graphics::WHITE))?;
+
+        self.draw_preview(ctx, batch)
+    }
+
+    /// The crab in what it's wearing, with the cosmetic picked out swapped in.
+    fn draw_preview(&mut self, ctx: &mut Context, batch: &mut SpriteBatch) -> GameResult {
+        let (picks, pieces) = (&self.picks, &self.pieces);
+        let trying = self
+            .catalogue
+            .get(self.selected)
+            .and_then(|cosmetic| Some((cosmetic.slot, pieces.get(&cosmetic.id)?)));
+        let look = |slot: Slot| match trying {
+            Some((trying_slot, piece)) if trying_slot == slot => Some(piece),
+            _ => worn(picks, pieces, slot),
+        };
+        let (skin, hat, trail) = (look(Slot::Skin).unwrap_or(&self.plain), look(Slot::Hat), look(Slot::Trail));
+
+        let centre = Point2::new(0.0, 0.0);
+        if let Some(trail) = trail {
+            let batch = batch_for(&mut self.batches, batch, &trail.atlas);
+            for i in 0..3 {
+                let spot = Point2::new(-(i as f32 + 1.0) * skin.sprites[0].width, 0.0);
+                batch.add(trail_param(trail, spot, i));
+            }
+        }
+        batch.add(
+            skin.sprites[0]
+                .draw_params(centre)
+                .offset(Point2::new(0.5, 0.5))
+                .color(skin.tint),
+        );
+        if let Some(hat) = hat {
+            batch_for(&mut self.batches, batch, &hat.atlas).add(hat_param(hat, centre, 0.0, 1.0));
+        }
+
+        let scale = SCALE * PREVIEW_SCALE;
+        let param = DrawParam::new()
+            .dest(Point2::new(SCREEN_WIDTH * 0.75, SCREEN_HEIGHT / 2.0))
+            .scale(Vector2::new(scale, scale));
+        graphics::draw(ctx, batch, param)?;
+        batch.clear();
+        self.draw_batches(ctx, param)
+    }
+}
+
+/// What's being worn in the slot, if anything.
+fn worn<'a>(picks: &Picks, pieces: &'a HashMap<String, Piece>, slot: Slot) -> Option<&'a Piece> {
+    picks.worn(slot).as_ref().and_then(|id| pieces.get(id))
+}
+
+/// The batch for the atlas a piece is drawn from.
+fn batch_for<'a>(
+    batches: &'a mut HashMap<String, SpriteBatch>,
+    main: &'a mut SpriteBatch,
+    atlas: &Option<String>,
+) -> &'a mut SpriteBatch {
+    match atlas.as_ref().and_then(move |name| batches.get_mut(name)) {
+        Some(batch) => batch,
+        None => main,
+    }
+}
+
+fn trail_param(trail: &Piece, spot: Point2<f32>, age: usize) -> DrawParam {
+    let fade = 1.0 - age as f32 / TRAIL_LENGTH as f32;
+    let tint = Color::new(trail.tint.r, trail.tint.g, trail.tint.b, trail.tint.a * TRAIL_ALPHA * fade);
+    trail.sprites[0]
+        .draw_params(spot)
+        .offset(Point2::new(0.5, 0.5))
+        .scale(Vector2::new(fade, fade))
+        .color(tint)
+}
+
+/// A hat sitting on a crab whose middle is at `centre`, tilted and sized along with it.
+fn hat_param(hat: &Piece, centre: Point2<f32>, angle: f32, size: f32) -> DrawParam {
+    let (sin, cos) = angle.sin_cos();
+    let (x, y) = (hat.offset.x * size, hat.offset.y * size);
+    let dest = Point2::new(centre.x + x * cos - y * sin, centre.y + x * sin + y * cos);
+    hat.sprites[0]
+        .draw_params(dest)
+        .offset(Point2::new(0.5, 0.5))
+        .scale(Vector2::new(size, size))
+        .rotation(angle)
+        .color(hat.tint)
+}
+
+fn piece(atlas: &Atlas, cosmetic: &CosmeticData) -> Result<Piece, String> {
+    if let Some(missing) = cosmetic.frames.iter().find(|frame| !atlas.has_sprite(frame)) {
+        return Err(format!("there's no sprite called {}", missing));
+    }
+    let [r, g, b] = cosmetic.tint.unwrap_or([255, 255, 255]);
+    Ok(Piece {
+        sprites: cosmetic.frames.iter().map(|frame| atlas.create_sprite(frame)).collect(),
+        tint: Color::from_rgb(r, g, b),
+        offset: Vector2::new(cosmetic.offset.0, cosmetic.offset.1),
+        atlas: cosmetic.atlas.clone(),
+    })
+}
+
+/// Another atlas in the resources folder, and a batch to draw from its image.
+fn load_atlas(ctx: &mut Context, resources: &Path, name: &str) -> Result<(Atlas, SpriteBatch), String> {
+    let atlas = Atlas::load(&resources.join(format!("{}.json", name)))?;
+    let mut image = Image::new(ctx, format!("/{}.png", name)).map_err(|e| e.to_string())?;
+    image.set_filter(FilterMode::Nearest);
+    Ok((atlas, SpriteBatch::new(image)))
+}
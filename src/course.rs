@@ -0,0 +1,307 @@
+use crate::crab;
+use crate::pipe::{pipe_velocity, VERTICAL_GAP, VERTICAL_GAP_DEVIANCE};
+use crate::settings::CourseSettings;
+use crate::storage;
+use crate::tile::FLOOR_Y;
+use crate::window::WORLD_HEIGHT;
+use noise::{NoiseFn, Perlin, Seedable};
+
+/// Players can't be expected to flap more often than this, in updates.
+pub const FLAP_INTERVAL: u32 = 12;
+/// How far a gap has to stay from the top of the screen and the floor.
+const GAP_MARGIN: f32 = 4.0;
+/// Where the gaps of the last run are saved, for the replay generator.
+pub const RECORDED_COURSE: &str = "last_course.json";
+
+/// The highest the middle of a gap this tall can go.
+fn highest_gap(gap_height: f32) -> f32 {
+    gap_height / 2.0 + GAP_MARGIN
+}
+
+/// The lowest the middle of a gap this tall can go.
+fn lowest_gap(gap_height: f32) -> f32 {
+    FLOOR_Y - gap_height / 2.0 - GAP_MARGIN
+}
+
+/// Comes up with where the gaps in the endless course go.
+/// The `PipeTracker` keeps whatever it comes up with on screen and reachable from the gap before.
+pub trait CourseGenerator {
+    /// Where the middle of the next gap goes.
+    fn next_gap(&mut self) -> f32;
+
+    /// A copy that carries on from the same point in the course.
+    fn box_clone(&self) -> Box<dyn CourseGenerator>;
+}
+
+impl Clone for Box<dyn CourseGenerator> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Makes the generator the settings ask for. Different seeds make different courses.
+/// `gap_height` is how tall the gaps are, which is how close to the top and the floor they can go.
+pub fn create_generator(settings: &CourseSettings, seed: u32, gap_height: f32) -> Box<dyn CourseGenerator> {
+    match settings {
+        CourseSettings::Perlin => Box::new(PerlinCourse::new(seed)),
+        CourseSettings::Uniform { max_delta } => Box::new(UniformCourse::new(seed, *max_delta, gap_height)),
+        CourseSettings::Sine { amplitude, period } => Box::new(SineCourse::new(seed, *amplitude, *period, gap_height)),
+        CourseSettings::Replay { file } => match ReplayCourse::load(file) {
+            Some(replay) => Box::new(replay),
+            None => {
+                println!("Couldn't replay {}, using the normal course instead", file);
+                Box::new(PerlinCourse::new(seed))
+            }
+        },
+    }
+}
+
+/// A number in [0, 1) made from the seed and how many rolls came before it.
+pub fn roll(seed: u32, rolls: u32) -> f32 {
+    let mut z = ((seed as u64) << 32 | rolls as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// The furthest the crab can climb while the course moves `distance`,
+/// flapping as often as a player fairly can.
+pub fn max_climb(distance: f32) -> f32 {
+    let ticks = (distance / -pipe_velocity()) as u32;
+    let mut velocity = 0.0;
+    let mut climb: f32 = 0.0;
+    let mut best: f32 = 0.0;
+    for tick in 0..ticks {
+        velocity = crab::step_velocity(velocity, tick % FLAP_INTERVAL == 0);
+        climb -= velocity;
+        best = best.max(climb);
+    }
+    best
+}
+
+/// Moves a gap `gap_height` tall so it's on screen
+/// and no higher above `previous` than the crab can climb in `distance`.
+pub fn keep_reachable(previous: Option<f32>, gap: f32, distance: Option<f32>, gap_height: f32) -> f32 {
+    let gap = gap.max(highest_gap(gap_height)).min(lowest_gap(gap_height));
+    match (previous, distance) {
+        (Some(previous), Some(distance)) => gap.max(previous - max_climb(distance)),
+        _ => gap,
+    }
+}
+
+/// The original course. Gaps follow Perlin noise, so they wander up and down smoothly.
+#[derive(Clone)]
+pub struct PerlinCourse {
+    time: f32,
+    random_fn: Perlin,
+}
+
+impl PerlinCourse {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            time: 0.0,
+            random_fn: Perlin::new().set_seed(seed),
+        }
+    }
+}
+
+impl CourseGenerator for PerlinCourse {
+    fn next_gap(&mut self) -> f32 {
+        self.time += VERTICAL_GAP_DEVIANCE;
+        let noise = self.random_fn.get([self.time as f64, self.time as f64]) as f32 + 1.0;
+        let top = (VERTICAL_GAP + 5.0) + noise * (WORLD_HEIGHT - (VERTICAL_GAP * 2.0));
+        top - VERTICAL_GAP / 2.0
+    }
+
+    fn box_clone(&self) -> Box<dyn CourseGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Each gap is picked at random, but never more than `max_delta` from the one before.
+#[derive(Clone)]
+pub struct UniformCourse {
+    seed: u32,
+    rolls: u32,
+    max_delta: f32,
+    previous: f32,
+    gap_height: f32,
+}
+
+impl UniformCourse {
+    pub fn new(seed: u32, max_delta: f32, gap_height: f32) -> Self {
+        Self {
+            seed,
+            rolls: 0,
+            max_delta,
+            previous: (highest_gap(gap_height) + lowest_gap(gap_height)) / 2.0,
+            gap_height,
+        }
+    }
+}
+
+impl CourseGenerator for UniformCourse {
+    fn next_gap(&mut self) -> f32 {
+        self.rolls += 1;
+        let low = (self.previous - self.max_delta).max(highest_gap(self.gap_height));
+        let high = (self.previous + self.max_delta).min(lowest_gap(self.gap_height));
+        self.previous = low + (high - low) * roll(self.seed, self.rolls);
+        self.previous
+    }
+
+    fn box_clone(&self) -> Box<dyn CourseGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Gaps go up and down in a wave, `period` pipes long. The seed picks where in the wave it starts.
+#[derive(Clone)]
+pub struct SineCourse {
+    pipes: u32,
+    amplitude: f32,
+    period: f32,
+    phase: f32,
+    /// Halfway between the highest and lowest a gap can go.
+    middle: f32,
+}
+
+impl SineCourse {
+    pub fn new(seed: u32, amplitude: f32, period: f32, gap_height: f32) -> Self {
+        Self {
+            pipes: 0,
+            amplitude,
+            period: period.max(1.0),
+            phase: roll(seed, 0) * std::f32::consts::PI * 2.0,
+            middle: (highest_gap(gap_height) + lowest_gap(gap_height)) / 2.0,
+        }
+    }
+}
+
+impl CourseGenerator for SineCourse {
+    fn next_gap(&mut self) -> f32 {
+        self.pipes += 1;
+        let angle = self.pipes as f32 / self.period * std::f32::consts::PI * 2.0 + self.phase;
+        self.middle + self.amplitude * angle.sin()
+    }
+
+    fn box_clone(&self) -> Box<dyn CourseGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Plays back the gaps of a recorded course, starting over once it runs out.
+#[derive(Clone)]
+pub struct ReplayCourse {
+    gaps: Vec<f32>,
+    next: usize,
+}
+
+impl ReplayCourse {
+    /// Reads a JSON list of gaps, like the one saved after every run.
+    /// The file is found in the profile's folder, unless it's an absolute path.
+    pub fn load(file: &str) -> Option<Self> {
+        let gaps: Vec<f32> = storage::read(file)?;
+        if gaps.is_empty() {
+            return None;
+        }
+        Some(Self { gaps, next: 0 })
+    }
+}
+
+impl CourseGenerator for ReplayCourse {
+    fn next_gap(&mut self) -> f32 {
+        let gap = self.gaps[self.next % self.gaps.len()];
+        self.next += 1;
+        gap
+    }
+
+    fn box_clone(&self) -> Box<dyn CourseGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipe::{pipe_spacing, PipeTracker};
+
+    const SEEDS: u32 = 200;
+    const PIPES: usize = 200;
+    /// The width of the pipe sprite in the atlas.
+    const PIPE_WIDTH: f32 = 32.0;
+    /// Room for rounding when comparing climbs.
+    const EPSILON: f32 = 0.001;
+    /// The normal gaps, and the tallest the assists allow.
+    const GAP_HEIGHTS: [f32; 2] = [VERTICAL_GAP, VERTICAL_GAP * 1.5];
+
+    /// Every gap of every seed is on screen and no higher above the one before than the crab can climb,
+    /// however tall the gaps are.
+    fn check_reachable(settings: &CourseSettings) {
+        for gap_height in GAP_HEIGHTS.iter() {
+            check_reachable_with(settings, *gap_height);
+        }
+    }
+
+    fn check_reachable_with(settings: &CourseSettings, gap_height: f32) {
+        let spacing = pipe_spacing(PIPE_WIDTH);
+        let climb = max_climb(spacing);
+        let on_screen = highest_gap(gap_height)..=lowest_gap(gap_height);
+        for seed in 0..SEEDS {
+            let mut tracker = PipeTracker::new(seed, settings, gap_height);
+            tracker.spacing = Some(spacing);
+            let mut previous: Option<f32> = None;
+            for pipe in 0..PIPES {
+                let gap = tracker.next_gap_centre();
+                assert!(
+                    on_screen.contains(&gap),
+                    "seed {} pipe {}: gap {} of height {} is off screen",
+                    seed,
+                    pipe,
+                    gap,
+                    gap_height
+                );
+                if let Some(previous) = previous {
+                    assert!(
+                        previous - gap <= climb + EPSILON,
+                        "seed {} pipe {}: gap {} climbs too far from {}",
+                        seed,
+                        pipe,
+                        gap,
+                        previous
+                    );
+                }
+                previous = Some(gap);
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_is_reachable() {
+        check_reachable(&CourseSettings::Perlin);
+    }
+
+    #[test]
+    fn uniform_is_reachable() {
+        check_reachable(&CourseSettings::Uniform { max_delta: 20.0 });
+        check_reachable(&CourseSettings::Uniform { max_delta: 500.0 });
+    }
+
+    #[test]
+    fn sine_is_reachable() {
+        check_reachable(&CourseSettings::Sine { amplitude: 30.0, period: 8.0 });
+        check_reachable(&CourseSettings::Sine { amplitude: 200.0, period: 2.0 });
+    }
+
+    #[test]
+    fn replay_is_reachable() {
+        // Gaps far off screen and leaping up and down, as a hand edited file might have.
+        let gaps = vec![-100.0, 300.0, 10.0, 140.0, 0.0, 80.0, 1000.0, -1000.0, 60.0];
+        let file = std::env::temp_dir().join("crab_replay_course_test.json");
+        std::fs::write(&file, serde_json::to_string(&gaps).unwrap()).unwrap();
+        let settings = CourseSettings::Replay { file: file.to_string_lossy().into_owned() };
+        assert!(ReplayCourse::load(&file.to_string_lossy()).is_some());
+        check_reachable(&settings);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
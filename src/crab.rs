@@ -1,17 +1,45 @@
 use crate::{atlas};
-use crate::entity::{PlayState, PipeEntity};
+use crate::assists::Assists;
+use crate::tile::FLOOR_Y;
+use crate::entity::PlayState;
 use crate::atlas::Sprite;
 use ggez::nalgebra::{Point2, Vector2};
-use ggez::{Context, graphics, GameResult};
+use ggez::{graphics, GameResult};
 use ggez::graphics::spritebatch::SpriteBatch;
+use serde::{Deserialize, Serialize};
 
-const GRAVITY: f32 = 0.25;
-const JUMP_IMPULSE: f32 = 2.75;
+pub const GRAVITY: f32 = 0.25;
+pub const JUMP_IMPULSE: f32 = 2.75;
 pub const SCREEN_TOP: f32 = -16.0;
+/// How far below this the crab has to be to hit the ground.
+pub const GROUND: f32 = 135.0;
+/// How far from the left of the screen the crab flies.
+pub const CRAB_X: f32 = 40.0;
+/// How fast the crab spins while tumbling after death.
+const TUMBLE_SPIN: f32 = 0.3;
+/// How see-through the ghost of the best run is.
+const GHOST_ALPHA: f32 = 0.4;
+/// How quickly the crab starts rising while floating, and the fastest it rises.
+const FLOAT_LIFT: f32 = 0.4;
+const FLOAT_SPEED: f32 = 1.5;
+
+/// The crab's vertical velocity one update on, at normal speed, from `velocity`.
+/// A flap pushes against gravity for the update it happens in, just like `PlayerEntity::update` does.
+/// This is what the course generators and the reachability check fly the crab with.
+pub fn step_velocity(velocity: f32, flapped: bool) -> f32 {
+    if flapped {
+        -JUMP_IMPULSE - GRAVITY
+    } else {
+        velocity + GRAVITY
+    }
+}
 
 /// The physics on the entity.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Physics {
+    #[serde(with = "crate::shapes::vector")]
     pub velocity: Vector2<f32>,
+    #[serde(with = "crate::shapes::vector")]
     pub acceleration: Vector2<f32>,
     pub gravity: bool,
 }
@@ -31,44 +59,67 @@ pub fn create_player(sprites: &atlas::Atlas) -> Box<PlayerEntity> {
     let sprite = crab0.clone();
     let crab1 = sprites.create_sprite("crab1.png");
     let player_sprites = vec![crab0, crab1];
-    let player = PlayerEntity::new(sprite, (40.0, SCREEN_TOP), player_sprites);
+    let player = PlayerEntity::new(sprite, (CRAB_X, SCREEN_TOP), player_sprites);
 
     Box::new(player)
 }
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PlayerEntity {
     pub sprite: Sprite,
+    #[serde(with = "crate::shapes::point")]
     pub position: Point2<f32>,
     pub player_sprites: Vec<Sprite>,
     can_jump: bool,
     pub physics: Physics,
+    /// The rotation of the crab once it has died and is tumbling.
+    tumble: Option<f32>,
+    /// Whether the player made the crab jump this update.
+    pub flapped: bool,
+    /// How big the crab is compared to its sprite.
+    pub size: f32,
+    /// The colour the crab is drawn in, so players can tell their crabs apart.
+    #[serde(skip, default = "untinted")]
+    pub tint: graphics::Color,
+    #[serde(default)]
+    pub assists: Assists,
+}
+
+fn untinted() -> graphics::Color {
+    graphics::WHITE
 }
 
 impl PlayerEntity {
+    /// Moves the crab on by one update. `flap_held` is whether the flap key is down.
     pub fn update(
         &mut self,
-        ctx: &mut Context,
+        flap_held: bool,
         state: &PlayState,
     ) -> PlayState {
+        let speed = self.assists.speed;
         let physics = &mut self.physics;
         physics.acceleration = if physics.gravity {
-            Vector2::new(0.0, GRAVITY)
+            Vector2::new(0.0, GRAVITY * speed * speed)
         } else {
             Vector2::new(0.0, 0.0)
         };
 
 
         let mut state = state.clone();
+        self.flapped = false;
         if state.is_not_dead()
         {
-            use ggez::event::KeyCode;
-            use ggez::input::keyboard;
-            if !keyboard::pressed_keys(ctx).contains(&KeyCode::Space) && !self.can_jump {
+            if !flap_held && !self.can_jump {
                 self.can_jump = true;
             }
 
-            if keyboard::is_key_pressed(ctx, KeyCode::Space) && self.can_jump {
+            if flap_held && self.can_jump {
                 let physics = &mut self.physics;
-                PlayerEntity::jump(physics);
+                if self.assists.hold_to_float {
+                    PlayerEntity::float(physics, speed);
+                } else {
+                    PlayerEntity::jump(physics, speed);
+                }
+                self.flapped = true;
 
                 // exit start screen state.
                 if state == PlayState::StartScreen {
@@ -82,19 +133,17 @@ impl PlayerEntity {
             self.auto_jump()
         }
 
-        self.change_player_position(ctx);
+        self.change_player_position();
 
         // Crab should not go above the top of the screen easily.
         self.prevent_going_off();
         state
     }
 
-    fn change_player_position(&mut self, ctx: &mut Context) {
-        let delta = ggez::timer::delta(ctx).as_nanos() as f32;
+    /// Every update moves the crab the same amount, so runs play out the same way given the same flaps.
+    fn change_player_position(&mut self) {
         let physics = &mut self.physics;
-        physics.acceleration.scale(1.0 / delta);
         physics.velocity += physics.acceleration;
-        physics.velocity.scale(1.0 / delta);
         // moves all the entities on the board.
         self.position += physics.velocity;
     }
@@ -106,16 +155,33 @@ impl PlayerEntity {
             physics: Physics::new(true),
             can_jump: true,
             player_sprites,
+            tumble: None,
+            flapped: false,
+            size: 1.0,
+            tint: graphics::WHITE,
+            assists: Assists::default(),
         }
     }
-    pub fn overlaps(&self, other : &PipeEntity) -> bool {
+    /// How far the crab is from the rect vertically,
+    /// or None if the rect isn't level with the crab.
+    pub fn clearance(&self, other_rect: &graphics::Rect) -> Option<f32> {
         let player_rect = self.get_bounds();
-        let other_rect = other.get_rect();
+        if player_rect.right() < other_rect.left() || player_rect.left() > other_rect.right() {
+            return None;
+        }
+
+        let above = other_rect.top() - player_rect.bottom();
+        let below = player_rect.top() - other_rect.bottom();
+        Some(above.max(below))
+    }
 
-        other_rect.overlaps(&player_rect)
+    pub fn at_ceiling(&self) -> bool {
+        self.position.y <= SCREEN_TOP
     }
+
     pub fn get_bounds(&self) -> graphics::Rect {
         let mut rect = self.sprite.get_bound_box();
+        rect.scale(self.size, self.size);
         rect.move_to(self.position.clone());
 
         rect
@@ -130,34 +196,91 @@ impl PlayerEntity {
     fn auto_jump(&mut self) -> () {
         let physics = &mut self.physics;
         if self.position.y > 600.0 / 8.0 {
-            PlayerEntity::jump(physics);
+            PlayerEntity::jump(physics, self.assists.speed);
         }
     }
+
+    /// Jumps off the ground instead of dying on it, for the invincibility assist.
+    pub fn bounce(&mut self) {
+        self.position.y = GROUND;
+        PlayerEntity::jump(&mut self.physics, self.assists.speed);
+    }
     pub fn draw(&mut self, batch: &mut SpriteBatch) -> GameResult {
         self.draw_player(batch);
 
         Ok(())
     }
+    /// Drops the dead crab to the floor, spinning as it goes.
+    /// Returns true once it is lying on the floor.
+    pub fn tumble(&mut self) -> bool {
+        let resting_y = FLOOR_Y - self.sprite.height / 2.0;
+        if self.position.y >= resting_y {
+            self.position.y = resting_y;
+            return true;
+        }
+
+        let spin = self.tumble.unwrap_or(0.0) + TUMBLE_SPIN;
+        self.tumble = Some(spin);
+        let physics = &mut self.physics;
+        physics.velocity.y = physics.velocity.y.max(0.0) + GRAVITY;
+        self.position.y = (self.position.y + physics.velocity.y).min(resting_y);
+        false
+    }
+
     fn draw_player(&mut self, batch: &mut SpriteBatch) {
-        let s = &mut self.player_sprites;
+        batch.add(self.draw_param());
+    }
+
+    /// Which sprite the crab is drawn with and where, tilted and tinted.
+    pub fn draw_param(&self) -> graphics::DrawParam {
+        let s = &self.player_sprites;
         let p = &self.physics;
         // need velocity to map to these rotations between -0.2 and 0.2!
-        let angle = rescale_range(p.velocity.y, -7.0, 7.0, -0.6, 0.6);
+        let angle = if self.assists.reduced_motion {
+            0.0
+        } else {
+            self.tumble
+                .unwrap_or_else(|| rescale_range(p.velocity.y, -7.0, 7.0, -0.6, 0.6))
+        };
         let x = if p.velocity.y >= 0.0 {
-            &mut s[1]
+            &s[1]
+        } else {
+            &s[0]
+        };
+        x.draw_params(self.position.clone())
+            .offset(Point2::new(0.5, 0.5))
+            .scale(Vector2::new(self.size, self.size))
+            .rotation(angle)
+            .color(self.tint)
+    }
+
+    /// Draws a see-through crab at `position`, tilted the way this crab would be at that speed.
+    pub fn draw_ghost(&self, batch: &mut SpriteBatch, position: Point2<f32>, velocity: f32) {
+        let sprite = if velocity >= 0.0 {
+            &self.player_sprites[1]
         } else {
-            &mut s[0]
+            &self.player_sprites[0]
         };
         batch.add(
-            x.add_draw_param(self.position.clone())
+            sprite
+                .draw_params(position)
                 .offset(Point2::new(0.5, 0.5))
-                .rotation(angle),
+                .rotation(rescale_range(velocity, -7.0, 7.0, -0.6, 0.6))
+                .color(graphics::Color::new(1.0, 1.0, 1.0, GHOST_ALPHA)),
         );
     }
 
-    fn jump(physics: &mut Physics) {
-        physics.acceleration = Vector2::new(0.0, -GRAVITY);
-        physics.velocity = Vector2::new(0.0, -JUMP_IMPULSE);
+    /// Everything is scaled by the speed assist: velocities by the speed and accelerations by its square,
+    /// so the crab flies the same arcs, only slower.
+    fn jump(physics: &mut Physics, speed: f32) {
+        physics.acceleration = Vector2::new(0.0, -GRAVITY * speed * speed);
+        physics.velocity = Vector2::new(0.0, -JUMP_IMPULSE * speed);
+    }
+
+    /// Rises gently for as long as the flap key is held, for the hold to float assist.
+    fn float(physics: &mut Physics, speed: f32) {
+        physics.acceleration = Vector2::new(0.0, 0.0);
+        physics.velocity.y = (physics.velocity.y - FLOAT_LIFT * speed * speed).max(-FLOAT_SPEED * speed);
     }
 }
 
@@ -170,3 +293,25 @@ fn rescale_range(value: f32, old_min: f32, old_max: f32, new_min: f32, new_max:
     let new_range = new_max - new_min;
     (((clamp(value, old_min, old_max) - old_min) * new_range) / old_range) + new_min
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::{Atlas, ATLAS_FILE};
+    use std::path::Path;
+
+    #[test]
+    fn step_velocity_flies_like_the_crab() {
+        let atlas = Atlas::parse_atlas_json(&Path::new("resources").join(ATLAS_FILE));
+        let mut player = create_player(&atlas);
+        player.position.y = 100.0;
+        let mut velocity = 0.0;
+        for tick in 0..36 {
+            let flap = tick % 12 == 0;
+            let before = player.position.y;
+            player.update(flap, &PlayState::Play);
+            velocity = step_velocity(velocity, flap);
+            assert!((player.position.y - before - velocity).abs() < 0.0001, "update {}", tick);
+        }
+    }
+}
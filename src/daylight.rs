@@ -0,0 +1,156 @@
+//! Day and night on the endless course. The sky and the world's sprites are tinted by keyframes
+//! spread over a day, which goes by as the course moves, so every run starts in the morning.
+//! At night some sprites can glow, like the jellyfish.
+
+use crate::atlas::{Atlas, Sprite};
+use ggez::graphics::{Color, Rect, WHITE};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Keyframe {
+    /// How far through the day, from 0.0 to 1.0.
+    pub at: f32,
+    /// Multiplies the sky colour.
+    pub sky: [u8; 3],
+    /// Multiplies the colours of the sprites.
+    #[serde(default = "no_tint")]
+    pub tint: [u8; 3],
+    /// How much the glowing sprites glow, from 0.0 to 1.0.
+    #[serde(default)]
+    pub glow: f32,
+}
+
+fn no_tint() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DaylightSettings {
+    /// How far the course moves in a whole day.
+    pub day_length: f32,
+    /// The light through the day. Left empty, it's always daytime.
+    pub keyframes: Vec<Keyframe>,
+    /// The sprites that glow at night.
+    pub glowing: Vec<String>,
+    pub glow_colour: [u8; 3],
+}
+
+impl Default for DaylightSettings {
+    fn default() -> Self {
+        Self {
+            day_length: 4000.0,
+            keyframes: vec![],
+            glowing: vec!["jellyfish.png".to_string(), "pearl.png".to_string()],
+            glow_colour: [150, 255, 235],
+        }
+    }
+}
+
+fn colour([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgb(r, g, b)
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let between = |from: f32, to: f32| from + (to - from) * amount;
+    Color::new(between(from.r, to.r), between(from.g, to.g), between(from.b, to.b), between(from.a, to.a))
+}
+
+fn multiply(a: Color, b: Color) -> Color {
+    Color::new(a.r * b.r, a.g * b.g, a.b * b.b, a.a * b.a)
+}
+
+/// The light of the day, as it is now.
+pub struct Daylight {
+    day_length: f32,
+    /// Sorted by how far through the day they are.
+    keyframes: Vec<Keyframe>,
+    /// Where the glowing sprites are in the atlas.
+    glowing: Vec<Rect>,
+    glow_colour: Color,
+    sky: Color,
+    tint: Color,
+    glow: f32,
+}
+
+/// Always daytime.
+impl Default for Daylight {
+    fn default() -> Self {
+        Self {
+            day_length: 1.0,
+            keyframes: vec![],
+            glowing: vec![],
+            glow_colour: WHITE,
+            sky: WHITE,
+            tint: WHITE,
+            glow: 0.0,
+        }
+    }
+}
+
+impl Daylight {
+    pub fn new(settings: &DaylightSettings, atlas: &Atlas) -> Self {
+        let mut keyframes: Vec<Keyframe> = settings
+            .keyframes
+            .iter()
+            .map(|keyframe| Keyframe {
+                at: keyframe.at.max(0.0).min(1.0),
+                glow: keyframe.glow.max(0.0).min(1.0),
+                ..keyframe.clone()
+            })
+            .collect();
+        keyframes.sort_by(|a, b| a.at.partial_cmp(&b.at).unwrap());
+        let glowing = settings
+            .glowing
+            .iter()
+            .filter(|name| {
+                let found = atlas.has_sprite(name);
+                if !found {
+                    println!("There's no sprite called {} to glow", name);
+                }
+                found
+            })
+            .map(|name| atlas.create_sprite(name).rect)
+            .collect();
+        let mut daylight = Self {
+            day_length: settings.day_length.max(1.0),
+            keyframes,
+            glowing,
+            glow_colour: colour(settings.glow_colour),
+            ..Daylight::default()
+        };
+        daylight.update(0.0);
+        daylight
+    }
+
+    /// Works out the light this far into the run, between the keyframes either side.
+    pub fn update(&mut self, distance: f32) {
+        let (first, last) = match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        let time = (distance / self.day_length).rem_euclid(1.0);
+        // The day wraps around, so before the first keyframe is still the last one's night.
+        let before = self.keyframes.iter().rev().find(|keyframe| keyframe.at <= time).unwrap_or(last);
+        let after = self.keyframes.iter().find(|keyframe| keyframe.at > time).unwrap_or(first);
+        let span = (after.at - before.at).rem_euclid(1.0);
+        let amount = if span > 0.0 { (time - before.at).rem_euclid(1.0) / span } else { 0.0 };
+        self.sky = mix(colour(before.sky), colour(after.sky), amount);
+        self.tint = mix(colour(before.tint), colour(after.tint), amount);
+        self.glow = before.glow + (after.glow - before.glow) * amount;
+    }
+
+    /// The sky in this light.
+    pub fn sky(&self, sky: Color) -> Color {
+        multiply(sky, self.sky)
+    }
+
+    /// The colour to draw the sprite in, tinted or glowing.
+    pub fn colour(&self, sprite: &Sprite) -> Color {
+        if self.glow > 0.0 && self.glowing.contains(&sprite.rect) {
+            mix(self.tint, self.glow_colour, self.glow)
+        } else {
+            self.tint
+        }
+    }
+}
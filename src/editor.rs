@@ -0,0 +1,687 @@
+use crate::atlas::{Atlas, Sprite};
+use crate::collectibles::{PowerUp, POWER_UPS};
+use crate::daylight::Daylight;
+use crate::level::{self, Level, Obstacle, Section};
+use crate::obstacles::ObstacleSprites;
+use crate::systems;
+use crate::tile::FLOOR_Y;
+use crate::window::{SCALE, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT};
+use crate::world::World;
+use ggez::event::{KeyCode, KeyMods, MouseButton};
+use ggez::graphics::{self, spritebatch::SpriteBatch, Text};
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{Context, GameResult};
+use std::path::{Path, PathBuf};
+
+/// The level the editor opens when the campaign isn't loaded.
+pub const CUSTOM_LEVEL: &str = "custom.json";
+/// How many grid squares fit across a pipe.
+const GRID_DIVISIONS: f32 = 4.0;
+/// How far the arrow keys scroll, in grid squares.
+const SCROLL_STEP: f32 = 4.0;
+/// How much course the timeline shows past the last obstacle.
+const TIMELINE_RUN_OFF: f32 = 200.0;
+const TIMELINE_TOP: f32 = 30.0;
+const TIMELINE_HEIGHT: f32 = 24.0;
+const HELP: &str = "1-7: tool  Left click: place/move  Right click: delete  Arrows/wheel: scroll  \
+N: new section  P: play from cursor  Ctrl+Z/Y: undo/redo  Ctrl+S: save  Ctrl+O: reload  Esc: leave";
+
+/// Something in the level being edited, and how far along the course it is.
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    /// The section it belongs to. Something right where a section starts could be in either.
+    section: usize,
+    distance: f32,
+    obstacle: Obstacle,
+}
+
+/// Everything undo and redo can take back.
+#[derive(Debug, Clone, PartialEq)]
+struct Course {
+    /// The settings of each section. Their obstacles live in `items`.
+    sections: Vec<Section>,
+    /// How far along the course each section starts.
+    section_starts: Vec<f32>,
+    items: Vec<Item>,
+}
+
+impl Course {
+    fn from_level(level: &Level, default_spacing: f32) -> Self {
+        let (section_starts, placements) = level::layout(level, default_spacing);
+        Self {
+            sections: level
+                .sections
+                .iter()
+                .map(|section| Section {
+                    obstacles: vec![],
+                    ..section.clone()
+                })
+                .collect(),
+            section_starts,
+            items: placements
+                .into_iter()
+                .map(|placement| Item {
+                    section: placement.section,
+                    distance: placement.distance,
+                    obstacle: placement.obstacle,
+                })
+                .collect(),
+        }
+    }
+
+    fn section_at(&self, distance: f32) -> usize {
+        self.section_starts.iter().rposition(|start| *start <= distance).unwrap_or(0)
+    }
+
+    /// Makes a level of everything from `from` on, with every distance written out.
+    /// Sections left with nothing in them are dropped.
+    fn to_level(&self, name: &str, from: f32) -> Level {
+        let mut items = self.items.iter().filter(|item| item.distance >= from).collect::<Vec<&Item>>();
+        items.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut sections = self.sections.clone();
+        let mut previous = from;
+        for item in items {
+            let mut obstacle = item.obstacle.clone();
+            obstacle.set_after(item.distance - previous);
+            previous = item.distance;
+            sections[item.section].obstacles.push(obstacle);
+        }
+        sections.retain(|section| !section.obstacles.is_empty());
+
+        Level {
+            name: name.to_string(),
+            sections,
+        }
+    }
+}
+
+/// What the game should do after the editor has had a key.
+pub enum EditorAction {
+    Nothing,
+    Leave,
+    PlayTest(Level),
+}
+
+/// Lays out a level on a timeline that can be scrolled through and edited with the mouse.
+pub struct Editor {
+    path: PathBuf,
+    name: String,
+    course: Course,
+    undo: Vec<Course>,
+    redo: Vec<Course>,
+    /// What the course was before the current drag, to be undone in one go.
+    before_drag: Option<Course>,
+    /// What left click puts down.
+    tool: Obstacle,
+    selected: Option<usize>,
+    dragging: bool,
+    /// How far along the course the left of the screen is.
+    scroll: f32,
+    /// Where the mouse is on the course.
+    cursor: Point2<f32>,
+    grid: f32,
+    default_spacing: f32,
+    sprites: ObstacleSprites,
+    floor: Sprite,
+    /// The course spawned into a world so it can be drawn like it is played.
+    preview: World,
+    /// What happened last, like a save or a mistake in the level.
+    pub status: String,
+}
+
+impl Editor {
+    /// Opens the level at `path`, or starts a new one if there isn't one.
+    pub fn open(atlas: &Atlas, path: &Path) -> Self {
+        let sprites = ObstacleSprites::new(atlas);
+        let default_spacing = level::default_spacing(&sprites);
+        let mut editor = Self {
+            path: path.to_path_buf(),
+            name: "Custom".to_string(),
+            course: Course {
+                sections: vec![],
+                section_starts: vec![],
+                items: vec![],
+            },
+            undo: vec![],
+            redo: vec![],
+            before_drag: None,
+            tool: tools()[0].clone(),
+            selected: None,
+            dragging: false,
+            scroll: 0.0,
+            cursor: Point2::new(0.0, 0.0),
+            grid: sprites.pipe_tip.width / GRID_DIVISIONS,
+            default_spacing,
+            sprites,
+            floor: atlas.create_sprite("floor_tile.png"),
+            preview: World::new(),
+            status: String::new(),
+        };
+        editor.load();
+        editor
+    }
+
+    /// Reads the level back from its file, or starts an empty one.
+    /// If the file has mistakes in it, they are shown and whatever was being edited is kept.
+    fn load(&mut self) {
+        let loaded = if self.path.is_file() {
+            level::load_level(&self.path).map(Some)
+        } else {
+            Ok(None)
+        };
+        let level = match loaded {
+            Ok(Some(level)) => {
+                self.status = format!("Opened {}", self.path.display());
+                level
+            }
+            Err(e) if !self.course.sections.is_empty() => {
+                self.status = e.to_string();
+                return;
+            }
+            Err(e) => {
+                self.status = e.to_string();
+                empty_level(&self.name)
+            }
+            Ok(None) => {
+                self.status = format!("New level {}", self.path.display());
+                empty_level(&self.name)
+            }
+        };
+        self.name = level.name.clone();
+        self.course = Course::from_level(&level, self.default_spacing);
+        self.undo.clear();
+        self.redo.clear();
+        self.selected = None;
+        self.refresh();
+    }
+
+    fn save(&mut self) {
+        match level::save_level(&self.path, &self.course.to_level(&self.name, 0.0)) {
+            Ok(()) => self.status = format!("Saved {}", self.path.display()),
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    /// Remembers the course so the next change can be undone.
+    fn checkpoint(&mut self) {
+        self.undo.push(self.course.clone());
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(course) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.course, course));
+            self.selected = None;
+            self.refresh();
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(course) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.course, course));
+            self.selected = None;
+            self.refresh();
+        }
+    }
+
+    /// Spawns the course into the preview world again after it has changed.
+    fn refresh(&mut self) {
+        self.preview = World::new();
+        for item in &self.course.items {
+            level::spawn_obstacle(&mut self.preview, &self.sprites, &item.obstacle, item.distance);
+        }
+    }
+
+    fn snap(&self, value: f32) -> f32 {
+        (value / self.grid).round() * self.grid
+    }
+
+    /// The box an item takes up on the course, for clicking on it.
+    fn bounds(&self, item: &Item) -> graphics::Rect {
+        let (width, height) = match &item.obstacle {
+            Obstacle::Jellyfish { .. } => (self.sprites.jellyfish.width, self.sprites.jellyfish.height),
+            Obstacle::Seagull { .. } => {
+                let sprite = &self.sprites.seagull[0];
+                return graphics::Rect::new(item.distance, 0.0, sprite.width, sprite.height);
+            }
+            Obstacle::Pearl { .. } => (self.sprites.pearl.width, self.sprites.pearl.height),
+            Obstacle::PowerUp { power_up, .. } => {
+                let sprite = self.sprites.power_up(*power_up);
+                (sprite.width, sprite.height)
+            }
+            _ => {
+                let width = self.sprites.pipe_tip.width;
+                return graphics::Rect::new(item.distance - width / 2.0, 0.0, width, FLOOR_Y);
+            }
+        };
+        let y = item.obstacle.y();
+        graphics::Rect::new(item.distance - width / 2.0, y - height / 2.0, width, height)
+    }
+
+    /// The item under the cursor. Pipes go under everything else, so things in their gaps can be picked.
+    fn item_under_cursor(&self) -> Option<usize> {
+        let under = |pipes: bool| {
+            self.course
+                .items
+                .iter()
+                .rposition(|item| item.obstacle.is_pipe() == pipes && self.bounds(item).contains(self.cursor))
+        };
+        under(false).or_else(|| under(true))
+    }
+
+    fn move_selected(&mut self) {
+        let (distance, y) = (self.snap(self.cursor.x).max(0.0), self.snap(self.cursor.y));
+        let section = self.course.section_at(distance);
+        if let Some(item) = self.selected.and_then(|i| self.course.items.get_mut(i)) {
+            item.section = section;
+            item.distance = distance;
+            item.obstacle.set_y(y);
+        }
+        self.refresh();
+    }
+
+    fn delete(&mut self, index: usize) {
+        self.checkpoint();
+        self.course.items.remove(index);
+        self.selected = None;
+        self.refresh();
+    }
+
+    pub fn handle_key(&mut self, keycode: KeyCode, keymods: KeyMods) -> EditorAction {
+        let ctrl = keymods.contains(KeyMods::CTRL);
+        match keycode {
+            KeyCode::Escape => return EditorAction::Leave,
+            KeyCode::Z if ctrl && keymods.contains(KeyMods::SHIFT) => self.redo(),
+            KeyCode::Z if ctrl => self.undo(),
+            KeyCode::Y if ctrl => self.redo(),
+            KeyCode::S if ctrl => self.save(),
+            KeyCode::O if ctrl => self.load(),
+            KeyCode::Left => self.scroll_by(-self.grid * SCROLL_STEP),
+            KeyCode::Right => self.scroll_by(self.grid * SCROLL_STEP),
+            KeyCode::Delete | KeyCode::Back => {
+                if let Some(selected) = self.selected {
+                    self.delete(selected);
+                }
+            }
+            KeyCode::N => self.new_section(),
+            KeyCode::P => {
+                let level = self.course.to_level(&self.name, self.cursor.x);
+                if level.sections.is_empty() {
+                    self.status = "There's nothing after the cursor to play".to_string();
+                } else {
+                    return EditorAction::PlayTest(level);
+                }
+            }
+            _ => self.pick_tool(keycode),
+        }
+        EditorAction::Nothing
+    }
+
+    /// Number keys pick what to put down. Picking power ups again goes through each kind.
+    fn pick_tool(&mut self, keycode: KeyCode) {
+        let index = match keycode {
+            KeyCode::Key1 => 0,
+            KeyCode::Key2 => 1,
+            KeyCode::Key3 => 2,
+            KeyCode::Key4 => 3,
+            KeyCode::Key5 => 4,
+            KeyCode::Key6 => 5,
+            KeyCode::Key7 => 6,
+            _ => return,
+        };
+        let tool = tools()[index].clone();
+        self.tool = match (&self.tool, &tool) {
+            (Obstacle::PowerUp { power_up, .. }, Obstacle::PowerUp { .. }) => {
+                let next = POWER_UPS.iter().position(|p| p == power_up).map_or(0, |i| (i + 1) % POWER_UPS.len());
+                power_up_tool(POWER_UPS[next])
+            }
+            _ => tool,
+        };
+    }
+
+    /// Starts a new section at the cursor, looking like the one it's in.
+    fn new_section(&mut self) {
+        let distance = self.snap(self.cursor.x).max(0.0);
+        if self.course.section_starts.iter().any(|start| *start == distance) {
+            return;
+        }
+        self.checkpoint();
+        let current = self.course.section_at(distance);
+        let section = self.course.sections[current].clone();
+        self.course.sections.insert(current + 1, section);
+        self.course.section_starts.insert(current + 1, distance);
+        for item in &mut self.course.items {
+            if item.section > current || (item.section == current && item.distance >= distance) {
+                item.section += 1;
+            }
+        }
+        self.status = format!("Started section {} at {}", current + 2, distance);
+    }
+
+    pub fn scroll_by(&mut self, distance: f32) {
+        self.scroll = (self.scroll + distance).max(0.0);
+    }
+
+    /// How long the course on the timeline is.
+    fn course_length(&self) -> f32 {
+        let last = self.course.items.iter().map(|item| item.distance).fold(0.0, f32::max);
+        last + TIMELINE_RUN_OFF
+    }
+
+    pub fn mouse_moved(&mut self, x: f32, y: f32) {
+        self.cursor = Point2::new(x / SCALE + self.scroll, y / SCALE);
+        if self.dragging {
+            self.move_selected();
+        }
+    }
+
+    pub fn mouse_down(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.mouse_moved(x, y);
+        if y >= TIMELINE_TOP && y < TIMELINE_TOP + TIMELINE_HEIGHT {
+            // Jump to where the timeline was clicked.
+            self.scroll = (x / SCREEN_WIDTH * self.course_length() - SCREEN_WIDTH / SCALE / 2.0).max(0.0);
+            return;
+        }
+
+        match button {
+            MouseButton::Left => {
+                self.before_drag = Some(self.course.clone());
+                self.selected = match self.item_under_cursor() {
+                    Some(index) => Some(index),
+                    None => {
+                        self.course.items.push(Item {
+                            section: 0,
+                            distance: 0.0,
+                            obstacle: self.tool.clone(),
+                        });
+                        Some(self.course.items.len() - 1)
+                    }
+                };
+                self.dragging = true;
+                self.move_selected();
+            }
+            MouseButton::Right => {
+                if let Some(index) = self.item_under_cursor() {
+                    self.delete(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = false;
+        if let Some(before) = self.before_drag.take() {
+            if before != self.course {
+                self.undo.push(before);
+                self.redo.clear();
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, batch: &mut SpriteBatch) -> GameResult {
+        let section = &self.course.sections[self.course.section_at(self.scroll)];
+        let [r, g, b] = section.background;
+        graphics::clear(ctx, graphics::Color::from_rgb(r, g, b));
+
+        let view_width = SCREEN_WIDTH / SCALE;
+        let first_tile = (self.scroll / self.floor.width).floor() as i32;
+        let tiles = (view_width / self.floor.width).ceil() as i32 + 1;
+        for tile in first_tile..first_tile + tiles {
+            batch.add(self.floor.draw_params(Point2::new(tile as f32 * self.floor.width, FLOOR_Y)));
+        }
+        systems::render(ctx, &self.preview, WORLD_HEIGHT, batch, &Daylight::default())?;
+        let p = graphics::DrawParam::new()
+            .dest(Point2::new(-self.scroll * SCALE, 0.0))
+            .scale(Vector2::new(SCALE, SCALE));
+        graphics::draw(ctx, batch, p)?;
+        batch.clear();
+
+        self.draw_overlay(ctx)?;
+        self.draw_timeline(ctx)?;
+
+        let lines = [
+            format!("{} ({})  Tool: {}", self.name, self.path.display(), tool_name(&self.tool)),
+            self.status.clone(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let text = Text::new(line.as_str());
+            graphics::draw(ctx, &text, (Point2::new(10.0, 6.0 + 54.0 * i as f32), graphics::WHITE))?;
+        }
+        let mut help = Text::new(HELP);
+        help.set_bounds(Point2::new(SCREEN_WIDTH - 20.0, SCREEN_HEIGHT), graphics::Align::Left);
+        graphics::draw(ctx, &help, (Point2::new(10.0, SCREEN_HEIGHT - 60.0), graphics::WHITE))
+    }
+
+    /// Draws the grid, where sections start and what's selected.
+    fn draw_overlay(&self, ctx: &mut Context) -> GameResult {
+        let to_screen = |x: f32| (x - self.scroll) * SCALE;
+        let faint = graphics::Color::new(1.0, 1.0, 1.0, 0.15);
+        let mut builder = graphics::MeshBuilder::new();
+
+        let step = self.grid * GRID_DIVISIONS;
+        let mut x = (self.scroll / step).floor() * step;
+        while to_screen(x) < SCREEN_WIDTH {
+            builder.rectangle(graphics::DrawMode::fill(), graphics::Rect::new(to_screen(x), 0.0, 1.0, SCREEN_HEIGHT), faint);
+            x += step;
+        }
+        let mut y = 0.0;
+        while y < FLOOR_Y {
+            builder.rectangle(graphics::DrawMode::fill(), graphics::Rect::new(0.0, y * SCALE, SCREEN_WIDTH, 1.0), faint);
+            y += step;
+        }
+
+        for start in &self.course.section_starts {
+            let rect = graphics::Rect::new(to_screen(*start), 0.0, 3.0, SCREEN_HEIGHT);
+            builder.rectangle(graphics::DrawMode::fill(), rect, graphics::Color::from_rgb(255, 220, 0));
+        }
+
+        if let Some(item) = self.selected.and_then(|i| self.course.items.get(i)) {
+            let bounds = self.bounds(item);
+            let rect = graphics::Rect::new(to_screen(bounds.x), bounds.y * SCALE, bounds.w * SCALE, bounds.h * SCALE);
+            builder.rectangle(graphics::DrawMode::stroke(2.0), rect, graphics::Color::from_rgb(255, 80, 80));
+        }
+
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())
+    }
+
+    /// Draws the whole course squashed into a strip, with the part on screen marked.
+    fn draw_timeline(&self, ctx: &mut Context) -> GameResult {
+        let length = self.course_length();
+        let to_timeline = |distance: f32| distance / length * SCREEN_WIDTH;
+        let mut builder = graphics::MeshBuilder::new();
+        builder.rectangle(
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, TIMELINE_TOP, SCREEN_WIDTH, TIMELINE_HEIGHT),
+            graphics::Color::new(0.0, 0.0, 0.0, 0.5),
+        );
+        for item in &self.course.items {
+            let colour = if item.obstacle.is_pipe() {
+                graphics::Color::from_rgb(80, 200, 80)
+            } else {
+                graphics::WHITE
+            };
+            let rect = graphics::Rect::new(to_timeline(item.distance), TIMELINE_TOP + 4.0, 2.0, TIMELINE_HEIGHT - 8.0);
+            builder.rectangle(graphics::DrawMode::fill(), rect, colour);
+        }
+        for start in &self.course.section_starts {
+            let rect = graphics::Rect::new(to_timeline(*start), TIMELINE_TOP, 2.0, TIMELINE_HEIGHT);
+            builder.rectangle(graphics::DrawMode::fill(), rect, graphics::Color::from_rgb(255, 220, 0));
+        }
+        let view = graphics::Rect::new(
+            to_timeline(self.scroll),
+            TIMELINE_TOP,
+            to_timeline(SCREEN_WIDTH / SCALE),
+            TIMELINE_HEIGHT,
+        );
+        builder.rectangle(graphics::DrawMode::stroke(2.0), view, graphics::WHITE);
+
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())
+    }
+}
+
+fn empty_level(name: &str) -> Level {
+    Level {
+        name: name.to_string(),
+        sections: vec![Section {
+            background: [112, 216, 255],
+            speed: 1.0,
+            music: None,
+            spacing: None,
+            obstacles: vec![],
+        }],
+    }
+}
+
+/// What each number key puts down.
+fn tools() -> Vec<Obstacle> {
+    vec![
+        Obstacle::Pipe { gap_centre: 80.0, gap_height: crate::pipe::VERTICAL_GAP, after: None },
+        Obstacle::BobbingPipe { gap_centre: 80.0, gap_height: crate::pipe::VERTICAL_GAP, amplitude: 10.0, after: None },
+        Obstacle::JawPipe { gap_centre: 80.0, gap_height: crate::pipe::VERTICAL_GAP, closed_gap: 36.0, after: None },
+        Obstacle::Jellyfish { y: 80.0, after: None },
+        Obstacle::Seagull { after: None },
+        Obstacle::Pearl { y: 80.0, after: None },
+        power_up_tool(PowerUp::Shield),
+    ]
+}
+
+fn power_up_tool(power_up: PowerUp) -> Obstacle {
+    Obstacle::PowerUp { power_up, y: 80.0, after: None }
+}
+
+fn tool_name(tool: &Obstacle) -> String {
+    match tool {
+        Obstacle::PowerUp { power_up, .. } => format!("{} ({})", tool.key(), power_up.name()),
+        _ => tool.key().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::ATLAS_FILE;
+    use crate::pipe::pipe_spacing;
+
+    /// The width of the pipe sprite in the atlas.
+    const PIPE_WIDTH: f32 = 32.0;
+
+    fn resources() -> PathBuf {
+        Path::new("resources").to_path_buf()
+    }
+
+    /// An editor on a level file that doesn't exist yet, so it starts empty and never saves.
+    fn new_editor(file: &str) -> Editor {
+        let path = std::env::temp_dir().join(file);
+        let _ = std::fs::remove_file(&path);
+        let atlas = Atlas::parse_atlas_json(&resources().join(ATLAS_FILE));
+        Editor::open(&atlas, &path)
+    }
+
+    /// Puts down whatever the tool is at (x, y) on the course.
+    fn place(editor: &mut Editor, x: f32, y: f32) {
+        editor.mouse_down(MouseButton::Left, (x - editor.scroll) * SCALE, y * SCALE);
+        editor.mouse_up();
+    }
+
+    #[test]
+    fn to_level_round_trips_through_the_loader() {
+        let spacing = pipe_spacing(PIPE_WIDTH);
+        for file in &["rock_pools.json", "open_water.json"] {
+            let original = level::load_level(&resources().join(level::LEVELS_DIR).join(file)).unwrap();
+            let course = Course::from_level(&original, spacing);
+
+            let path = std::env::temp_dir().join(format!("crab_editor_{}", file));
+            level::save_level(&path, &course.to_level(&original.name, 0.0)).unwrap();
+            let saved = level::load_level(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(saved.name, original.name);
+            assert_eq!(saved.sections.len(), original.sections.len(), "{}", file);
+            for (saved, original) in saved.sections.iter().zip(&original.sections) {
+                assert_eq!(saved.obstacles.len(), original.obstacles.len(), "{}", file);
+            }
+            let reloaded = Course::from_level(&saved, spacing);
+            assert_eq!(reloaded.section_starts, course.section_starts, "{}", file);
+            let where_items =
+                |course: &Course| course.items.iter().map(|i| (i.section, i.distance)).collect::<Vec<_>>();
+            assert_eq!(where_items(&reloaded), where_items(&course), "{}", file);
+            assert_eq!(reloaded.to_level(&saved.name, 0.0), saved, "{}", file);
+        }
+    }
+
+    #[test]
+    fn to_level_from_the_cursor() {
+        let spacing = pipe_spacing(PIPE_WIDTH);
+        let original = level::load_level(&resources().join(level::LEVELS_DIR).join("rock_pools.json")).unwrap();
+        let course = Course::from_level(&original, spacing);
+        let from = course.section_starts[1] + 1.0;
+
+        let level = course.to_level(&original.name, from);
+        assert_eq!(level.sections.len(), 1);
+        assert_eq!(level.sections[0].obstacles.len(), original.sections[1].obstacles.len());
+        let (_, placements) = level::layout(&level, spacing);
+        for (placement, item) in placements.iter().zip(course.items.iter().filter(|item| item.distance >= from)) {
+            assert_eq!(placement.distance + from, item.distance);
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut editor = new_editor("crab_editor_undo_test.json");
+        let empty = editor.course.clone();
+        place(&mut editor, 100.0, 80.0);
+        let one = editor.course.clone();
+        place(&mut editor, 200.0, 80.0);
+        let two = editor.course.clone();
+        assert_eq!(two.items.len(), 2);
+
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL);
+        assert_eq!(editor.course, one);
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL);
+        assert_eq!(editor.course, empty);
+        // Nothing left to undo.
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL);
+        assert_eq!(editor.course, empty);
+
+        editor.handle_key(KeyCode::Y, KeyMods::CTRL);
+        assert_eq!(editor.course, one);
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL | KeyMods::SHIFT);
+        assert_eq!(editor.course, two);
+
+        // A new change can't be redone over.
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL);
+        place(&mut editor, 300.0, 80.0);
+        let changed = editor.course.clone();
+        editor.handle_key(KeyCode::Y, KeyMods::CTRL);
+        assert_eq!(editor.course, changed);
+    }
+
+    #[test]
+    fn clicking_without_a_change_leaves_nothing_to_undo() {
+        let mut editor = new_editor("crab_editor_click_test.json");
+        place(&mut editor, 100.0, 80.0);
+        // Clicking the same item picks it up and puts it back where it was.
+        place(&mut editor, 100.0, 80.0);
+        assert_eq!(editor.undo.len(), 1);
+    }
+
+    #[test]
+    fn new_sections_take_what_comes_after_them() {
+        let mut editor = new_editor("crab_editor_section_test.json");
+        place(&mut editor, 100.0, 80.0);
+        place(&mut editor, 200.0, 80.0);
+        editor.mouse_moved((150.0 - editor.scroll) * SCALE, 80.0 * SCALE);
+        editor.handle_key(KeyCode::N, KeyMods::empty());
+
+        let level = editor.course.to_level("Sections", 0.0);
+        assert_eq!(level.sections.len(), 2);
+        assert_eq!(level.sections[0].obstacles.len(), 1);
+        assert_eq!(level.sections[1].obstacles.len(), 1);
+        editor.handle_key(KeyCode::Z, KeyMods::CTRL);
+        assert_eq!(editor.course.to_level("Sections", 0.0).sections.len(), 1);
+    }
+}
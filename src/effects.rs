@@ -0,0 +1,71 @@
+use crate::settings::EffectSettings;
+use crate::window::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use ggez::graphics;
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use rand::{thread_rng, Rng};
+
+/// Furthest the screen can move in pixels at full trauma.
+const MAX_SHAKE: f32 = 12.0;
+const SHAKE_DECAY: f32 = 0.04;
+const FLASH_DECAY: f32 = 0.05;
+
+/// Screen shake and flash played when the crab dies.
+pub struct Effects {
+    settings: EffectSettings,
+    trauma: f32,
+    flash: f32,
+}
+
+impl Effects {
+    pub fn new(settings: EffectSettings) -> Self {
+        Self {
+            settings,
+            trauma: 0.0,
+            flash: 0.0,
+        }
+    }
+
+    /// Kicks off the shake and flash. Either can be disabled in the settings.
+    pub fn hit(&mut self) {
+        self.trauma = 1.0;
+        self.flash = self.settings.flash;
+    }
+
+    pub fn update(&mut self) {
+        self.trauma = (self.trauma - SHAKE_DECAY).max(0.0);
+        self.flash = (self.flash - FLASH_DECAY).max(0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.trauma = 0.0;
+        self.flash = 0.0;
+    }
+
+    /// Where the world should be drawn this frame.
+    /// Squaring the trauma makes the shake die off quickly.
+    pub fn shake_offset(&self) -> Point2<f32> {
+        let strength = MAX_SHAKE * self.settings.screen_shake * self.trauma * self.trauma;
+        if strength <= 0.0 {
+            return Point2::new(0.0, 0.0);
+        }
+        let mut rng = thread_rng();
+        Point2::new(
+            rng.gen_range(-1.0, 1.0) * strength,
+            rng.gen_range(-1.0, 1.0) * strength,
+        )
+    }
+
+    pub fn draw_flash(&self, ctx: &mut Context) -> GameResult {
+        if self.flash <= 0.0 {
+            return Ok(());
+        }
+        let mesh = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT),
+            graphics::Color::new(1.0, 1.0, 1.0, self.flash.min(1.0)),
+        )?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())
+    }
+}
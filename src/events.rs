@@ -0,0 +1,49 @@
+use crate::collectibles::Collectible;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// What killed the crab.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Pipe,
+    Ground,
+    Jellyfish,
+    Seagull,
+}
+
+impl DeathCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Pipe => "Pipe",
+            DeathCause::Ground => "Ground",
+            DeathCause::Jellyfish => "Jellyfish",
+            DeathCause::Seagull => "Seagull",
+        }
+    }
+}
+
+/// Things that happen during a run that other systems want to hear about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// The player left the start screen on the course made from this seed.
+    RunStarted { seed: u32 },
+    /// The player made the crab jump.
+    Flapped,
+    /// The crab passed a pipe. Holds the new score.
+    Scored(i128),
+    /// The crab passed a pipe. Holds the closest it came to hitting it.
+    PipeCleared { clearance: f32 },
+    /// The crab picked something up.
+    Collected(Collectible),
+    /// The crab bumped its head on the top of the screen.
+    HitCeiling,
+    /// How long the crab has been alive this run.
+    Survived(Duration),
+    /// The crab hit something.
+    Died(DeathCause),
+    /// The crab landed after dying. Holds the final score.
+    RunEnded(i128),
+}
+
+/// How close the crab has to pass a pipe for it to count as a near miss.
+pub const NEAR_MISS: f32 = 4.0;
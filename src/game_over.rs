@@ -0,0 +1,89 @@
+use crate::achievements::Medal;
+use crate::window::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+
+const PANEL_WIDTH: f32 = 360.0;
+const PANEL_HEIGHT: f32 = 180.0;
+
+/// Draws the panel shown once the crab has landed after dying.
+pub fn draw_game_over(
+    ctx: &mut Context,
+    score: i128,
+    best_score: i128,
+    medal: Option<Medal>,
+) -> GameResult {
+    let medal = medal.map_or("None", |m| m.name());
+    draw_panel(
+        ctx,
+        &[
+            "GAME OVER".to_string(),
+            format!("Score: {}", score),
+            format!("Best: {}", best_score),
+            format!("Medal: {}", medal),
+            "Press space to play again".to_string(),
+        ],
+    )
+}
+
+/// Draws the panel shown once the crab crosses a level's finish line.
+pub fn draw_level_complete(ctx: &mut Context, level_name: &str, score: i128, last_level: bool) -> GameResult {
+    let next = if last_level {
+        "Campaign complete! Press space"
+    } else {
+        "Press space for the next level"
+    };
+    draw_panel(
+        ctx,
+        &[
+            "LEVEL COMPLETE".to_string(),
+            level_name.to_string(),
+            format!("Score: {}", score),
+            next.to_string(),
+        ],
+    )
+}
+
+/// Draws the panel shown over a run that's about to carry on from somewhere, before it starts moving again.
+pub fn draw_paused(ctx: &mut Context, message: &str) -> GameResult {
+    draw_panel(
+        ctx,
+        &[
+            "PAUSED".to_string(),
+            message.to_string(),
+            "Press space to carry on".to_string(),
+        ],
+    )
+}
+
+/// Draws the results at the end of a match.
+pub fn draw_results(ctx: &mut Context, lines: &[String]) -> GameResult {
+    draw_panel(ctx, lines)
+}
+
+/// The panel grows to fit however many lines it has.
+fn draw_panel(ctx: &mut Context, lines: &[String]) -> GameResult {
+    let height = PANEL_HEIGHT.max(40.0 + 30.0 * lines.len() as f32);
+    let left = (SCREEN_WIDTH - PANEL_WIDTH) / 2.0;
+    let top = (SCREEN_HEIGHT - height) / 2.0;
+
+    let panel = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        graphics::Rect::new(left, top, PANEL_WIDTH, height),
+        graphics::Color::new(0.0, 0.0, 0.0, 0.6),
+    )?;
+    graphics::draw(ctx, &panel, graphics::DrawParam::new())?;
+
+    for (i, line) in lines.iter().enumerate() {
+        let text = Text::new(line.as_str());
+        graphics::draw(
+            ctx,
+            &text,
+            (Point2::new(left + 20.0, top + 20.0 + 30.0 * i as f32), graphics::WHITE),
+        )?;
+    }
+
+    Ok(())
+}
@@ -1,89 +1,736 @@
 use ggez::{
-    graphics::{spritebatch::SpriteBatch},
+    audio::{SoundSource, Source},
+    event::{KeyCode, KeyMods},
+    graphics::{self, spritebatch::SpriteBatch},
     Context,
 };
 use audio::Player;
 use crate::crab::{PlayerEntity, create_player};
-use crate::tile::{TileEntity, create_tiles};
-use crate::entity::{PlayState, PipeEntity};
-use crate::pipe::{create_pipes, PipeTracker};
-use crate::{audio, atlas, pipe, RESTART_AFTER};
+use crate::tile::spawn_tiles;
+use crate::entity::PlayState;
+use crate::pipe::{spawn_pipes, PipeTracker};
+use crate::world::World;
+use crate::achievements::{Achievements, Medal};
+use crate::collectibles::{Collectible, PowerUps, Wallet};
+use crate::cosmetics::Wardrobe;
+use crate::daylight::Daylight;
+use crate::effects::Effects;
+use crate::events::{DeathCause, GameEvent};
+use crate::menu::MenuPage;
+use crate::editor::{Editor, EditorAction, CUSTOM_LEVEL};
+use crate::cli::{Mode, Options};
+use crate::course::RECORDED_COURSE;
+use crate::recording::{Recording, LAST_RUN};
+use crate::assists::Assists;
+use crate::ghost::Ghost;
+use crate::input::{InputMap, MAX_PLAYERS};
+use crate::local_match::LocalMatch;
+use crate::palette::{Outlines, Palette};
+use crate::practice::Practice;
+use crate::snapshot::{RewindBuffer, Snapshot, SUSPENDED_RUN};
+use crate::level::{Campaign, Level, LevelRun, LEVELS_DIR};
+use crate::obstacles::{ObstacleGenerator, ObstacleSprites};
+use crate::settings::Settings;
+use crate::stats::Stats;
+use crate::theme::Themes;
+use crate::{audio, atlas, online, pipe, storage, HIT_STOP};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Where the first pipe of the endless course starts.
+pub const FIRST_PIPE_X: f32 = 200.0;
+/// The sky colour when nothing says otherwise.
+pub const DEFAULT_BACKGROUND: (u8, u8, u8) = (112, 216, 255);
 
 pub struct GameState {
-    pub tiles_drawn: bool,
-    pub pipes: Vec<Box<PipeEntity>>,
-    pub tiles: Vec<Box<TileEntity>>,
+    /// The pipes and the floor.
+    pub world: World,
     pub player: Box<PlayerEntity>,
     /// The sprite batch of all the images
     pub sprite_batch: SpriteBatch,
     /// The struct that moves the pipes around :)
     /// Can use any function over time between 0 and 600/16
     pub pipe_tracker: PipeTracker,
+    /// Decides which obstacles turn up as the score goes up.
+    pub obstacles: ObstacleGenerator,
     pub play_state: PlayState,
     atlas: atlas::Atlas,
     pub score: i128,
     pub best_score: i128,
     pub sound_player: audio::Player,
+    pub settings: Settings,
+    pub effects: Effects,
+    pub achievements: Achievements,
+    pub stats: Stats,
+    /// The medal won by the last finished run.
+    pub run_medal: Option<Medal>,
+    /// When the current run left the start screen.
+    pub run_started: Duration,
+    /// The closest the crab has come to a pipe since its last point.
+    pub closest_clearance: f32,
+    pub on_ceiling: bool,
+    pub power_ups: PowerUps,
+    pub wallet: Wallet,
+    /// Pearls picked up this run.
+    pub run_pearls: u64,
+    pub menu_page: Option<MenuPage>,
+    /// The campaign being played, if the player isn't playing the endless course.
+    pub campaign: Option<Campaign>,
+    /// The level being played, if there is one.
+    pub level_run: Option<LevelRun>,
+    /// Why the campaign couldn't be loaded, shown on the start screen.
+    pub level_error: Option<String>,
+    pub background: graphics::Color,
+    music: Option<Source>,
+    pub editor: Option<Editor>,
+    /// The level being tried out from the editor.
+    test_level: Option<Level>,
+    /// Where the images, sounds and data files are.
+    pub resources: PathBuf,
+    /// How many updates the current run has been going for.
+    pub run_ticks: u32,
+    /// The flaps of the current run, to be saved once it's over.
+    pub recording: Recording,
+    /// The run being watched, if the flaps are coming from a recording instead of the keyboard.
+    pub replay: Option<Recording>,
+    /// Set in practice mode. Practice runs can be rewound and go back to checkpoints,
+    /// and don't count towards scores, stats or achievements.
+    pub practice: Option<Practice>,
+    /// How far the course has moved this run.
+    pub distance: f32,
+    /// How long a run carried on from a save had already lasted.
+    survived_before: Duration,
+    /// The best run on this course, to race against.
+    pub ghost: Option<Ghost>,
+    /// Which keys each player flaps with.
+    pub input: InputMap,
+    /// Set when two to four crabs are playing the endless course together.
+    pub local_match: Option<LocalMatch>,
+    /// The colours everything is drawn in.
+    pub palette: Palette,
+    /// Lines around the crab and the pipes, if the palette has them.
+    pub outlines: Option<Outlines>,
+    /// What the crab can wear and is wearing.
+    pub wardrobe: Wardrobe,
+    /// How the endless course looks and sounds.
+    pub themes: Themes,
+    /// The light as the day goes by.
+    pub daylight: Daylight,
 }
 
 impl GameState {
-    pub fn handle_after_losing(&mut self, ctx: &mut Context, state: PlayState) {
-        match state {
-            PlayState::Dead { time } => {
-                if (ggez::timer::time_since_start(ctx) - time) > RESTART_AFTER {
-                    self.restart()
+    /// Plays out the death sequence.
+    /// Everything freezes for a moment, then the crab tumbles to the floor
+    /// and the game over panel waits for the player to restart.
+    pub fn handle_after_losing(&mut self, ctx: &mut Context, time: Duration) {
+        if (ggez::timer::time_since_start(ctx) - time) < HIT_STOP {
+            return;
+        }
+
+        if self.player.tumble() {
+            if self.back_to_checkpoint() {
+                return;
+            }
+            self.play_state = PlayState::GameOver;
+            self.end_run();
+        }
+    }
+
+    fn end_run(&mut self) {
+        self.stop_music();
+        if self.practice.is_some() {
+            return;
+        }
+        self.swap_scores();
+        if let (Some(ghost), None) = (&mut self.ghost, &self.replay) {
+            ghost.finish(self.score as i64);
+        }
+        self.emit(GameEvent::RunEnded(self.score));
+        self.wallet.save();
+        if self.level_run.is_none() && self.replay.is_none() {
+            storage::save(RECORDED_COURSE, &self.pipe_tracker.history);
+            storage::save(LAST_RUN, &self.recording);
+            if let Some(settings) = &self.settings.online {
+                online::submit_in_background(settings, self.score as i64, self.recording.clone());
+            }
+        }
+        self.run_medal = self.achievements.finish_run(self.score);
+    }
+
+    /// Passes a game event on to everything that is listening.
+    pub fn emit(&mut self, event: GameEvent) {
+        if self.practice.is_some() {
+            return;
+        }
+        self.achievements.handle(&event);
+        self.stats.handle(&event);
+    }
+
+    pub fn start_run(&mut self, ctx: &mut Context) {
+        self.play_state = PlayState::Play;
+        self.run_started = ggez::timer::time_since_start(ctx);
+        self.survived_before = Duration::from_secs(0);
+        self.emit(GameEvent::RunStarted { seed: self.pipe_tracker.seed });
+        self.stats.run.assisted = self.settings.assists.any();
+        if self.level_run.is_some() {
+            self.enter_section(ctx);
+        } else {
+            self.play_music(ctx, self.themes.theme().music.clone());
+        }
+    }
+
+    /// How fast the section of the level being played goes, or 1.0 outside levels.
+    pub fn section_speed(&self) -> f32 {
+        self.level_run.as_ref().map_or(1.0, |run| run.current_section().speed)
+    }
+
+    /// Moves the level along, if one is being played,
+    /// and finishes it once the crab crosses the finish line.
+    pub fn update_level(&mut self, ctx: &mut Context, speed: f32) {
+        if !self.play_state.is_playing() {
+            return;
+        }
+        let crab_x = self.player.position.x;
+        let (new_section, finished) = match &mut self.level_run {
+            Some(run) => (run.update(&mut self.world, speed, crab_x), run.finished(crab_x)),
+            None => return,
+        };
+        if new_section {
+            self.enter_section(ctx);
+        }
+        if finished {
+            self.play_state = PlayState::LevelComplete;
+            self.end_run();
+        }
+    }
+
+    /// Switches to the background and music of the level section the crab is in.
+    fn enter_section(&mut self, ctx: &mut Context) {
+        let (background, music) = match &self.level_run {
+            Some(run) => (run.background(), run.current_section().music.clone()),
+            None => return,
+        };
+        self.background = background;
+        self.play_music(ctx, music);
+    }
+
+    /// Plays the music on a loop, instead of whatever was playing before.
+    fn play_music(&mut self, ctx: &mut Context, music: Option<String>) {
+        self.stop_music();
+        if let Some(path) = music {
+            match Source::new(ctx, path.as_str()) {
+                Ok(mut source) => {
+                    source.set_repeat(true);
+                    if let Err(e) = source.play() {
+                        println!("Couldn't play {}: {}", path, e);
+                    }
+                    self.music = Some(source);
                 }
+                Err(e) => println!("Couldn't load {}: {}", path, e),
             }
-            _ => {}
         }
     }
 
+    /// Shows the theme the themes are on now, and plays its music if a run is going.
+    /// Levels keep their own sky and music.
+    pub fn show_theme(&mut self, ctx: &mut Context) {
+        self.dress_theme();
+        if self.level_run.is_none() && self.play_state.is_playing() {
+            self.play_music(ctx, self.themes.theme().music.clone());
+        }
+    }
+
+    /// Swaps in the images, sounds and sky of the theme the themes are on now.
+    fn dress_theme(&mut self) {
+        self.sprite_batch.set_image(self.themes.image());
+        if let (Some(outlines), Some(image)) = (&mut self.outlines, self.themes.outline_image()) {
+            outlines.set_image(image);
+        }
+        self.sound_player.use_theme(self.themes.current);
+        if self.level_run.is_none() {
+            self.background = self.themes.theme().sky();
+        }
+    }
+
+    /// Starts runs in the next theme.
+    pub fn next_theme(&mut self) {
+        self.themes.next_start();
+        self.restart();
+    }
+
+    fn stop_music(&mut self) {
+        if let Some(music) = &mut self.music {
+            music.stop();
+        }
+        self.music = None;
+    }
+
+    /// Everything about the run that decides how it plays out from here.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            world: self.world.clone(),
+            player: self.player.clone(),
+            pipe_tracker: self.pipe_tracker.clone(),
+            obstacles: self.obstacles.clone(),
+            power_ups: self.power_ups.clone(),
+            score: self.score as i64,
+            run_pearls: self.run_pearls,
+            closest_clearance: self.closest_clearance,
+            on_ceiling: self.on_ceiling,
+            run_ticks: self.run_ticks,
+            recording: self.recording.clone(),
+            run: self.stats.run.clone(),
+            distance: self.distance,
+        }
+    }
+
+    /// Puts the run back the way it was when the snapshot was taken.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.world = snapshot.world;
+        self.player = snapshot.player;
+        self.wardrobe.dress(&mut self.player);
+        self.pipe_tracker = snapshot.pipe_tracker;
+        self.obstacles = snapshot.obstacles;
+        self.power_ups = snapshot.power_ups;
+        self.score = snapshot.score as i128;
+        self.run_pearls = snapshot.run_pearls;
+        self.closest_clearance = snapshot.closest_clearance;
+        self.on_ceiling = snapshot.on_ceiling;
+        self.run_ticks = snapshot.run_ticks;
+        self.recording = snapshot.recording;
+        self.stats.run = snapshot.run;
+        self.distance = snapshot.distance;
+        if self.level_run.is_none() && self.themes.for_score(self.score) {
+            self.dress_theme();
+        }
+        if let Some(practice) = &mut self.practice {
+            practice.went_back(self.distance);
+        }
+    }
+
+    /// Keeps track of the practice run of the endless course: where the crab has been,
+    /// a snapshot every so often to rewind to and one at every pipe for checkpoints.
+    pub fn update_practice(&mut self, passed_pipe: bool) {
+        if self.level_run.is_some() {
+            return;
+        }
+        let wants_rewind = RewindBuffer::wants(self.run_ticks);
+        let snapshot = match &self.practice {
+            Some(_) if wants_rewind || passed_pipe => Some(self.snapshot()),
+            _ => None,
+        };
+        let (distance, y) = (self.distance, self.player.position.y);
+        if let Some(practice) = &mut self.practice {
+            practice.record(distance, y);
+            if let Some(snapshot) = snapshot {
+                if passed_pipe {
+                    practice.passed_pipe(snapshot.clone());
+                }
+                if wants_rewind {
+                    practice.rewind.push(snapshot);
+                }
+            }
+        }
+    }
+
+    /// Makes the last pipe the crab got past the checkpoint.
+    pub fn set_checkpoint(&mut self) {
+        if let (Some(practice), PlayState::Play) = (&mut self.practice, &self.play_state) {
+            if !practice.set_checkpoint() {
+                println!("Get past a pipe before setting a checkpoint");
+            }
+        }
+    }
+
+    /// Puts the crab back at the checkpoint after it dies in practice mode.
+    /// Returns false if there isn't a checkpoint to go back to.
+    fn back_to_checkpoint(&mut self) -> bool {
+        if self.level_run.is_some() {
+            return false;
+        }
+        let checkpoint = match &self.practice {
+            Some(practice) => practice.checkpoint.clone(),
+            None => None,
+        };
+        match checkpoint {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                self.play_state = PlayState::Paused;
+                self.effects.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Goes back about three seconds, as long as the crab is still alive.
+    pub fn rewind(&mut self) {
+        let snapshot = match &mut self.practice {
+            Some(practice) => practice.rewind.rewind(&self.play_state, self.run_ticks),
+            None => None,
+        };
+        if let Some(snapshot) = snapshot {
+            self.restore(snapshot);
+            self.effects.clear();
+        }
+    }
+
+    /// Whether quitting now should keep the run to carry on with next time.
+    pub fn can_suspend(&self) -> bool {
+        self.play_state.is_playing()
+            && self.level_run.is_none()
+            && self.replay.is_none()
+            && self.practice.is_none()
+            && self.local_match.is_none()
+    }
+
+    /// Saves the run so the game carries on from here next time it's started.
+    pub fn suspend(&mut self) {
+        storage::save(SUSPENDED_RUN, &self.snapshot());
+    }
+
+    /// Carries on with a run that was saved when the game was quit, or with a paused one.
+    pub fn resume(&mut self, ctx: &mut Context) {
+        self.play_state = PlayState::Play;
+        self.run_started = ggez::timer::time_since_start(ctx);
+        self.survived_before = Duration::from_secs_f32(self.stats.run.duration_secs);
+        if self.music.is_none() && self.level_run.is_none() {
+            self.play_music(ctx, self.themes.theme().music.clone());
+        }
+    }
+
+    /// How long the crab has been alive this run.
+    pub fn survived(&self, ctx: &Context) -> Duration {
+        ggez::timer::time_since_start(ctx) - self.run_started + self.survived_before
+    }
+
+    /// Switches practice mode on or off.
+    pub fn toggle_practice(&mut self) {
+        self.local_match = None;
+        self.leave_practice_score();
+        self.practice = match self.practice {
+            Some(_) => None,
+            None => Some(Practice::new()),
+        };
+        self.restart();
+    }
+
+    /// Switches between the endless course and the campaign.
+    pub fn toggle_campaign(&mut self) {
+        self.local_match = None;
+        if let Some(practice) = &mut self.practice {
+            practice.forget();
+        }
+        if self.campaign.is_some() {
+            self.campaign = None;
+        } else {
+            match Campaign::load(&self.resources.join(LEVELS_DIR)) {
+                Ok(campaign) => {
+                    self.campaign = Some(campaign);
+                    self.level_error = None;
+                }
+                Err(e) => {
+                    println!("Couldn't load the campaign: {}", e);
+                    self.level_error = Some(e.to_string());
+                }
+            }
+        }
+        self.restart();
+    }
+
+    /// Goes from one crab to two, three and four crabs playing the endless course together, then back to one.
+    pub fn toggle_local_match(&mut self) {
+        let players = self.local_match.as_ref().map_or(1, |round| round.players()) + 1;
+        self.local_match = if players > MAX_PLAYERS {
+            None
+        } else {
+            self.leave_practice_score();
+            self.practice = None;
+            self.campaign = None;
+            Some(LocalMatch::new(&self.atlas, players))
+        };
+        self.restart();
+    }
+
+    /// Forgets the score of a practice run, so it isn't taken for the best score once practice is over.
+    fn leave_practice_score(&mut self) {
+        if self.practice.is_some() {
+            self.score = 0;
+        }
+    }
+
+    /// Passes a key on to the wardrobe, and dresses the crab in whatever it's wearing now.
+    pub fn wardrobe_key(&mut self, keycode: KeyCode) {
+        if self.wardrobe.key(keycode, &mut self.wallet, &self.achievements) {
+            self.wardrobe.dress(&mut self.player);
+        }
+    }
+
+    /// Opens the editor on the campaign level being played, or on the custom level.
+    pub fn open_editor(&mut self) {
+        let dir = self.resources.join(LEVELS_DIR);
+        let path = match &self.campaign {
+            Some(campaign) => dir.join(&campaign.files[campaign.current]),
+            None => dir.join(CUSTOM_LEVEL),
+        };
+        self.editor = Some(Editor::open(&self.atlas, &path));
+        self.play_state = PlayState::Editing;
+    }
+
+    pub fn editor_key(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods) {
+        let action = match &mut self.editor {
+            Some(editor) => editor.handle_key(keycode, keymods),
+            None => return,
+        };
+        match action {
+            EditorAction::Nothing => {}
+            EditorAction::Leave => {
+                self.editor = None;
+                self.restart();
+            }
+            EditorAction::PlayTest(level) => {
+                self.test_level = Some(level);
+                self.restart();
+                self.start_run(ctx);
+            }
+        }
+    }
+
+    /// Goes back to the editor once a play test is over.
+    pub fn return_to_editor(&mut self) {
+        self.test_level = None;
+        self.restart();
+        self.play_state = PlayState::Editing;
+    }
+
+    /// Moves on from a finished level to the next one,
+    /// or back to the endless course once the campaign is done.
+    pub fn next_level(&mut self) {
+        let more = self.campaign.as_mut().map_or(false, |campaign| campaign.advance());
+        if !more {
+            self.campaign = None;
+        }
+        self.restart();
+    }
+
+    /// Pearls go in the wallet. Power ups were switched on as soon as the run picked them up.
+    pub fn pick_up(&mut self, collectible: Collectible) {
+        if collectible == Collectible::Pearl {
+            self.run_pearls += 1;
+            self.wallet.pearls += 1;
+        }
+        self.sound_player.score();
+        self.emit(GameEvent::Collected(collectible));
+    }
+
+    /// Called when the crab hits something.
+    pub fn kill_player(&mut self, ctx: &mut Context, cause: DeathCause) {
+        if let Some(practice) = &mut self.practice {
+            practice.end_attempt();
+        }
+        self.sound_player.ouch();
+        self.effects.hit();
+        self.play_state.set_dead(ggez::timer::time_since_start(ctx));
+        self.emit(GameEvent::Died(cause));
+    }
+
     /// Creates a new GameState
     /// Panics if can't access the sprite image resource.
-    pub fn new(ctx: &mut Context, sprite_batch: SpriteBatch) -> Self {
-        let mut pipe_tracker = pipe::PipeTracker::new();
-        let atlas =
-            atlas::Atlas::parse_atlas_json(std::path::Path::new("resources/texture_atlas.json"));
-        let sound_player = Player::new(ctx);
-
-        Self {
-            tiles_drawn: false,
-            pipes: GameState::create_start_entities(&atlas, &mut pipe_tracker),
-            player: create_player(&atlas),
-            tiles : create_tiles(&atlas),
+    /// When there is a `replay`, its course is played and its flaps are used instead of the keyboard.
+    pub fn new(ctx: &mut Context, mut sprite_batch: SpriteBatch, options: &Options, replay: Option<Recording>) -> Self {
+        let mut settings = Settings::load(&options.settings_file());
+        let palette = Palette::new(&settings.palette);
+        let themes = Themes::load(ctx, &options.resources, &settings.theme, &palette);
+        let atlas = atlas::Atlas::parse_atlas_json(&themes.atlas_file(&options.resources));
+        let sound_player = Player::new(ctx, themes.list().iter().map(|theme| &theme.sounds));
+        let mut seed = options.seed;
+        if let Some(replay) = &replay {
+            settings.course = replay.course.clone();
+            settings.assists = replay.assists.clone();
+            seed = replay.seed;
+        }
+        settings.assists = settings.assists.clamped();
+        let mut pipe_tracker =
+            pipe::PipeTracker::new(seed, &settings.course, settings.assists.gap_height());
+        let mut effect_settings = settings.effects.clone();
+        if settings.assists.reduced_motion {
+            effect_settings.screen_shake = 0.0;
+            effect_settings.flash = 0.0;
+        }
+        let effects = Effects::new(effect_settings);
+        let stats = Stats::new();
+        let recording = Recording {
+            seed,
+            course: settings.course.clone(),
+            assists: settings.assists.clone(),
+            ..Recording::default()
+        };
+        let ghost = if settings.assists.any() {
+            None
+        } else {
+            Some(Ghost::endless(seed, &settings.course))
+        };
+        let mut player = create_player(&atlas);
+        player.assists = settings.assists.clone();
+        let mut wardrobe = Wardrobe::new(ctx, &options.resources, &atlas);
+        wardrobe.dress(&mut player);
+        let input = InputMap::new(&settings.controls);
+        let daylight = Daylight::new(&settings.daylight, &atlas);
+        sprite_batch.set_image(themes.image());
+        let outlines = themes.outline_image().map(Outlines::new);
+
+        let mut game = Self {
+            world: GameState::create_world(&atlas, &mut pipe_tracker),
+            player,
             sprite_batch,
+            obstacles: ObstacleGenerator::new(ObstacleSprites::new(&atlas), pipe_tracker.seed),
             pipe_tracker,
             play_state: PlayState::StartScreen,
             atlas,
             score: 0,
-            best_score: 0,
-            sound_player
+            best_score: stats.lifetime.best_score as i128,
+            sound_player,
+            settings,
+            effects,
+            achievements: Achievements::new(&options.resources),
+            stats,
+            run_medal: None,
+            run_started: Duration::from_secs(0),
+            closest_clearance: std::f32::MAX,
+            on_ceiling: false,
+            power_ups: PowerUps::new(),
+            wallet: Wallet::load(),
+            run_pearls: 0,
+            menu_page: None,
+            campaign: None,
+            level_run: None,
+            level_error: None,
+            background: themes.theme().sky(),
+            music: None,
+            editor: None,
+            test_level: None,
+            resources: options.resources.clone(),
+            run_ticks: 0,
+            recording,
+            replay,
+            practice: None,
+            distance: 0.0,
+            survived_before: Duration::from_secs(0),
+            ghost,
+            input,
+            local_match: None,
+            palette,
+            outlines,
+            wardrobe,
+            themes,
+            daylight,
+        };
+        game.dress_theme();
+        if options.mode == Mode::Campaign {
+            game.toggle_campaign();
+        } else if game.replay.is_none() {
+            if let Some(snapshot) = storage::take::<Snapshot>(SUSPENDED_RUN) {
+                game.restore(snapshot);
+                game.play_state = PlayState::Paused;
+                // The ghost can't line up with a run that started before the game did.
+                game.ghost = None;
+            }
         }
+        game
     }
 
-    /// The last entity *must* be the player.
-    pub fn create_start_entities(
+    /// Fills a new world with the floor and the starting pipes.
+    pub fn create_world(
         sprites: &atlas::Atlas,
         pipe_tracker: &mut PipeTracker,
-    ) -> Vec<Box<PipeEntity>> {
-        let pipes = create_pipes(
+    ) -> World {
+        let mut world = World::new();
+        spawn_tiles(&mut world, sprites);
+        spawn_pipes(
+            &mut world,
             sprites.create_sprite("pipe_bottom.png"),
             sprites.create_sprite("pipe_top.png"),
             pipe_tracker,
-            200.0,
+            FIRST_PIPE_X,
         );
-        pipes
+        world
+    }
+
+    /// A level starts with just the floor. Its obstacles come in as it's played.
+    pub fn create_level_world(sprites: &atlas::Atlas) -> World {
+        let mut world = World::new();
+        spawn_tiles(&mut world, sprites);
+        world
+    }
+
+    /// The assists for the next run. Local matches are played without them.
+    fn assists(&self) -> Assists {
+        match self.local_match {
+            Some(_) => Assists::default(),
+            None => self.settings.assists.clone(),
+        }
     }
 
     pub fn restart(&mut self) {
+        let assists = self.assists();
+        let mut pt =
+            PipeTracker::new(self.pipe_tracker.seed, &self.settings.course, assists.gap_height());
+        let sprites = ObstacleSprites::new(&self.atlas);
+        let level = match (&self.test_level, &self.campaign) {
+            (Some(level), _) => Some(level.clone()),
+            (None, Some(campaign)) => Some(campaign.level().clone()),
+            (None, None) => None,
+        };
+        self.level_run = level.map(|level| LevelRun::new(level, sprites.clone()));
+        self.world = match self.level_run {
+            Some(_) => GameState::create_level_world(&self.atlas),
+            None => GameState::create_world(&self.atlas, &mut pt),
+        };
+        self.themes.restart();
+        self.dress_theme();
         self.sound_player.begin();
-        let mut pt = PipeTracker::new();
-        self.pipes = GameState::create_start_entities(&self.atlas, &mut pt);
+        if let Some(run) = &self.level_run {
+            self.background = run.background();
+        }
+        self.stop_music();
         self.player = create_player(&self.atlas);
+        self.player.assists = assists.clone();
+        self.wardrobe.dress(&mut self.player);
+        self.obstacles = ObstacleGenerator::new(sprites, pt.seed);
         self.pipe_tracker = pt;
         self.play_state = PlayState::StartScreen;
-        self.swap_scores();
+        self.effects.clear();
+        // Practice runs don't count towards the best score, just like in `end_run`.
+        if self.practice.is_none() {
+            self.swap_scores();
+        }
         self.score = 0;
+        self.run_medal = None;
+        self.closest_clearance = std::f32::MAX;
+        self.on_ceiling = false;
+        self.run_ticks = 0;
+        self.recording = Recording {
+            seed: self.pipe_tracker.seed,
+            course: self.settings.course.clone(),
+            assists: assists.clone(),
+            ..Recording::default()
+        };
+        self.power_ups = PowerUps::new();
+        self.run_pearls = 0;
+        self.distance = 0.0;
+        self.ghost = match (&self.level_run, &self.practice, &self.test_level) {
+            _ if assists.any() => None,
+            (_, Some(_), _) | (_, _, Some(_)) => None,
+            (Some(run), None, None) => Some(Ghost::level(&run.level.name)),
+            (None, None, None) => Some(Ghost::endless(self.pipe_tracker.seed, &self.settings.course)),
+        };
+        if let Some(practice) = &mut self.practice {
+            practice.rewind.clear();
+        }
+        if let Some(players) = self.local_match.as_ref().map(|round| round.players()) {
+            self.local_match = Some(LocalMatch::new(&self.atlas, players));
+            self.ghost = None;
+        }
     }
 
     pub fn swap_scores(&mut self) {
@@ -0,0 +1,105 @@
+use crate::crab::PlayerEntity;
+use crate::settings::CourseSettings;
+use crate::storage;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+/// How far apart the crab and the ghost can be and still count as level, in world units.
+const LEVEL_DISTANCE: f32 = 1.0;
+
+/// Where the crab was on one update: how far the course had moved, its height and how fast it was falling.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct GhostPoint(pub f32, pub f32, pub f32);
+
+/// The best run on a course, one point per update.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GhostRun {
+    pub score: i64,
+    pub points: Vec<GhostPoint>,
+}
+
+/// Races the crab against the best run on the same course, and records this run in case it's better.
+pub struct Ghost {
+    file: String,
+    best: Option<GhostRun>,
+    current: Vec<GhostPoint>,
+}
+
+impl Ghost {
+    /// The ghost for the endless course made from this seed and generator.
+    pub fn endless(seed: u32, course: &CourseSettings) -> Self {
+        Ghost::load(format!("ghost_endless_{}_{}.json", course.name(), seed))
+    }
+
+    /// The ghost for a level.
+    pub fn level(name: &str) -> Self {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        Ghost::load(format!("ghost_level_{}.json", name))
+    }
+
+    fn load(file: String) -> Self {
+        let best: GhostRun = storage::load(&file);
+        Self {
+            best: if best.points.is_empty() { None } else { Some(best) },
+            file,
+            current: vec![],
+        }
+    }
+
+    /// Adds where the crab is this update to the run.
+    pub fn record(&mut self, distance: f32, y: f32, velocity: f32) {
+        self.current.push(GhostPoint(distance, y, velocity));
+    }
+
+    /// Where the best run was at this point in the current one, if it lasted this long.
+    fn now(&self) -> Option<GhostPoint> {
+        let best = self.best.as_ref()?;
+        best.points.get(self.current.len().saturating_sub(1)).cloned()
+    }
+
+    /// Keeps the run if it beat the best one.
+    pub fn finish(&mut self, score: i64) {
+        let beaten = self.best.as_ref().map_or(true, |best| score > best.score);
+        if !beaten || self.current.is_empty() {
+            return;
+        }
+        let run = GhostRun {
+            score,
+            points: std::mem::replace(&mut self.current, vec![]),
+        };
+        storage::save(&self.file, &run);
+        self.best = Some(run);
+    }
+
+    /// Draws the best run's crab see-through, where it was at this point in its run.
+    /// `distance` is how far the course has moved and `crab_x` is where the crab is.
+    pub fn draw(&self, batch: &mut SpriteBatch, player: &PlayerEntity, distance: f32, crab_x: f32) {
+        if let Some(GhostPoint(ghost_distance, y, velocity)) = self.now() {
+            let position = Point2::new(crab_x + ghost_distance - distance, y);
+            player.draw_ghost(batch, position, velocity);
+        }
+    }
+
+    /// Says whether the crab is ahead of or behind the best run.
+    pub fn draw_hud(&self, ctx: &mut Context, distance: f32) -> GameResult {
+        let best = match &self.best {
+            Some(best) => best,
+            None => return Ok(()),
+        };
+        let standing = match self.now() {
+            None => "past it!",
+            Some(GhostPoint(ghost_distance, _, _)) if (distance - ghost_distance).abs() < LEVEL_DISTANCE => "level",
+            Some(GhostPoint(ghost_distance, _, _)) if distance > ghost_distance => "ahead",
+            Some(_) => "behind",
+        };
+        let text = Text::new(format!("Best run: {}   You're {}", best.score, standing));
+        graphics::draw(ctx, &text, (Point2::new(10.0, 34.0), graphics::WHITE))
+    }
+}
@@ -0,0 +1,92 @@
+//! Which keys each player flaps with.
+//! The names in the settings file are turned into keys once, when the game starts.
+
+use ggez::event::KeyCode;
+use ggez::input::keyboard;
+use ggez::Context;
+use serde::{Deserialize, Serialize};
+
+/// The most crabs that can play at once.
+pub const MAX_PLAYERS: usize = 4;
+
+/// The flap keys of each player, by name, as they are in the settings file.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Controls {
+    pub players: Vec<Vec<String>>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        let keys = ["Space", "Q", "Return", "Up"];
+        Self {
+            players: keys.iter().map(|key| vec![key.to_string()]).collect(),
+        }
+    }
+}
+
+/// The flap keys of each player.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    players: Vec<Vec<KeyCode>>,
+}
+
+impl InputMap {
+    /// Looks up the keys named in the controls. Names that aren't keys are skipped with a warning,
+    /// and anyone left without a key gets their default one.
+    pub fn new(controls: &Controls) -> Self {
+        let defaults = Controls::default();
+        let players = (0..MAX_PLAYERS)
+            .map(|player| {
+                let keys: Vec<KeyCode> = controls
+                    .players
+                    .get(player)
+                    .map_or(&[][..], |names| &names[..])
+                    .iter()
+                    .filter_map(|name| {
+                        let key = key_from_name(name);
+                        if key.is_none() {
+                            println!("Player {} has a flap key that isn't a key: {}", player + 1, name);
+                        }
+                        key
+                    })
+                    .collect();
+                if keys.is_empty() {
+                    defaults.players[player].iter().filter_map(|name| key_from_name(name)).collect()
+                } else {
+                    keys
+                }
+            })
+            .collect();
+        Self { players }
+    }
+
+    /// Whether any of the player's flap keys is down. Players start at 0.
+    pub fn flap_held(&self, ctx: &Context, player: usize) -> bool {
+        self.players
+            .get(player)
+            .map_or(false, |keys| keys.iter().any(|key| keyboard::is_key_pressed(ctx, *key)))
+    }
+
+    /// The name of the player's first flap key, for showing on screen.
+    pub fn key_name(&self, player: usize) -> String {
+        self.players
+            .get(player)
+            .and_then(|keys| keys.first())
+            .map_or("?".to_string(), |key| format!("{:?}", key))
+    }
+}
+
+/// The key with this name, like "Space", "Q", "Key1" or "Up". The names are the same as ggez's `KeyCode`s.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    let keys = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        Space, Return, Tab, Back, Up, Down, Left, Right,
+        LShift, RShift, LControl, RControl, LAlt, RAlt,
+        Comma, Period, Slash, Semicolon, Apostrophe, LBracket, RBracket, Minus, Equals,
+    ];
+    keys.iter().find(|key| format!("{:?}", key).eq_ignore_ascii_case(name)).cloned()
+}
@@ -0,0 +1,792 @@
+use crate::collectibles::PowerUp;
+use crate::obstacles::{self, ObstacleSprites};
+use crate::pipe::{self, pipe_spacing, pipe_velocity, VERTICAL_GAP};
+use crate::components::PipePair;
+use crate::tile::FLOOR_Y;
+use crate::window::SCALE;
+use crate::world::World;
+use ggez::graphics;
+use ggez::nalgebra::Vector2;
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// The folder in the resources folder that levels are kept in.
+pub const LEVELS_DIR: &str = "levels";
+const CAMPAIGN_FILE: &str = "campaign.json";
+/// The narrowest gap the crab can be expected to get through.
+const MIN_GAP: f32 = 24.0;
+const MAX_SPEED: f32 = 3.0;
+/// Where the course starts, just off the right of the screen.
+const START_X: f32 = 200.0;
+/// Obstacles are spawned once they are this close to coming on screen.
+const SPAWN_MARGIN: f32 = 32.0;
+const FINISH_SQUARE: f32 = 4.0;
+
+fn default_background() -> [u8; 3] {
+    [112, 216, 255]
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+fn default_gap() -> f32 {
+    VERTICAL_GAP
+}
+
+/// A hand made course, played from start to finish.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    pub name: String,
+    pub sections: Vec<Section>,
+}
+
+/// A stretch of a level with its own look, speed and music.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Section {
+    #[serde(default = "default_background")]
+    pub background: [u8; 3],
+    /// How fast the course moves compared to normal.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    /// A sound file in the resources folder, like "/music/reef.ogg".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
+    /// How far apart obstacles are unless they say otherwise. Defaults to the endless pipe spacing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spacing: Option<f32>,
+    pub obstacles: Vec<Obstacle>,
+}
+
+/// One thing placed along the course.
+/// `after` is how far it is from the thing before it, and y values are where its middle goes.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Obstacle {
+    Pipe {
+        gap_centre: f32,
+        #[serde(default = "default_gap")]
+        gap_height: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    BobbingPipe {
+        gap_centre: f32,
+        #[serde(default = "default_gap")]
+        gap_height: f32,
+        amplitude: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    JawPipe {
+        gap_centre: f32,
+        #[serde(default = "default_gap")]
+        gap_height: f32,
+        closed_gap: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    Jellyfish {
+        y: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    Seagull {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    Pearl {
+        y: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+    PowerUp {
+        power_up: PowerUp,
+        y: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<f32>,
+    },
+}
+
+impl Obstacle {
+    /// The name it goes by in level files.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Obstacle::Pipe { .. } => "pipe",
+            Obstacle::BobbingPipe { .. } => "bobbing_pipe",
+            Obstacle::JawPipe { .. } => "jaw_pipe",
+            Obstacle::Jellyfish { .. } => "jellyfish",
+            Obstacle::Seagull { .. } => "seagull",
+            Obstacle::Pearl { .. } => "pearl",
+            Obstacle::PowerUp { .. } => "power_up",
+        }
+    }
+
+    fn after(&self) -> Option<f32> {
+        match self {
+            Obstacle::Pipe { after, .. }
+            | Obstacle::BobbingPipe { after, .. }
+            | Obstacle::JawPipe { after, .. }
+            | Obstacle::Jellyfish { after, .. }
+            | Obstacle::Seagull { after }
+            | Obstacle::Pearl { after, .. }
+            | Obstacle::PowerUp { after, .. } => *after,
+        }
+    }
+
+    pub fn set_after(&mut self, distance: f32) {
+        match self {
+            Obstacle::Pipe { after, .. }
+            | Obstacle::BobbingPipe { after, .. }
+            | Obstacle::JawPipe { after, .. }
+            | Obstacle::Jellyfish { after, .. }
+            | Obstacle::Seagull { after }
+            | Obstacle::Pearl { after, .. }
+            | Obstacle::PowerUp { after, .. } => *after = Some(distance),
+        }
+    }
+
+    /// Where its middle is, or where its gap is for pipes.
+    pub fn y(&self) -> f32 {
+        match *self {
+            Obstacle::Pipe { gap_centre, .. }
+            | Obstacle::BobbingPipe { gap_centre, .. }
+            | Obstacle::JawPipe { gap_centre, .. } => gap_centre,
+            Obstacle::Jellyfish { y, .. } | Obstacle::Pearl { y, .. } | Obstacle::PowerUp { y, .. } => y,
+            Obstacle::Seagull { .. } => 0.0,
+        }
+    }
+
+    /// Moves it up or down, as far as it can go and still be played.
+    pub fn set_y(&mut self, new_y: f32) {
+        match self {
+            Obstacle::Pipe { gap_centre, gap_height, .. } | Obstacle::JawPipe { gap_centre, gap_height, .. } => {
+                let reach = *gap_height / 2.0;
+                *gap_centre = new_y.max(reach).min(FLOOR_Y - reach);
+            }
+            Obstacle::BobbingPipe { gap_centre, gap_height, amplitude, .. } => {
+                let reach = *gap_height / 2.0 + *amplitude;
+                *gap_centre = new_y.max(reach).min(FLOOR_Y - reach);
+            }
+            Obstacle::Jellyfish { y, .. } | Obstacle::Pearl { y, .. } | Obstacle::PowerUp { y, .. } => {
+                *y = new_y.max(0.0).min(FLOOR_Y);
+            }
+            Obstacle::Seagull { .. } => {}
+        }
+    }
+
+    pub fn is_pipe(&self) -> bool {
+        match self {
+            Obstacle::Pipe { .. } | Obstacle::BobbingPipe { .. } | Obstacle::JawPipe { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Checks the obstacle can be played. Errors name the field that's wrong.
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if let Some(after) = self.after() {
+            if after < 0.0 {
+                return Err(("after", format!("after must not be negative, got {}", after)));
+            }
+        }
+        match *self {
+            Obstacle::Pipe { gap_centre, gap_height, .. } => validate_gap(gap_centre, gap_height, 0.0),
+            Obstacle::BobbingPipe { gap_centre, gap_height, amplitude, .. } => {
+                if amplitude < 0.0 {
+                    return Err(("amplitude", format!("amplitude must not be negative, got {}", amplitude)));
+                }
+                validate_gap(gap_centre, gap_height, amplitude)
+            }
+            Obstacle::JawPipe { gap_centre, gap_height, closed_gap, .. } => {
+                validate_gap(gap_centre, gap_height, 0.0)?;
+                if closed_gap < MIN_GAP || closed_gap >= gap_height {
+                    return Err((
+                        "closed_gap",
+                        format!("closed_gap must be between {} and gap_height ({}), got {}", MIN_GAP, gap_height, closed_gap),
+                    ));
+                }
+                Ok(())
+            }
+            Obstacle::Jellyfish { y, .. } | Obstacle::Pearl { y, .. } | Obstacle::PowerUp { y, .. } => {
+                if y < 0.0 || y > FLOOR_Y {
+                    return Err(("y", format!("y must be between 0 and {}, got {}", FLOOR_Y, y)));
+                }
+                Ok(())
+            }
+            Obstacle::Seagull { .. } => Ok(()),
+        }
+    }
+}
+
+/// The gap has to be wide enough for the crab and stay between the top of the screen and the floor,
+/// even when it moves by `amplitude`.
+fn validate_gap(gap_centre: f32, gap_height: f32, amplitude: f32) -> Result<(), (&'static str, String)> {
+    if gap_height < MIN_GAP {
+        return Err(("gap_height", format!("gap_height must be at least {}, got {}", MIN_GAP, gap_height)));
+    }
+    let top = gap_centre - gap_height / 2.0 - amplitude;
+    let bottom = gap_centre + gap_height / 2.0 + amplitude;
+    if top < 0.0 || bottom > FLOOR_Y {
+        return Err((
+            "gap_centre",
+            format!("the gap reaches from {} to {}, but has to stay between 0 and {}", top, bottom, FLOOR_Y),
+        ));
+    }
+    Ok(())
+}
+
+/// Something wrong with a level file, and where.
+#[derive(Debug)]
+pub struct LevelError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Loads a level and checks it can be played.
+pub fn load_level(path: &Path) -> Result<Level, LevelError> {
+    let file = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|e| LevelError {
+        file: file.clone(),
+        line: None,
+        message: e.to_string(),
+    })?;
+    parse_level(&text, file, resources_for(path))
+}
+
+/// Reads a level from the text of `file` and checks it can be played.
+fn parse_level(text: &str, file: String, resources: Option<&Path>) -> Result<Level, LevelError> {
+    let level: Level = serde_json::from_str(text).map_err(|e| {
+        // serde puts the position on the end of the message, but it goes at the front here.
+        let message = e.to_string();
+        let message = message.split(" at line ").next().unwrap_or("").to_string();
+        LevelError {
+            file: file.clone(),
+            line: Some(e.line()),
+            message,
+        }
+    })?;
+
+    match validate(&level, resources) {
+        Ok(()) => Ok(level),
+        Err((path, message)) => Err(LevelError {
+            file,
+            line: line_of(&value_lines(text), &path),
+            message,
+        }),
+    }
+}
+
+/// Checks a level can be played and writes it out.
+pub fn save_level(path: &Path, level: &Level) -> Result<(), LevelError> {
+    let file = path.display().to_string();
+    let error = |message: String| LevelError {
+        file: file.clone(),
+        line: None,
+        message,
+    };
+    if let Err((path, message)) = validate(level, resources_for(path)) {
+        return Err(error(format!("{}: {}", path, message)));
+    }
+    let json = serde_json::to_string_pretty(level).map_err(|e| error(e.to_string()))?;
+    std::fs::write(path, json).map_err(|e| error(e.to_string()))
+}
+
+/// Levels are kept in a folder in the resources folder, which is where their music is looked for.
+fn resources_for(level_file: &Path) -> Option<&Path> {
+    level_file.parent().and_then(Path::parent)
+}
+
+/// Checks everything serde can't. Errors come with the path of the value that's wrong.
+fn validate(level: &Level, resources: Option<&Path>) -> Result<(), (String, String)> {
+    if level.sections.is_empty() {
+        return Err(("sections".to_string(), "a level needs at least one section".to_string()));
+    }
+    for (i, section) in level.sections.iter().enumerate() {
+        let path = format!("sections[{}]", i);
+        if section.speed <= 0.0 || section.speed > MAX_SPEED {
+            return Err((
+                format!("{}.speed", path),
+                format!("speed must be above 0 and at most {}, got {}", MAX_SPEED, section.speed),
+            ));
+        }
+        if let Some(spacing) = section.spacing {
+            if spacing <= 0.0 {
+                return Err((format!("{}.spacing", path), format!("spacing must be above 0, got {}", spacing)));
+            }
+        }
+        if let (Some(music), Some(resources)) = (&section.music, resources) {
+            if !resources.join(music.trim_start_matches('/')).is_file() {
+                return Err((format!("{}.music", path), format!("couldn't find music {}", music)));
+            }
+        }
+        if section.obstacles.is_empty() {
+            return Err((format!("{}.obstacles", path), "a section needs at least one obstacle".to_string()));
+        }
+        for (j, obstacle) in section.obstacles.iter().enumerate() {
+            if let Err((field, message)) = obstacle.validate() {
+                return Err((format!("{}.obstacles[{}].{}.{}", path, j, obstacle.key(), field), message));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the line each value in a JSON document starts on, by path, like `sections[0].obstacles[2]`.
+/// Only used to point at mistakes once serde has read the document, so it trusts the JSON is valid.
+fn value_lines(text: &str) -> HashMap<String, usize> {
+    enum Frame {
+        Object { path: String, key: Option<String> },
+        Array { path: String, index: usize },
+    }
+
+    let mut lines = HashMap::new();
+    let mut stack: Vec<Frame> = vec![];
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let expecting_key = match stack.last() {
+            Some(Frame::Object { key, .. }) => key.is_none(),
+            _ => false,
+        };
+        match c {
+            '\n' => line += 1,
+            ' ' | '\t' | '\r' | ':' => {}
+            ',' => match stack.last_mut() {
+                Some(Frame::Object { key, .. }) => *key = None,
+                Some(Frame::Array { index, .. }) => *index += 1,
+                None => {}
+            },
+            '}' | ']' => {
+                stack.pop();
+            }
+            '"' if expecting_key => {
+                let mut name = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        // Unicode escapes are left as they are, no level needs them in a key.
+                        '\\' => match chars.next() {
+                            Some('n') => name.push('\n'),
+                            Some('t') => name.push('\t'),
+                            Some('r') => name.push('\r'),
+                            Some(c) => name.push(c),
+                            None => {}
+                        },
+                        c => name.push(c),
+                    }
+                }
+                if let Some(Frame::Object { key, .. }) = stack.last_mut() {
+                    *key = Some(name);
+                }
+            }
+            c => {
+                let path = match stack.last() {
+                    Some(Frame::Object { path, key: Some(key) }) if path.is_empty() => key.clone(),
+                    Some(Frame::Object { path, key: Some(key) }) => format!("{}.{}", path, key),
+                    Some(Frame::Array { path, index }) => format!("{}[{}]", path, index),
+                    _ => String::new(),
+                };
+                lines.entry(path.clone()).or_insert(line);
+                match c {
+                    '{' => stack.push(Frame::Object { path, key: None }),
+                    '[' => stack.push(Frame::Array { path, index: 0 }),
+                    '"' => {
+                        while let Some(c) = chars.next() {
+                            match c {
+                                '"' => break,
+                                '\\' => {
+                                    chars.next();
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {
+                        while let Some(&c) = chars.peek() {
+                            if c == ',' || c == '}' || c == ']' || c.is_whitespace() {
+                                break;
+                            }
+                            chars.next();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// The line of the value at `path`, or of the nearest thing containing it
+/// when the value isn't in the file because it was left to its default.
+fn line_of(lines: &HashMap<String, usize>, path: &str) -> Option<usize> {
+    let mut path = path;
+    loop {
+        if let Some(line) = lines.get(path) {
+            return Some(*line);
+        }
+        match path.rfind(|c| c == '.' || c == '[') {
+            Some(end) => path = &path[..end],
+            None => return None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignFile {
+    levels: Vec<String>,
+}
+
+/// The levels in `resources/levels/campaign.json`, played one after another.
+pub struct Campaign {
+    pub levels: Vec<Level>,
+    /// The file each level came from, relative to the levels folder.
+    pub files: Vec<String>,
+    pub current: usize,
+}
+
+impl Campaign {
+    /// Loads every level up front so mistakes turn up before anyone starts playing.
+    pub fn load(dir: &Path) -> Result<Self, LevelError> {
+        let path = dir.join(CAMPAIGN_FILE);
+        let file = path.display().to_string();
+        let text = std::fs::read_to_string(&path).map_err(|e| LevelError {
+            file: file.clone(),
+            line: None,
+            message: e.to_string(),
+        })?;
+        let campaign: CampaignFile = serde_json::from_str(&text).map_err(|e| LevelError {
+            file: file.clone(),
+            line: Some(e.line()),
+            message: e.to_string().split(" at line ").next().unwrap_or("").to_string(),
+        })?;
+        if campaign.levels.is_empty() {
+            return Err(LevelError {
+                file,
+                line: line_of(&value_lines(&text), "levels"),
+                message: "a campaign needs at least one level".to_string(),
+            });
+        }
+
+        let levels = campaign
+            .levels
+            .iter()
+            .map(|name| load_level(&dir.join(name)))
+            .collect::<Result<Vec<Level>, LevelError>>()?;
+        Ok(Self {
+            levels,
+            files: campaign.levels,
+            current: 0,
+        })
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.levels[self.current]
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 >= self.levels.len()
+    }
+
+    /// Moves on to the next level. Returns false if there isn't one.
+    pub fn advance(&mut self) -> bool {
+        if self.is_last() {
+            return false;
+        }
+        self.current += 1;
+        true
+    }
+}
+
+/// An obstacle, the section it's in and how far along the course it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub section: usize,
+    pub distance: f32,
+    pub obstacle: Obstacle,
+}
+
+/// How far apart obstacles are when neither they nor their section say.
+pub fn default_spacing(sprites: &ObstacleSprites) -> f32 {
+    pipe_spacing(sprites.pipe_tip.width)
+}
+
+/// Works out where everything in a level goes.
+/// Returns how far along the course each section starts, and where each obstacle is.
+pub fn layout(level: &Level, default_spacing: f32) -> (Vec<f32>, Vec<Placement>) {
+    let mut placements = vec![];
+    let mut section_starts = vec![];
+    let mut distance = 0.0;
+    for (i, section) in level.sections.iter().enumerate() {
+        section_starts.push(distance);
+        let spacing = section.spacing.unwrap_or(default_spacing);
+        for obstacle in &section.obstacles {
+            distance += obstacle.after().unwrap_or(spacing);
+            placements.push(Placement {
+                section: i,
+                distance,
+                obstacle: obstacle.clone(),
+            });
+        }
+    }
+    (section_starts, placements)
+}
+
+/// A level being played. Obstacles are spawned as they come into view,
+/// and nothing comes around again.
+pub struct LevelRun {
+    pub level: Level,
+    placements: Vec<Placement>,
+    /// The next placement to spawn.
+    next: usize,
+    /// How far along the course each section starts.
+    section_starts: Vec<f32>,
+    pub section: usize,
+    finish: f32,
+    /// How far the course has moved.
+    travelled: f32,
+    sprites: ObstacleSprites,
+}
+
+impl LevelRun {
+    pub fn new(level: Level, sprites: ObstacleSprites) -> Self {
+        let default_spacing = default_spacing(&sprites);
+        let (section_starts, placements) = layout(&level, default_spacing);
+        let last = placements.last().map_or(0.0, |placement| placement.distance);
+
+        Self {
+            level,
+            placements,
+            next: 0,
+            section_starts,
+            section: 0,
+            finish: last + default_spacing,
+            travelled: 0.0,
+            sprites,
+        }
+    }
+
+    pub fn current_section(&self) -> &Section {
+        &self.level.sections[self.section]
+    }
+
+    pub fn background(&self) -> graphics::Color {
+        let [r, g, b] = self.current_section().background;
+        graphics::Color::from_rgb(r, g, b)
+    }
+
+    /// Where something this far along the course is on screen.
+    fn screen_x(&self, distance: f32) -> f32 {
+        START_X + distance - self.travelled
+    }
+
+    /// Moves the course on by one update and spawns whatever is about to come on screen.
+    /// `crab_x` is where the crab is, which is where new sections start.
+    /// Returns true if the crab has just got to a new section.
+    pub fn update(&mut self, world: &mut World, speed: f32, crab_x: f32) -> bool {
+        self.travelled += -pipe_velocity() * speed;
+
+        while self.next < self.placements.len()
+            && self.screen_x(self.placements[self.next].distance) < START_X + SPAWN_MARGIN
+        {
+            let x = self.screen_x(self.placements[self.next].distance);
+            spawn_obstacle(world, &self.sprites, &self.placements[self.next].obstacle, x);
+            self.next += 1;
+        }
+
+        let next_section = self.section + 1;
+        if next_section < self.section_starts.len() && self.screen_x(self.section_starts[next_section]) <= crab_x {
+            self.section = next_section;
+            return true;
+        }
+        false
+    }
+
+    /// Whether the crab has crossed the finish line.
+    pub fn finished(&self, crab_x: f32) -> bool {
+        self.screen_x(self.finish) <= crab_x
+    }
+
+    /// Draws a chequered line across the course at the finish.
+    pub fn draw_finish_line(&self, ctx: &mut Context) -> GameResult {
+        let x = self.screen_x(self.finish);
+        if x > START_X {
+            return Ok(());
+        }
+        let mut builder = graphics::MeshBuilder::new();
+        let rows = (FLOOR_Y / FINISH_SQUARE) as usize;
+        for row in 0..rows {
+            for column in 0..2 {
+                let colour = if (row + column) % 2 == 0 { graphics::BLACK } else { graphics::WHITE };
+                let square = graphics::Rect::new(
+                    x + FINISH_SQUARE * column as f32,
+                    FINISH_SQUARE * row as f32,
+                    FINISH_SQUARE,
+                    FINISH_SQUARE,
+                );
+                builder.rectangle(graphics::DrawMode::fill(), square, colour);
+            }
+        }
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new().scale(Vector2::new(SCALE, SCALE)))
+    }
+}
+
+/// Spawns an obstacle centred on x. Seagulls start at x and fly in from there.
+pub fn spawn_obstacle(world: &mut World, sprites: &ObstacleSprites, obstacle: &Obstacle, x: f32) {
+    let pipe_pair = |gap_height| PipePair::new(gap_height, sprites.pipe_body.clone(), sprites.pipe_tip.clone());
+    let pipe_x = x - sprites.pipe_tip.width / 2.0;
+    match *obstacle {
+        Obstacle::Pipe { gap_centre, gap_height, .. } => {
+            pipe::spawn_level_pipe_pair(world, pipe_pair(gap_height), pipe_x, gap_centre);
+        }
+        Obstacle::BobbingPipe { gap_centre, gap_height, amplitude, .. } => {
+            let pipe = pipe::spawn_level_pipe_pair(world, pipe_pair(gap_height), pipe_x, gap_centre);
+            world.motion_scripts[pipe] = Some(obstacles::bobbing_pipe(amplitude));
+        }
+        Obstacle::JawPipe { gap_centre, gap_height, closed_gap, .. } => {
+            let pipe = pipe::spawn_level_pipe_pair(world, pipe_pair(gap_height), pipe_x, gap_centre);
+            world.motion_scripts[pipe] = Some(obstacles::jaw_pipe(gap_height, closed_gap));
+        }
+        Obstacle::Jellyfish { y, .. } => {
+            obstacles::spawn_jellyfish(world, sprites, x, y);
+        }
+        Obstacle::Seagull { .. } => {
+            obstacles::spawn_seagull(world, sprites, x);
+        }
+        Obstacle::Pearl { y, .. } => {
+            obstacles::spawn_pearl(world, sprites, x, y);
+        }
+        Obstacle::PowerUp { power_up, y, .. } => {
+            obstacles::spawn_power_up(world, sprites, power_up, x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = r#"{
+  "name": "Test { level ]",
+  "sections": [
+    {
+      "speed": 1.0,
+      "obstacles": [
+        { "pipe": { "gap_centre": 80 } },
+        { "jaw_pipe": { "gap_centre": 80, "closed_gap": 30 } }
+      ]
+    },
+    {
+      "speed": 1.5,
+      "obstacles": [
+        { "pearl": { "y": 70 } }
+      ]
+    }
+  ]
+}"#;
+
+    /// Loads `LEVEL` with `from` swapped for `to` and returns the line of the error.
+    fn error_line(from: &str, to: &str) -> Option<usize> {
+        let text = LEVEL.replacen(from, to, 1);
+        parse_level(&text, "test.json".to_string(), None).unwrap_err().line
+    }
+
+    #[test]
+    fn value_lines_of_nested_arrays() {
+        let text = "{\n  \"a\": [\n    [1, 2],\n    [\n      3,\n      {\"b\": 4}\n    ]\n  ],\n  \"c\": 5\n}";
+        let lines = value_lines(text);
+        let expected = [
+            ("", 1),
+            ("a", 2),
+            ("a[0]", 3),
+            ("a[0][0]", 3),
+            ("a[0][1]", 3),
+            ("a[1]", 4),
+            ("a[1][0]", 5),
+            ("a[1][1]", 6),
+            ("a[1][1].b", 6),
+            ("c", 9),
+        ];
+        for (path, line) in expected.iter() {
+            assert_eq!(lines.get(*path), Some(line), "line of {}", path);
+        }
+        assert_eq!(lines.len(), expected.len());
+    }
+
+    #[test]
+    fn value_lines_skip_strings() {
+        let text = "{\n  \"name\": \"a {tricky] \\\"name\\\", with: commas\",\n  \"next\": [\"}\", \"\\\\\"]\n}";
+        assert!(serde_json::from_str::<serde_json::Value>(text).is_ok());
+        let lines = value_lines(text);
+        assert_eq!(lines.get("name"), Some(&2));
+        assert_eq!(lines.get("next"), Some(&3));
+        assert_eq!(lines.get("next[1]"), Some(&3));
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn value_lines_unescape_keys() {
+        let text = r#"{"say \"hi\"": 1,
+ "tab\tbed": {"back\\slash": 2}}"#;
+        let value: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert!(value.get("say \"hi\"").is_some());
+        assert!(value["tab\tbed"].get("back\\slash").is_some());
+
+        let lines = value_lines(text);
+        assert_eq!(lines.get("say \"hi\""), Some(&1));
+        assert_eq!(lines.get("tab\tbed"), Some(&2));
+        assert_eq!(lines.get("tab\tbed.back\\slash"), Some(&2));
+    }
+
+    #[test]
+    fn line_of_falls_back_to_the_containing_value() {
+        let lines = value_lines(LEVEL);
+        assert_eq!(line_of(&lines, "sections[1].obstacles[0].pearl.y"), Some(14));
+        // Left to its default, so it points at the obstacle it belongs to.
+        assert_eq!(line_of(&lines, "sections[0].obstacles[1].jaw_pipe.gap_height"), Some(8));
+        assert_eq!(line_of(&lines, "sections[1].music"), Some(11));
+        assert_eq!(line_of(&lines, "missing"), None);
+    }
+
+    #[test]
+    fn valid_level_loads() {
+        let level = parse_level(LEVEL, "test.json".to_string(), None).unwrap();
+        assert_eq!(level.name, "Test { level ]");
+        assert_eq!(level.sections.len(), 2);
+    }
+
+    #[test]
+    fn validation_errors_point_at_the_line() {
+        assert_eq!(error_line("\"speed\": 1.5", "\"speed\": 5"), Some(12));
+        assert_eq!(error_line("\"gap_centre\": 80 }", "\"gap_centre\": 5 }"), Some(7));
+        assert_eq!(error_line("\"closed_gap\": 30", "\"closed_gap\": 90"), Some(8));
+        assert_eq!(error_line("\"y\": 70", "\"y\": -1"), Some(14));
+        assert_eq!(error_line("{ \"pearl\": { \"y\": 70 } }", ""), Some(13));
+        assert_eq!(error_line("\"speed\": 1.0", "\"spacing\": 0, \"speed\": 1.0"), Some(5));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_line() {
+        let text = LEVEL.replacen("\"speed\": 1.5", "\"sped\": 1.5", 1);
+        let error = parse_level(&text, "test.json".to_string(), None).unwrap_err();
+        assert_eq!(error.line, Some(12));
+        assert!(!error.message.contains(" at line "));
+        assert!(error.to_string().starts_with("test.json:12: "));
+    }
+}
@@ -0,0 +1,382 @@
+use atlas::Sprite;
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{
+    event::{EventHandler, KeyCode, KeyMods, MouseButton},
+    graphics::{spritebatch::SpriteBatch, Text},
+    Context,
+    GameResult,
+    graphics,
+    event
+};
+pub mod entity;
+pub mod atlas;
+pub mod pipe;
+pub mod game_state;
+pub mod crab;
+pub mod audio;
+pub mod window;
+pub mod tile;
+pub mod effects;
+pub mod game_over;
+pub mod settings;
+pub mod achievements;
+pub mod events;
+pub mod menu;
+pub mod storage;
+pub mod stats;
+pub mod components;
+pub mod systems;
+pub mod world;
+pub mod obstacles;
+pub mod collectibles;
+pub mod cosmetics;
+pub mod daylight;
+pub mod level;
+pub mod editor;
+pub mod course;
+pub mod reachability;
+pub mod cli;
+pub mod recording;
+pub mod sim;
+pub mod assets;
+pub mod shapes;
+pub mod snapshot;
+pub mod step;
+pub mod practice;
+pub mod ghost;
+pub mod assists;
+pub mod input;
+pub mod local_match;
+pub mod net;
+pub mod online;
+pub mod palette;
+pub mod profile;
+pub mod profile_menu;
+pub mod theme;
+pub mod versus;
+use entity::PlayState;
+use events::GameEvent;
+use std::time::Duration;
+use crate::game_state::GameState;
+use crate::step::{Run, Tick};
+
+pub const NUMBER_OF_TILES: u8 = 14;
+/// How long everything freezes for when the crab gets hit.
+pub const HIT_STOP: Duration = std::time::Duration::from_millis(150);
+
+
+impl EventHandler for GameState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.effects.update();
+        self.achievements.update();
+        match self.play_state.clone() {
+            PlayState::Dead { time } => {
+                self.handle_after_losing(ctx, time);
+                return Ok(());
+            }
+            PlayState::GameOver | PlayState::LevelComplete | PlayState::Editing | PlayState::Paused => {
+                return Ok(())
+            }
+            _ => {}
+        }
+        if self.menu_page.is_some() {
+            return Ok(());
+        }
+        if self.local_match.is_some() {
+            local_match::update(self, ctx);
+            return Ok(());
+        }
+        let flap_held = match &self.replay {
+            Some(replay) => replay.flap_held(self.run_ticks),
+            None => self.input.flap_held(ctx, 0),
+        };
+        // The crab bounces around on the start screen, so where it was when the run started is part of the run.
+        if self.play_state == PlayState::StartScreen && flap_held {
+            match &self.replay {
+                Some(replay) => {
+                    self.player.position.y = replay.start_y;
+                    self.player.physics.velocity.y = replay.start_velocity;
+                }
+                None => {
+                    self.recording.start_y = self.player.position.y;
+                    self.recording.start_velocity = self.player.physics.velocity.y;
+                }
+            }
+        }
+        let section_speed = self.section_speed();
+        let run = Run {
+            world: &mut self.world,
+            player: &mut self.player,
+            pipe_tracker: &mut self.pipe_tracker,
+            obstacles: &mut self.obstacles,
+            power_ups: &mut self.power_ups,
+            assists: &self.settings.assists,
+            distance: &mut self.distance,
+            score: self.score,
+        };
+        let tick = run.step(flap_held, &self.play_state, section_speed);
+        if !self.play_state.is_playing() && tick.state == PlayState::Play {
+            self.start_run(ctx);
+        }
+        if self.play_state.is_playing() {
+            if flap_held {
+                self.recording.flaps.push(self.run_ticks);
+            }
+            self.run_ticks += 1;
+        }
+        if self.player.flapped && self.play_state.is_playing() {
+            self.emit(GameEvent::Flapped);
+        }
+        self.wardrobe.update(&self.player, tick.speed);
+        if self.play_state.is_playing() {
+            if let Some(ghost) = &mut self.ghost {
+                ghost.record(self.distance, self.player.position.y, self.player.physics.velocity.y);
+            }
+        }
+        self.update_level(ctx, tick.speed);
+        let score_before = self.score;
+        update_it(self, ctx, tick);
+        if self.play_state.is_playing() && self.practice.is_some() {
+            self.update_practice(self.score > score_before);
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if let (PlayState::Editing, Some(editor)) = (&self.play_state, &self.editor) {
+            editor.draw(ctx, &mut self.sprite_batch)?;
+            graphics::present(ctx)?;
+            return Ok(());
+        }
+
+        self.daylight.update(self.distance);
+        graphics::clear(ctx, self.daylight.sky(self.palette.sky(self.background)));
+
+        if self.level_run.is_none() {
+            self.themes.draw_layers(&mut self.sprite_batch, self.distance, &self.daylight);
+        }
+        match &mut self.local_match {
+            Some(round) => round.draw(&mut self.sprite_batch)?,
+            None => {
+                if let (Some(ghost), PlayState::Play) = (&self.ghost, &self.play_state) {
+                    ghost.draw(&mut self.sprite_batch, &self.player, self.distance, self.player.position.x);
+                }
+                self.wardrobe.draw_trail(&mut self.sprite_batch);
+                self.player.draw(&mut self.sprite_batch)?;
+                self.wardrobe.draw_hat(&self.player, &mut self.sprite_batch);
+            }
+        }
+        systems::render(ctx, &self.world, window::WORLD_HEIGHT, &mut self.sprite_batch, &self.daylight)?;
+
+        let shake = self.effects.shake_offset();
+        if let Some(outlines) = &mut self.outlines {
+            match &self.local_match {
+                Some(round) => round.racers.iter().for_each(|racer| outlines.add_crab(&racer.crab)),
+                None => outlines.add_crab(&self.player),
+            }
+            outlines.add_pipes(&self.world);
+            outlines.draw(ctx, shake)?;
+        }
+        let p = graphics::DrawParam::new()
+            .dest(shake)
+            .scale(Vector2::new(window::SCALE, window::SCALE));
+        {
+            graphics::draw(ctx, &mut self.sprite_batch, p)?;
+            self.sprite_batch.clear();
+        }
+        self.wardrobe.draw_batches(ctx, p)?;
+        if let Some(run) = &self.level_run {
+            run.draw_finish_line(ctx)?;
+        }
+        if let Some(practice) = &self.practice {
+            practice.draw_ghosts(ctx, self.distance, self.player.position.x)?;
+        }
+        self.effects.draw_flash(ctx)?;
+
+        if let Some(round) = &self.local_match {
+            match self.play_state {
+                PlayState::GameOver => round.draw_results(ctx)?,
+                PlayState::StartScreen => {
+                    round.draw_scores(ctx, &self.input)?;
+                    menu::draw_menu(ctx, self)?;
+                }
+                _ => round.draw_scores(ctx, &self.input)?,
+            }
+        } else {
+            match self.play_state {
+                PlayState::GameOver => {
+                    game_over::draw_game_over(ctx, self.score, self.best_score, self.run_medal)?
+                }
+                PlayState::LevelComplete => {
+                    if let Some(run) = &self.level_run {
+                        let last_level = self.campaign.as_ref().map_or(true, |campaign| campaign.is_last());
+                        game_over::draw_level_complete(ctx, &run.level.name, self.score, last_level)?
+                    }
+                }
+                PlayState::StartScreen => {
+                    draw_scores(self.score, self.best_score, ctx);
+                    menu::draw_menu(ctx, self)?;
+                }
+                PlayState::Paused => {
+                    draw_scores(self.score, self.best_score, ctx);
+                    let message = match self.practice {
+                        Some(_) => "Back at your checkpoint",
+                        None => "Carrying on from last time",
+                    };
+                    game_over::draw_paused(ctx, message)?;
+                }
+                _ => {
+                    draw_scores(self.score, self.best_score, ctx);
+                    self.power_ups.draw_hud(ctx, self.run_pearls, &self.wallet)?;
+                    if self.practice.is_some() {
+                        menu::draw_practice_hint(ctx)?;
+                    }
+                    if let Some(ghost) = &self.ghost {
+                        ghost.draw_hud(ctx, self.distance)?;
+                    }
+                }
+            }
+        }
+        self.achievements.draw_toast(ctx)?;
+
+        graphics::present(ctx)?;
+        std::thread::yield_now();
+
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        if self.play_state == PlayState::Editing {
+            self.editor_key(ctx, keycode, keymods);
+            return;
+        }
+        if self.play_state == PlayState::StartScreen && menu::handle_key(self, keycode) {
+            return;
+        }
+        match keycode {
+            KeyCode::Escape if self.editor.is_some() => self.return_to_editor(),
+            KeyCode::Escape => {
+                if self.can_suspend() {
+                    self.suspend();
+                }
+                event::quit(ctx)
+            }
+            KeyCode::R if self.practice.is_some() => self.rewind(),
+            KeyCode::K if self.practice.is_some() => self.set_checkpoint(),
+            KeyCode::Space if self.play_state == PlayState::Paused => self.resume(ctx),
+            KeyCode::Space if !repeat && self.editor.is_some() => match self.play_state {
+                PlayState::GameOver | PlayState::LevelComplete => self.return_to_editor(),
+                _ => {}
+            },
+            KeyCode::Space if !repeat && self.play_state == PlayState::GameOver => self.restart(),
+            KeyCode::Space if !repeat && self.play_state == PlayState::LevelComplete => self.next_level(),
+            _ => {}
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if let (PlayState::Editing, Some(editor)) = (&self.play_state, &mut self.editor) {
+            let (x, y) = window::to_screen(ctx, x, y);
+            editor.mouse_down(button, x, y);
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, _button: MouseButton, _x: f32, _y: f32) {
+        if let (PlayState::Editing, Some(editor)) = (&self.play_state, &mut self.editor) {
+            editor.mouse_up();
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if let (PlayState::Editing, Some(editor)) = (&self.play_state, &mut self.editor) {
+            let (x, y) = window::to_screen(ctx, x, y);
+            editor.mouse_moved(x, y);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+        if let (PlayState::Editing, Some(editor)) = (&self.play_state, &mut self.editor) {
+            editor.scroll_by(-y * window::SCALE * 4.0);
+        }
+    }
+}
+
+/// Reacts to what happened in the run this update.
+fn update_it(game: &mut GameState, ctx: &mut Context, tick: Tick) {
+    if !game.play_state.is_playing() {
+        return;
+    }
+
+    for _ in 0..tick.points {
+        game.score += 1;
+        game.sound_player.score();
+        game.emit(GameEvent::Scored(game.score));
+        game.emit(GameEvent::PipeCleared { clearance: game.closest_clearance });
+        game.closest_clearance = std::f32::MAX;
+    }
+    if game.level_run.is_none() && game.themes.for_score(game.score) {
+        game.show_theme(ctx);
+    }
+
+    for collectible in tick.collected {
+        game.pick_up(collectible);
+    }
+
+    for collider in systems::scoring_colliders(&game.world) {
+        if let Some(clearance) = game.player.clearance(&collider) {
+            game.closest_clearance = game.closest_clearance.min(clearance);
+        }
+    }
+
+    if let Some(cause) = tick.death {
+        game.kill_player(ctx, cause);
+    }
+
+    let at_ceiling = game.player.at_ceiling();
+    if at_ceiling && !game.on_ceiling && game.play_state.is_playing() {
+        game.emit(GameEvent::HitCeiling);
+    }
+    game.on_ceiling = at_ceiling;
+
+    if game.play_state.is_playing() {
+        let alive_for = game.survived(ctx);
+        game.emit(GameEvent::Survived(alive_for));
+    }
+}
+
+pub fn create_batch_sprite(ctx: &mut Context) -> SpriteBatch {
+    let image = graphics::Image::new(ctx, atlas::ATLAS_IMAGE).unwrap();
+    let mut batch = graphics::spritebatch::SpriteBatch::new(image);
+    batch.set_filter(graphics::FilterMode::Nearest);
+    batch
+}
+
+impl PlayState {
+    fn is_playing(&self) -> bool {
+        *self == PlayState::Play
+    }
+
+    fn set_dead (&mut self, time : std::time::Duration) {
+        *self = PlayState::Dead {
+            time
+        }
+    }
+
+    fn is_not_dead (&self) -> bool {
+        *self == PlayState::Play || *self == PlayState::StartScreen
+    }
+}
+
+fn draw_scores(score : i128, best_score: i128, ctx: &mut Context) {
+    let fps_display = Text::new(format!(
+        "Best Score: {}   Current Score: {}",
+        best_score, score
+    ));
+
+    graphics::draw(
+        ctx,
+        &fps_display,
+        (Point2::new(10.0, 10.0), graphics::WHITE),
+    );
+}
@@ -0,0 +1,213 @@
+use crate::atlas::Atlas;
+use crate::collectibles::{Collectible, PowerUp, PowerUps};
+use crate::crab::{create_player, PlayerEntity, GROUND};
+use crate::entity::PlayState;
+use crate::events::DeathCause;
+use crate::game_over;
+use crate::game_state::GameState;
+use crate::input::InputMap;
+use crate::systems;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+
+/// The colour each player's crab is drawn in. Player 1's crab looks like it always has.
+const TINTS: [(u8, u8, u8); 4] = [(255, 255, 255), (255, 130, 130), (130, 180, 255), (160, 255, 130)];
+/// How far apart the crabs start, so they aren't all on top of each other.
+const START_SPREAD: f32 = 10.0;
+/// How far apart each player's score is on screen.
+const SCORE_SPACING: f32 = 190.0;
+
+/// One player's crab and how their round is going.
+pub struct Racer {
+    pub crab: Box<PlayerEntity>,
+    pub score: i128,
+    pub pearls: u64,
+    pub power_ups: PowerUps,
+    /// The update the crab died on and what killed it.
+    pub died: Option<(u32, DeathCause)>,
+    /// Whether the dead crab has finished tumbling to the floor.
+    landed: bool,
+}
+
+/// A round of two to four crabs on the same course, each with their own flap key.
+/// The round is over once the last crab is lying on the floor.
+pub struct LocalMatch {
+    pub racers: Vec<Racer>,
+}
+
+impl LocalMatch {
+    pub fn new(atlas: &Atlas, players: usize) -> Self {
+        let racers = (0..players.min(TINTS.len()))
+            .map(|player| {
+                let mut crab = create_player(atlas);
+                crab.tint = graphics::Color::from(TINTS[player]);
+                crab.position.y += START_SPREAD * player as f32;
+                Racer {
+                    crab,
+                    score: 0,
+                    pearls: 0,
+                    power_ups: PowerUps::new(),
+                    died: None,
+                    landed: false,
+                }
+            })
+            .collect();
+        Self { racers }
+    }
+
+    pub fn players(&self) -> usize {
+        self.racers.len()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.racers.iter().all(|racer| racer.landed)
+    }
+
+    /// The world slows down while any crab still flying has slow motion going.
+    pub fn world_speed(&self) -> f32 {
+        self.racers
+            .iter()
+            .filter(|racer| racer.died.is_none())
+            .map(|racer| racer.power_ups.world_speed())
+            .fold(1.0, f32::min)
+    }
+
+    fn best_score(&self) -> i128 {
+        self.racers.iter().map(|racer| racer.score).max().unwrap_or(0)
+    }
+
+    /// Each player's place, best first, as (place, player). The highest score wins,
+    /// and between equal scores whoever lasted longer. Players that can't be split share a place.
+    pub fn standings(&self) -> Vec<(usize, usize)> {
+        let lasted = |racer: &Racer| racer.died.map_or(std::u32::MAX, |(tick, _)| tick);
+        let mut players: Vec<usize> = (0..self.racers.len()).collect();
+        players.sort_by_key(|&player| {
+            let racer = &self.racers[player];
+            (-racer.score, std::u32::MAX - lasted(racer))
+        });
+        let mut standings: Vec<(usize, usize)> = vec![];
+        for (i, &player) in players.iter().enumerate() {
+            let racer = &self.racers[player];
+            let tied = |last: &Racer| last.score == racer.score && lasted(last) == lasted(racer);
+            let place = match standings.last() {
+                Some(&(place, last)) if tied(&self.racers[last]) => place,
+                _ => i + 1,
+            };
+            standings.push((place, player));
+        }
+        standings
+    }
+
+    /// Dead crabs are drawn first so the ones still flying are on top.
+    pub fn draw(&mut self, batch: &mut SpriteBatch) -> GameResult {
+        for racer in self.racers.iter_mut().filter(|racer| racer.died.is_some()) {
+            racer.crab.draw(batch)?;
+        }
+        for racer in self.racers.iter_mut().filter(|racer| racer.died.is_none()) {
+            racer.crab.draw(batch)?;
+        }
+        Ok(())
+    }
+
+    /// Each player's flap key and score along the top, in the colour of their crab.
+    pub fn draw_scores(&self, ctx: &mut Context, input: &InputMap) -> GameResult {
+        for (player, racer) in self.racers.iter().enumerate() {
+            let out = if racer.died.is_some() { "  out" } else { "" };
+            let text = Text::new(format!("P{} ({}): {}{}", player + 1, input.key_name(player), racer.score, out));
+            let position = Point2::new(10.0 + SCORE_SPACING * player as f32, 10.0);
+            graphics::draw(ctx, &text, (position, graphics::Color::from(TINTS[player])))?;
+        }
+        Ok(())
+    }
+
+    pub fn draw_results(&self, ctx: &mut Context) -> GameResult {
+        let mut lines = vec!["RESULTS".to_string()];
+        for (place, player) in self.standings() {
+            let racer = &self.racers[player];
+            lines.push(format!("{}. Player {}   {} pipes, {} pearls", place, player + 1, racer.score, racer.pearls));
+        }
+        lines.push("Press space to play again".to_string());
+        game_over::draw_results(ctx, &lines)
+    }
+}
+
+/// Moves a local match on by one update, in place of the usual one-crab update.
+/// Every crab shares the world, so pipes are scored by every crab still flying
+/// and whoever touches a pearl or power up first gets it.
+pub fn update(game: &mut GameState, ctx: &mut Context) {
+    let mut round = match game.local_match.take() {
+        Some(round) => round,
+        None => return,
+    };
+
+    let mut started = false;
+    for (player, racer) in round.racers.iter_mut().enumerate() {
+        if racer.died.is_some() {
+            racer.landed = racer.crab.tumble();
+            continue;
+        }
+        let flap_held = game.input.flap_held(ctx, player);
+        if racer.crab.update(flap_held, &game.play_state) == PlayState::Play {
+            started = true;
+        }
+        if game.play_state.is_playing() {
+            racer.power_ups.update();
+        }
+        racer.crab.size = racer.power_ups.crab_size();
+    }
+    if started && !game.play_state.is_playing() {
+        game.play_state = PlayState::Play;
+        game.run_started = ggez::timer::time_since_start(ctx);
+    }
+
+    let speed = round.world_speed();
+    systems::movement(&mut game.world, &game.play_state, speed);
+    systems::scripts(&mut game.world, &game.play_state);
+    systems::animate(&mut game.world);
+    for pipe in systems::recycle(&mut game.world, &mut game.pipe_tracker) {
+        game.obstacles.pipe_recycled(&mut game.world, pipe, round.best_score(), game.pipe_tracker.gap_height);
+    }
+
+    if game.play_state.is_playing() {
+        game.run_ticks += 1;
+        let passed = systems::scoring(&mut game.world, &game.play_state);
+        if passed > 0 {
+            game.sound_player.score();
+        }
+        for racer in round.racers.iter_mut().filter(|racer| racer.died.is_none()) {
+            racer.score += passed as i128;
+            if racer.power_ups.is_active(PowerUp::Magnet) {
+                systems::magnet(&mut game.world, racer.crab.position);
+            }
+            let rect = racer.crab.get_bounds();
+            for collectible in systems::collect(&mut game.world, &rect) {
+                match collectible {
+                    Collectible::Pearl => racer.pearls += 1,
+                    Collectible::PowerUp(power_up) => racer.power_ups.activate(power_up),
+                }
+                game.sound_player.score();
+            }
+
+            let hit = systems::hit(&game.world, &rect);
+            let cause = if hit.is_some() && !racer.power_ups.absorb_hit() {
+                hit
+            } else if racer.crab.position.y > GROUND {
+                Some(DeathCause::Ground)
+            } else {
+                None
+            };
+            if let Some(cause) = cause {
+                racer.died = Some((game.run_ticks, cause));
+                game.sound_player.ouch();
+                game.effects.hit();
+            }
+        }
+        if round.is_over() {
+            game.play_state = PlayState::GameOver;
+        }
+    }
+
+    game.local_match = Some(round);
+}
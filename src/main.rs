@@ -1,144 +1,261 @@
-use atlas::Sprite;
-use ggez::nalgebra::{Point2, Vector2};
-use ggez::{
-    event::EventHandler,
-    graphics::{spritebatch::SpriteBatch, Text},
-    Context,
-    GameResult,
-    graphics,
-    event
-};
-mod entity;
-mod atlas;
-mod pipe;
-mod game_state;
-mod crab;
-mod audio;
-mod window;
-mod tile;
-use entity::PlayState;
-use std::time::Duration;
-use crate::crab::PlayerEntity;
-use crate::game_state::GameState;
-
-pub const NUMBER_OF_TILES: u8 = 14;
-pub const RESTART_AFTER: Duration = std::time::Duration::from_secs(1);
-
-
-impl EventHandler for GameState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let state = self.play_state.clone();
-        self.handle_after_losing(ctx, state);
-        let state = self.player.update(ctx, &self.play_state);
-        if !self.play_state.is_playing() && state == PlayState::Play {
-            self.play_state = PlayState::Play;
-        }
-        for i in 0..self.pipes.len() {
-            self.pipes[i].update(&mut self.pipe_tracker, &self.play_state);
+use flappybird::cli::{self, Command, Options};
+use flappybird::game_state::GameState;
+use flappybird::input::InputMap;
+use flappybird::net::{self, Peer};
+use flappybird::online;
+use flappybird::palette::Palette;
+use flappybird::profile;
+use flappybird::profile_menu::Launcher;
+use flappybird::recording::Recording;
+use flappybird::sim::{Bot, Simulation};
+use flappybird::versus::Versus;
+use flappybird::{assets, atlas, create_batch_sprite, reachability, settings, stats, window};
+use ggez::event;
+
+/// How many pipes of each course `scan` checks.
+const SCAN_PIPES: usize = 100;
+/// How many runs `leaderboard` prints.
+const LEADERBOARD_RUNS: usize = 10;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, mut options) = match cli::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::HELP);
+            std::process::exit(2);
         }
-        update_it(self, ctx);
+    };
 
-        Ok(())
+    profile::adopt_shared_saves();
+    // Playing without a profile asks for one in the window. Everything else uses the one played last.
+    let needs_profile = match command {
+        Command::Help | Command::ListProfiles | Command::ExportProfile(_) | Command::ImportProfile { .. } => false,
+        Command::Play => options.profile.is_some(),
+        _ => true,
+    };
+    if needs_profile {
+        open_profile(&mut options);
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        graphics::clear(ctx, graphics::Color::from_rgb(112, 216, 255));
-
-        self.player.draw(&mut self.sprite_batch)?;
-        if !self.tiles_drawn {
-            for i in 0..self.tiles.len() {
-                self.tiles[i].draw(&mut self.sprite_batch);
+    match command {
+        Command::Play if options.profile.is_none() => {
+            let cb = window::build_window(&options);
+            let (ctx, event_loop) = &mut cb.build().expect("Failed to build ggez!");
+            window::fit_screen(ctx);
+            event::run(ctx, event_loop, &mut Launcher::new(&options)).unwrap();
+        }
+        Command::Play => play(&options, None),
+        Command::Replay(file) => match Recording::load(&file) {
+            Ok(recording) => play(&options, Some(recording)),
+            Err(e) => {
+                println!("Couldn't load the run: {}", e);
+                std::process::exit(1);
             }
-            self.tiles_drawn = false;
+        },
+        Command::Simulate { ticks, bot } => simulate(&options, ticks, &bot),
+        Command::Bench { runs, ticks, bot } => bench(&options, runs, ticks, &bot),
+        Command::ValidateAssets => {
+            let problems = assets::validate(&options.resources, &options.settings_file());
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                std::process::exit(1);
+            }
+            println!("Everything in {} looks fine", options.resources.display());
         }
-        for i in 0..self.pipes.len() {
-            self.pipes[i].draw(ctx, &mut self.sprite_batch)?;
-
+        Command::Leaderboard(None) => print_leaderboard(),
+        Command::Leaderboard(Some(server)) => print_online_leaderboard(&server),
+        Command::Scan { seeds } => scan_seeds(&options, seeds),
+        Command::Versus(peer) => versus(&options, &peer),
+        Command::ListProfiles => {
+            let last = profile::last();
+            for name in profile::list() {
+                println!("{} {}", if name == last { "*" } else { " " }, name);
+            }
         }
-
-        let p = graphics::DrawParam::new().scale(Vector2::new(4.0, 4.0));
-        {
-            graphics::draw(ctx, &mut self.sprite_batch, p)?;
-            self.sprite_batch.clear();
+        Command::ExportProfile(file) => {
+            let name = options.profile.clone().unwrap_or_else(profile::last);
+            match profile::export(&name, &file) {
+                Ok(()) => println!("Exported profile {} to {}", name, file.display()),
+                Err(e) => {
+                    println!("Couldn't export profile {}: {}", name, e);
+                    std::process::exit(1);
+                }
+            }
         }
-
-        draw_scores(self.score, self.best_score, ctx);
-
-        graphics::present(ctx)?;
-        std::thread::yield_now();
-
-        Ok(())
+        Command::ImportProfile { file, name } => match profile::import(&file, name) {
+            Ok(name) => println!("Imported profile {}. Play it with --profile \"{}\"", name, name),
+            Err(e) => {
+                println!("Couldn't import the profile: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Command::Help => print!("{}", cli::HELP),
     }
 }
 
-fn update_it(game: &mut GameState, ctx: &mut Context) {
-    let player = &game.player;
-    let pipes = &mut game.pipes;
-    for i in 0..pipes.len() {
-        if pipes[i].set_scored(&game.play_state) {
-            game.score += 1;
-            println!("{}", i);
-            game.sound_player.score();
-        }
-        // if crab hits a pipe or hits ground
-        if (player.overlaps(&pipes[i]) || hits_ground(player)) && game.play_state.is_playing() {
-            game.sound_player.ouch();
-            game.play_state.set_dead(ggez::timer::time_since_start(ctx));
-        }
+/// Starts using the profile asked for, or the one played last.
+fn open_profile(options: &mut Options) {
+    let name = options.profile.clone().unwrap_or_else(profile::last);
+    if let Err(e) = profile::open(&name, &options.default_settings_file()) {
+        println!("Couldn't open profile {}: {}", name, e);
+        std::process::exit(1);
     }
+    options.profile = Some(name);
 }
 
-fn hits_ground(player: &Box<PlayerEntity>) -> bool {
-    player.position.y > 135.0
-}
-
-fn main() {
-    let resource_dir = std::path::PathBuf::from("./resources");
-
-    let cb = window::build_window(resource_dir);
+fn play(options: &Options, replay: Option<Recording>) {
+    let cb = window::build_window(options);
 
     let (ctx, event_loop) = &mut cb.build().expect("Failed to build ggez!");
+    window::fit_screen(ctx);
 
     let batch = create_batch_sprite(ctx);
 
-    let mut state = GameState::new(ctx, batch);
+    let mut state = GameState::new(ctx, batch, options, replay);
 
     state.sound_player.begin();
     event::run(ctx, event_loop, &mut state).unwrap();
 }
 
-fn create_batch_sprite(ctx: &mut Context) -> SpriteBatch {
-    let image = graphics::Image::new(ctx, "/texture_atlas.png").unwrap();
-    let mut batch = graphics::spritebatch::SpriteBatch::new(image);
-    batch.set_filter(graphics::FilterMode::Nearest);
-    batch
+/// Finds the other game, then opens the window and races it.
+fn versus(options: &Options, peer: &Peer) {
+    let settings = settings::Settings::load(&options.settings_file());
+    let (connection, seed, course) = match net::connect(peer, options.seed, &settings.course) {
+        Ok(agreed) => agreed,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Racing on seed {} with the {} course", seed, course.name());
+
+    let cb = window::build_window(options);
+    let (ctx, event_loop) = &mut cb.build().expect("Failed to build ggez!");
+    window::fit_screen(ctx);
+    let mut batch = create_batch_sprite(ctx);
+    let palette = Palette::new(&settings.palette);
+    palette.recolour(ctx, &mut batch, atlas::ATLAS_IMAGE);
+    let atlas = atlas::Atlas::parse_atlas_json(&options.resources.join(atlas::ATLAS_FILE));
+    let input = InputMap::new(&settings.controls);
+    let mut state = Versus::new(connection, batch, &atlas, seed, &course, input, palette);
+    event::run(ctx, event_loop, &mut state).unwrap();
+}
+
+/// Reads the bot's name, or stops with an error if there's no such bot.
+fn load_bot(name: &str) -> Bot {
+    Bot::from_name(name).unwrap_or_else(|e| {
+        println!("Couldn't load the bot {}: {}", name, e);
+        std::process::exit(1);
+    })
 }
 
-impl PlayState {
-    fn is_playing(&self) -> bool {
-        *self == PlayState::Play
+/// Plays one run without a window and prints how it went.
+fn simulate(options: &Options, ticks: u32, bot: &str) {
+    let atlas = atlas::Atlas::parse_atlas_json(&options.resources.join(atlas::ATLAS_FILE));
+    let settings = settings::Settings::load(&options.settings_file());
+    let bot = load_bot(bot);
+    let mut sim = Simulation::for_bot(&atlas, &settings.course, options.seed, &bot);
+    sim.play(&bot, ticks);
+
+    let ending = match sim.cause {
+        Some(cause) => format!("killed by {}", cause.name()),
+        None => "still going".to_string(),
+    };
+    println!(
+        "Score {} with {} pearls and {} flaps after {} updates, {}",
+        sim.score, sim.pearls, sim.flaps, sim.ticks, ending
+    );
+}
+
+/// Times lots of simulated runs, each on its own seed.
+fn bench(options: &Options, runs: u32, ticks: u32, bot: &str) {
+    let atlas = atlas::Atlas::parse_atlas_json(&options.resources.join(atlas::ATLAS_FILE));
+    let settings = settings::Settings::load(&options.settings_file());
+    let bot = load_bot(bot);
+
+    let started = std::time::Instant::now();
+    let mut total_ticks = 0u64;
+    let mut total_score = 0i64;
+    for run in 0..runs {
+        let seed = options.seed.wrapping_add(run);
+        let mut sim = Simulation::for_bot(&atlas, &settings.course, seed, &bot);
+        sim.play(&bot, ticks);
+        total_ticks += sim.ticks as u64;
+        total_score += sim.score;
     }
+    let secs = started.elapsed().as_secs_f64().max(std::f64::EPSILON);
+    println!(
+        "{} runs, {} updates in {:.2}s: {:.0} updates a second, average score {:.1}",
+        runs,
+        total_ticks,
+        secs,
+        total_ticks as f64 / secs,
+        total_score as f64 / runs.max(1) as f64
+    );
+}
 
-    fn set_dead (&mut self, time : std::time::Duration) {
-        *self = PlayState::Dead {
-            time
-        }
+fn print_leaderboard() {
+    let runs = stats::Stats::best_runs(LEADERBOARD_RUNS);
+    if runs.is_empty() {
+        println!("No runs played yet");
+        return;
     }
+    for (i, run) in runs.iter().enumerate() {
+        println!(
+            "{:>2}. {:>4} points  seed {:<10} {:.1}s  {} pearls{}",
+            i + 1,
+            run.score,
+            run.seed,
+            run.duration_secs,
+            run.pearls,
+            if run.assisted { "  (assisted)" } else { "" }
+        );
+    }
+}
 
-    fn is_not_dead (&self) -> bool {
-        *self == PlayState::Play || *self == PlayState::StartScreen
+/// Prints the best runs on the server, then the best assisted ones.
+fn print_online_leaderboard(server: &str) {
+    for &assisted in &[false, true] {
+        let entries = match online::top(server, LEADERBOARD_RUNS, assisted) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        println!("{}", if assisted { "\nWith assists:" } else { "Without assists:" });
+        print_entries(&entries);
     }
 }
 
-fn draw_scores(score : i128, best_score: i128, ctx: &mut Context) {
-    let fps_display = Text::new(format!(
-        "Best Score: {}   Current Score: {}",
-        best_score, score
-    ));
+fn print_entries(entries: &[online::Entry]) {
+    if entries.is_empty() {
+        println!("No runs yet");
+        return;
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        println!(
+            "{:>2}. {:>4} points  {:<16} seed {:<10} {} course",
+            i + 1,
+            entry.score,
+            entry.name,
+            entry.seed,
+            entry.course
+        );
+    }
+}
 
-    graphics::draw(
-        ctx,
-        &fps_display,
-        (Point2::new(10.0, 10.0), graphics::WHITE),
-    );
+/// Checks the courses of lots of seeds can be finished and prints what it finds.
+fn scan_seeds(options: &Options, seeds: u32) {
+    let atlas = atlas::Atlas::parse_atlas_json(&options.resources.join(atlas::ATLAS_FILE));
+    let settings = settings::Settings::load(&options.settings_file());
+    let pipe = atlas.create_sprite("pipe_top.png");
+    let crab = atlas.create_sprite("crab0.png");
+    let gap_height = settings.assists.clamped().gap_height();
+    let report =
+        reachability::scan(&settings.course, gap_height, seeds, SCAN_PIPES, pipe.width, crab.width, crab.height);
+    print!("{}", report);
 }
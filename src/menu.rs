@@ -0,0 +1,122 @@
+use crate::game_state::GameState;
+use crate::input::MAX_PLAYERS;
+use crate::window::SCREEN_HEIGHT;
+use ggez::event::KeyCode;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+
+/// The pages that can be opened from the start screen.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MenuPage {
+    Achievements,
+    Stats,
+    Wardrobe,
+}
+
+const PAGES: [MenuPage; 3] = [MenuPage::Achievements, MenuPage::Stats, MenuPage::Wardrobe];
+
+impl MenuPage {
+    /// Each page is opened and closed with its own key.
+    fn key(&self) -> KeyCode {
+        match self {
+            MenuPage::Achievements => KeyCode::A,
+            MenuPage::Stats => KeyCode::S,
+            MenuPage::Wardrobe => KeyCode::W,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            MenuPage::Achievements => "A: achievements",
+            MenuPage::Stats => "S: stats",
+            MenuPage::Wardrobe => "W: wardrobe",
+        }
+    }
+}
+
+/// Opens and closes menu pages. Returns true if the key was used by the menu.
+pub fn handle_key(game: &mut GameState, keycode: KeyCode) -> bool {
+    if let Some(page) = game.menu_page {
+        if keycode == KeyCode::Back || keycode == page.key() {
+            game.menu_page = None;
+        } else if page == MenuPage::Wardrobe {
+            game.wardrobe_key(keycode);
+        }
+        return true;
+    }
+
+    if keycode == KeyCode::C {
+        game.toggle_campaign();
+        return true;
+    }
+    if keycode == KeyCode::E {
+        game.open_editor();
+        return true;
+    }
+    if keycode == KeyCode::P {
+        game.toggle_practice();
+        return true;
+    }
+    if keycode == KeyCode::M {
+        game.toggle_local_match();
+        return true;
+    }
+    if keycode == KeyCode::T && game.themes.list().len() > 1 {
+        game.next_theme();
+        return true;
+    }
+
+    match PAGES.iter().find(|page| page.key() == keycode) {
+        Some(page) => {
+            game.menu_page = Some(*page);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Reminds the player what they can do in practice mode.
+pub fn draw_practice_hint(ctx: &mut Context) -> GameResult {
+    let hint = Text::new("Practice   R: rewind   K: checkpoint at the last pipe");
+    graphics::draw(ctx, &hint, (Point2::new(10.0, SCREEN_HEIGHT - 30.0), graphics::WHITE))
+}
+
+/// Draws the open page, or the list of pages if none is open.
+pub fn draw_menu(ctx: &mut Context, game: &mut GameState) -> GameResult {
+    match game.menu_page {
+        Some(MenuPage::Achievements) => game.achievements.draw_gallery(ctx),
+        Some(MenuPage::Stats) => game.stats.draw_lifetime(ctx),
+        Some(MenuPage::Wardrobe) => {
+            game.wardrobe.draw_page(ctx, &mut game.sprite_batch, &game.wallet, &game.achievements)
+        }
+        None => {
+            let mut labels = PAGES.iter().map(|page| page.label()).collect::<Vec<&str>>();
+            labels.push(if game.campaign.is_some() { "C: endless" } else { "C: campaign" });
+            labels.push("E: editor");
+            labels.push(if game.practice.is_some() { "P: normal" } else { "P: practice" });
+            let players = match &game.local_match {
+                Some(round) if round.players() < MAX_PLAYERS => format!("M: {} players", round.players() + 1),
+                Some(_) => "M: 1 player".to_string(),
+                None => "M: 2 players".to_string(),
+            };
+            labels.push(&players);
+            let theme = format!("T: theme ({})", game.themes.theme().name);
+            if game.themes.list().len() > 1 {
+                labels.push(&theme);
+            }
+            let hint = Text::new(format!("Space: play   {}", labels.join("   ")));
+            graphics::draw(ctx, &hint, (Point2::new(10.0, SCREEN_HEIGHT - 30.0), graphics::WHITE))?;
+
+            if let Some(campaign) = &game.campaign {
+                let level = Text::new(format!("Level {}: {}", campaign.current + 1, campaign.level().name));
+                graphics::draw(ctx, &level, (Point2::new(10.0, 40.0), graphics::WHITE))?;
+            }
+            if let Some(error) = &game.level_error {
+                let error = Text::new(error.as_str());
+                graphics::draw(ctx, &error, (Point2::new(10.0, 40.0), graphics::Color::from_rgb(255, 80, 80)))?;
+            }
+            Ok(())
+        }
+    }
+}
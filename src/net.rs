@@ -0,0 +1,259 @@
+//! Racing someone else's game over the network.
+//! One game hosts and the other joins it by address. They agree on a course,
+//! then send each other whether the flap key was down on every update.
+//! Each game plays the other's flaps through its own simulation of their run,
+//! so only the flaps go over the wire and the two games never share any state.
+
+use crate::settings::CourseSettings;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+pub const DEFAULT_PORT: u16 = 7777;
+/// Changes whenever the messages do, so different versions of the game don't try to race.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Everything the two games say to each other, one JSON object per line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// The first thing each side sends.
+    Hello { version: u32 },
+    /// Sent by the host: the course to race on.
+    Course { seed: u32, course: CourseSettings },
+    /// The window is open and the race can start.
+    Ready,
+    /// Whether the flap key was down on this update of the sender's run. One for every update, in order.
+    Flap { tick: u32, held: bool },
+    /// The sender's crab has died. Lets the other side check it saw the same run.
+    Died { tick: u32, score: i64 },
+}
+
+/// How to find the other game.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Peer {
+    /// Wait for the other game to join on this port.
+    Host(u16),
+    /// Join the game hosting at this address, like `192.168.1.20` or `localhost:7777`.
+    Join(String),
+}
+
+/// A connection to the other game. Messages are read on their own thread
+/// so the game never waits on the network in the middle of an update.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+    /// Set once the other game has gone.
+    pub closed: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let (sender, incoming) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                match serde_json::from_str(&line) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => println!("Couldn't read a message from the other game: {}", e),
+                }
+            }
+        });
+        Ok(Self {
+            stream,
+            incoming,
+            closed: false,
+        })
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        if self.closed {
+            return;
+        }
+        let line = match serde_json::to_string(message) {
+            Ok(line) => line + "\n",
+            Err(e) => {
+                println!("Couldn't write a message to the other game: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.stream.write_all(line.as_bytes()) {
+            println!("Lost the other game: {}", e);
+            self.closed = true;
+        }
+    }
+
+    /// Waits for the next message. Only for before the race starts.
+    fn wait(&mut self) -> Result<Message, String> {
+        self.incoming.recv().map_err(|_| "The other game left".to_string())
+    }
+
+    /// Every message that has come in since last time, without waiting.
+    pub fn received(&mut self) -> Vec<Message> {
+        let mut messages = vec![];
+        loop {
+            match self.incoming.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// Finds the other game and agrees on the course. The host's seed and course are the ones raced on.
+pub fn connect(peer: &Peer, seed: u32, course: &CourseSettings) -> Result<(Connection, u32, CourseSettings), String> {
+    let stream = match peer {
+        Peer::Host(port) => {
+            let listener = TcpListener::bind(("0.0.0.0", *port))
+                .map_err(|e| format!("Couldn't listen on port {}: {}", port, e))?;
+            println!("Waiting for someone to join on port {}", port);
+            let (stream, address) = listener.accept().map_err(|e| e.to_string())?;
+            println!("{} joined", address);
+            stream
+        }
+        Peer::Join(address) => {
+            let address = if address.contains(':') {
+                address.clone()
+            } else {
+                format!("{}:{}", address, DEFAULT_PORT)
+            };
+            TcpStream::connect(&address).map_err(|e| format!("Couldn't join {}: {}", address, e))?
+        }
+    };
+    let mut connection = Connection::new(stream)?;
+
+    connection.send(&Message::Hello { version: PROTOCOL_VERSION });
+    match connection.wait()? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {}
+        Message::Hello { version } => {
+            return Err(format!("The other game speaks version {}, this one speaks {}", version, PROTOCOL_VERSION))
+        }
+        other => return Err(format!("Expected hello, got {:?}", other)),
+    }
+
+    match peer {
+        Peer::Host(_) => {
+            connection.send(&Message::Course { seed, course: course.clone() });
+            Ok((connection, seed, course.clone()))
+        }
+        Peer::Join(_) => match connection.wait()? {
+            Message::Course { seed, course } => Ok((connection, seed, course)),
+            other => Err(format!("Expected the course, got {:?}", other)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::JoinHandle;
+
+    /// Hosts on a free port of this machine and says `messages` to whoever joins,
+    /// like a game that speaks whatever the test wants. Gives back the address and what the joiner said first.
+    fn fake_host(messages: Vec<Message>) -> (String, JoinHandle<Message>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let host = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for message in &messages {
+                stream.write_all((serde_json::to_string(message).unwrap() + "\n").as_bytes()).unwrap();
+            }
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        });
+        (address, host)
+    }
+
+    fn said_hello(message: Message) -> bool {
+        match message {
+            Message::Hello { version } => version == PROTOCOL_VERSION,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn joining_takes_the_hosts_course() {
+        let (address, host) = fake_host(vec![
+            Message::Hello { version: PROTOCOL_VERSION },
+            Message::Course { seed: 42, course: CourseSettings::Sine { amplitude: 20.0, period: 8.0 } },
+        ]);
+        let (_, seed, course) = connect(&Peer::Join(address), 7, &CourseSettings::Perlin).unwrap();
+        assert_eq!(seed, 42);
+        match course {
+            CourseSettings::Sine { amplitude, period } => assert_eq!((amplitude, period), (20.0, 8.0)),
+            other => panic!("Joined the wrong course: {:?}", other),
+        }
+        assert!(said_hello(host.join().unwrap()));
+    }
+
+    #[test]
+    fn both_sides_race_the_hosts_course() {
+        let port = TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port();
+        let host = std::thread::spawn(move || connect(&Peer::Host(port), 42, &CourseSettings::Perlin));
+        // Keep trying until the host is listening.
+        let peer = Peer::Join(format!("127.0.0.1:{}", port));
+        let mut joined = Err(String::new());
+        for _ in 0..200 {
+            joined = connect(&peer, 7, &CourseSettings::Uniform { max_delta: 9.0 });
+            if joined.is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let (_, seed, course) = joined.unwrap();
+        assert_eq!((seed, course.name()), (42, "perlin"));
+        let (_, seed, course) = host.join().unwrap().unwrap();
+        assert_eq!((seed, course.name()), (42, "perlin"));
+    }
+
+    #[test]
+    fn different_versions_dont_race() {
+        let (address, host) = fake_host(vec![Message::Hello { version: PROTOCOL_VERSION + 1 }]);
+        let error = connect(&Peer::Join(address), 7, &CourseSettings::Perlin).err().unwrap();
+        assert!(error.contains(&format!("version {}", PROTOCOL_VERSION + 1)), "{}", error);
+        assert!(said_hello(host.join().unwrap()));
+    }
+
+    #[test]
+    fn the_first_message_has_to_be_hello() {
+        let (address, host) = fake_host(vec![Message::Ready]);
+        let error = connect(&Peer::Join(address), 7, &CourseSettings::Perlin).err().unwrap();
+        assert!(error.starts_with("Expected hello"), "{}", error);
+        assert!(said_hello(host.join().unwrap()));
+    }
+
+    #[test]
+    fn messages_are_one_json_object_each() {
+        let messages = vec![
+            (Message::Hello { version: 1 }, r#"{"type":"hello","version":1}"#),
+            (
+                Message::Course { seed: 3, course: CourseSettings::Uniform { max_delta: 30.0 } },
+                r#"{"type":"course","seed":3,"course":{"generator":"uniform","max_delta":30.0}}"#,
+            ),
+            (Message::Ready, r#"{"type":"ready"}"#),
+            (Message::Flap { tick: 12, held: true }, r#"{"type":"flap","tick":12,"held":true}"#),
+            (Message::Died { tick: 300, score: 9 }, r#"{"type":"died","tick":300,"score":9}"#),
+        ];
+        for (message, json) in messages {
+            assert_eq!(serde_json::to_string(&message).unwrap(), json);
+            let read: Message = serde_json::from_str(json).unwrap();
+            assert_eq!(serde_json::to_string(&read).unwrap(), json);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::atlas::{Atlas, Sprite};
+use crate::collectibles::{Collectible, PowerUp, POWER_UPS};
+use crate::course;
+use crate::components::{Animation, Collider, Motion, MotionScript, Transform, Velocity};
+use crate::events::DeathCause;
+use crate::pipe::{pipe_spacing, pipe_velocity};
+use crate::window::{SCALE, SCREEN_WIDTH};
+use crate::world::{Entity, World};
+use ggez::graphics;
+use ggez::nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+/// Hazards are a little smaller than they look, to be fair to the crab.
+const HAZARD_MARGIN: f32 = 2.0;
+const SEAGULL_SPEED: f32 = 1.8;
+/// Score at which the special obstacles turn up as often as they ever will.
+const MAX_DIFFICULTY_SCORE: f32 = 40.0;
+const PEARL_CHANCE: f32 = 0.5;
+const POWER_UP_CHANCE: f32 = 0.08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleKind {
+    /// A pipe pair whose gap moves up and down.
+    BobbingPipe,
+    /// A pipe pair whose gap closes and opens.
+    JawPipe,
+    /// Drifts up and down between two pipes.
+    Jellyfish,
+    /// Flies in faster than the pipes and swoops down.
+    Seagull,
+}
+
+/// Every special obstacle, the score it starts turning up at and how often it turns up.
+const CATALOGUE: [(ObstacleKind, i128, f32); 4] = [
+    (ObstacleKind::BobbingPipe, 5, 3.0),
+    (ObstacleKind::Jellyfish, 8, 2.0),
+    (ObstacleKind::JawPipe, 12, 2.0),
+    (ObstacleKind::Seagull, 16, 1.0),
+];
+
+/// The sprites for everything that can be put in the crab's way, or picked up.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObstacleSprites {
+    pub pipe_body: Sprite,
+    pub pipe_tip: Sprite,
+    pub jellyfish: Sprite,
+    pub seagull: Vec<Sprite>,
+    pub pearl: Sprite,
+    pub power_ups: Vec<(PowerUp, Sprite)>,
+}
+
+impl ObstacleSprites {
+    pub fn new(sprites: &Atlas) -> Self {
+        Self {
+            pipe_body: sprites.create_sprite("pipe_bottom.png"),
+            pipe_tip: sprites.create_sprite("pipe_top.png"),
+            jellyfish: sprites.create_sprite("jellyfish.png"),
+            seagull: vec![
+                sprites.create_sprite("seagull0.png"),
+                sprites.create_sprite("seagull1.png"),
+            ],
+            pearl: sprites.create_sprite("pearl.png"),
+            power_ups: POWER_UPS
+                .iter()
+                .map(|p| (*p, sprites.create_sprite(p.sprite_name())))
+                .collect(),
+        }
+    }
+
+    pub fn power_up(&self, power_up: PowerUp) -> Sprite {
+        self.power_ups
+            .iter()
+            .find(|(p, _)| *p == power_up)
+            .map(|(_, sprite)| sprite.clone())
+            .expect("Every power up has a sprite")
+    }
+}
+
+/// Decides what each pipe turns into when it comes around again,
+/// and what there is to pick up around it.
+/// Its choices only depend on the seed, so the same seed plays out the same way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObstacleGenerator {
+    seed: u32,
+    rolls: u32,
+    sprites: ObstacleSprites,
+}
+
+impl ObstacleGenerator {
+    pub fn new(sprites: ObstacleSprites, seed: u32) -> Self {
+        Self {
+            seed,
+            rolls: 0,
+            sprites,
+        }
+    }
+
+    /// A number in [0, 1) made from the seed and how many rolls came before it.
+    fn roll(&mut self) -> f32 {
+        self.rolls += 1;
+        course::roll(self.seed, self.rolls)
+    }
+
+    /// Picks one of the obstacles unlocked at this score, or None for a plain pipe.
+    fn choose(&mut self, score: i128) -> Option<ObstacleKind> {
+        let difficulty = (score as f32 / MAX_DIFFICULTY_SCORE).min(1.0);
+        if self.roll() >= 0.2 + 0.5 * difficulty {
+            return None;
+        }
+
+        let unlocked = CATALOGUE.iter().filter(|(_, unlock, _)| score >= *unlock);
+        let total: f32 = unlocked.clone().map(|(_, _, weight)| weight).sum();
+        let mut pick = self.roll() * total;
+        for (kind, _, weight) in unlocked {
+            if pick < *weight {
+                return Some(*kind);
+            }
+            pick -= weight;
+        }
+        None
+    }
+
+    /// Called when a pipe comes around again.
+    /// Turns it back into a plain pipe, then maybe into something nastier,
+    /// and sometimes leaves something to pick up.
+    /// `gap_height` is the open gap of the course's pipes, from its `PipeTracker`.
+    pub fn pipe_recycled(&mut self, world: &mut World, pipe: Entity, score: i128, gap_height: f32) {
+        let (x, gap_centre, width) = match (&world.transforms[pipe], &mut world.pipe_pairs[pipe]) {
+            (Some(transform), Some(pipe_pair)) => {
+                pipe_pair.gap_height = gap_height;
+                world.colliders[pipe] = Some(pipe_pair.collider());
+                (transform.position.x, transform.position.y, pipe_pair.width())
+            }
+            _ => return,
+        };
+        world.motion_scripts[pipe] = None;
+
+        // Halfway between this pipe and the one in front of it.
+        let between_pipes = x + width / 2.0 - pipe_spacing(width) / 2.0;
+        let kind = self.choose(score);
+        match kind {
+            Some(ObstacleKind::BobbingPipe) => {
+                let amplitude = 8.0 + 8.0 * self.roll();
+                world.motion_scripts[pipe] = Some(bobbing_pipe(amplitude));
+            }
+            Some(ObstacleKind::JawPipe) => {
+                world.motion_scripts[pipe] = Some(jaw_pipe(gap_height, gap_height * 0.6));
+            }
+            Some(ObstacleKind::Jellyfish) => {
+                spawn_jellyfish(world, &self.sprites, between_pipes, gap_centre);
+            }
+            Some(ObstacleKind::Seagull) => {
+                spawn_seagull(world, &self.sprites, SCREEN_WIDTH / SCALE);
+            }
+            None => {}
+        }
+
+        let roll = self.roll();
+        if roll < POWER_UP_CHANCE && kind != Some(ObstacleKind::Jellyfish) {
+            let pick = (self.roll() * POWER_UPS.len() as f32) as usize;
+            let power_up = POWER_UPS[pick.min(POWER_UPS.len() - 1)];
+            spawn_power_up(world, &self.sprites, power_up, between_pipes, gap_centre);
+        } else if roll < PEARL_CHANCE {
+            spawn_pearl(world, &self.sprites, x + width / 2.0, gap_centre);
+        }
+    }
+}
+
+pub fn bobbing_pipe(amplitude: f32) -> MotionScript {
+    MotionScript::new(Motion::Bob { amplitude, speed: 0.04 })
+}
+
+pub fn jaw_pipe(open_gap: f32, closed_gap: f32) -> MotionScript {
+    MotionScript::new(Motion::Jaw {
+        open_gap,
+        closed_gap,
+        speed: 0.05,
+    })
+}
+
+/// A jellyfish centred on (x, y), drifting up and down and along with the pipes.
+pub fn spawn_jellyfish(world: &mut World, sprites: &ObstacleSprites, x: f32, y: f32) -> Entity {
+    let sprite = sprites.jellyfish.clone();
+    let x = x - sprite.width / 2.0;
+    let y = y - sprite.height / 2.0;
+    world
+        .spawn()
+        .with_transform(Transform::new(x, y))
+        .with_collider(hazard_collider(&sprite, DeathCause::Jellyfish))
+        .with_sprite(sprite)
+        .with_velocity(Velocity(Vector2::new(pipe_velocity(), 0.0)))
+        .with_motion_script(MotionScript::new(Motion::Bob { amplitude: 12.0, speed: 0.06 }))
+        .build()
+}
+
+/// A seagull that flies in from x and swoops down across the screen.
+pub fn spawn_seagull(world: &mut World, sprites: &ObstacleSprites, x: f32) -> Entity {
+    let sprite = sprites.seagull[0].clone();
+    let screen_width = SCREEN_WIDTH / SCALE;
+    let duration = (screen_width + sprite.width) / SEAGULL_SPEED;
+    world
+        .spawn()
+        .with_transform(Transform::new(x, 0.0))
+        .with_collider(hazard_collider(&sprite, DeathCause::Seagull))
+        .with_sprite(sprite)
+        .with_velocity(Velocity(Vector2::new(-SEAGULL_SPEED, 0.0)))
+        .with_motion_script(MotionScript::new(Motion::Swoop { depth: 90.0, duration }))
+        .with_animation(Animation {
+            frames: sprites.seagull.clone(),
+            ticks_per_frame: 8,
+            ticks: 0,
+        })
+        .build()
+}
+
+pub fn spawn_pearl(world: &mut World, sprites: &ObstacleSprites, x: f32, y: f32) -> Entity {
+    spawn_collectible(world, Collectible::Pearl, sprites.pearl.clone(), x, y)
+}
+
+pub fn spawn_power_up(world: &mut World, sprites: &ObstacleSprites, power_up: PowerUp, x: f32, y: f32) -> Entity {
+    spawn_collectible(world, Collectible::PowerUp(power_up), sprites.power_up(power_up), x, y)
+}
+
+/// Collectibles sit still and move along with the pipes. They are centred on (x, y).
+fn spawn_collectible(world: &mut World, collectible: Collectible, sprite: Sprite, x: f32, y: f32) -> Entity {
+    let x = x - sprite.width / 2.0;
+    let y = y - sprite.height / 2.0;
+    world
+        .spawn()
+        .with_transform(Transform::new(x, y))
+        .with_sprite(sprite)
+        .with_velocity(Velocity(Vector2::new(pipe_velocity(), 0.0)))
+        .with_collectible(collectible)
+        .build()
+}
+
+fn hazard_collider(sprite: &Sprite, cause: DeathCause) -> Collider {
+    let bounds = graphics::Rect::new(
+        HAZARD_MARGIN,
+        HAZARD_MARGIN,
+        sprite.width - HAZARD_MARGIN * 2.0,
+        sprite.height - HAZARD_MARGIN * 2.0,
+    );
+    Collider::new(bounds, cause)
+}
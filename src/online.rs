@@ -0,0 +1,327 @@
+//! The online leaderboard: what the game and the server say to each other,
+//! the client side of it, and how the server checks a run before it's listed.
+//! Each request and response is one JSON object on its own line over TCP.
+
+use crate::atlas::Atlas;
+use crate::crab::create_player;
+use crate::entity::PlayState;
+use crate::recording::Recording;
+use crate::settings::CourseSettings;
+use crate::sim::{Bot, Simulation};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// How many runs a top list can have.
+pub const MAX_TOP: usize = 100;
+/// The longest run the server will play through to check, in updates. Half an hour.
+pub const MAX_CHECK_TICKS: u32 = 30 * 60 * 60;
+const MAX_NAME_LENGTH: usize = 16;
+/// How long the start screen is played through to see where a run can start from, in updates. A minute.
+const BOB_TICKS: u32 = 60 * 60;
+/// How close a run's starting velocity has to be to one the crab has while it bobs.
+const BOB_VELOCITY_MARGIN: f32 = 0.001;
+/// How long the game waits on the server before giving up.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where to send runs and who they're from. Leaving it out of the settings keeps the game offline.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OnlineSettings {
+    /// The leaderboard server, like `localhost:7878`.
+    pub server: String,
+    pub name: String,
+}
+
+/// A finished run sent to the server. The recording is played through to check the score.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Submission {
+    pub name: String,
+    pub score: i64,
+    pub recording: Recording,
+}
+
+/// A run on the leaderboard.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub score: i64,
+    pub seed: u32,
+    /// The name of the course generator.
+    pub course: String,
+    /// How many updates the run lasted.
+    pub ticks: u32,
+    /// Whether any assists that change how the game plays were on. Assisted runs are listed separately.
+    #[serde(default)]
+    pub assisted: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Submit(Submission),
+    /// The best `count` runs, with or without assists.
+    Top {
+        count: usize,
+        #[serde(default)]
+        assisted: bool,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The run was listed, at this place counting from 1.
+    Accepted { place: usize },
+    Rejected { reason: String },
+    Top { entries: Vec<Entry> },
+}
+
+/// Sends one request and waits for the answer.
+pub fn request(server: &str, request: &Request) -> Result<Response, String> {
+    let address = if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{}:{}", server, DEFAULT_PORT)
+    };
+    let socket = address
+        .to_socket_addrs()
+        .map_err(|e| format!("Couldn't find {}: {}", address, e))?
+        .next()
+        .ok_or_else(|| format!("Couldn't find {}", address))?;
+    let mut stream =
+        TcpStream::connect_timeout(&socket, TIMEOUT).map_err(|e| format!("Couldn't reach {}: {}", address, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let line = serde_json::to_string(request).map_err(|e| e.to_string())? + "\n";
+    stream.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer).map_err(|e| e.to_string())?;
+    serde_json::from_str(&answer).map_err(|e| format!("Couldn't read the answer from {}: {}", address, e))
+}
+
+/// The best runs on the server, either the assisted ones or the ones without assists.
+pub fn top(server: &str, count: usize, assisted: bool) -> Result<Vec<Entry>, String> {
+    match request(server, &Request::Top { count, assisted })? {
+        Response::Top { entries } => Ok(entries),
+        Response::Rejected { reason } => Err(reason),
+        other => Err(format!("Expected the top runs, got {:?}", other)),
+    }
+}
+
+/// Sends a finished run to the server without holding up the game, and prints how it went.
+pub fn submit_in_background(settings: &OnlineSettings, score: i64, recording: Recording) {
+    let server = settings.server.clone();
+    let submission = Submission {
+        name: settings.name.clone(),
+        score,
+        recording,
+    };
+    std::thread::spawn(move || match request(&server, &Request::Submit(submission)) {
+        Ok(Response::Accepted { place }) => println!("Your run is number {} on the leaderboard", place),
+        Ok(Response::Rejected { reason }) => println!("The leaderboard didn't take your run: {}", reason),
+        Ok(other) => println!("The leaderboard sent something odd back: {:?}", other),
+        Err(e) => println!("Couldn't send your run to the leaderboard: {}", e),
+    });
+}
+
+/// Plays the submitted run through to check it really scored what it says.
+/// Returns the leaderboard entry for it if it did.
+pub fn check(atlas: &Atlas, submission: &Submission) -> Result<Entry, String> {
+    let name = submission.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("Names need to be 1 to {} letters long", MAX_NAME_LENGTH));
+    }
+    let recording = &submission.recording;
+    if let CourseSettings::Replay { .. } = recording.course {
+        return Err("Runs on a course from a file can't be checked".to_string());
+    }
+    if recording.assists != recording.assists.clamped() {
+        return Err("The run's assists are out of bounds".to_string());
+    }
+    if recording.flaps.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("The run's flaps are out of order".to_string());
+    }
+    if !could_start_from(atlas, recording) {
+        return Err("The crab can't start a run from there".to_string());
+    }
+    if recording.flaps.last().map_or(false, |&tick| tick >= MAX_CHECK_TICKS) {
+        return Err("The run is too long to check".to_string());
+    }
+
+    let bot = Bot::Replay(recording.clone());
+    let mut sim = Simulation::for_bot(atlas, &recording.course, recording.seed, &bot);
+    sim.play(&bot, MAX_CHECK_TICKS);
+    if sim.cause.is_none() {
+        return Err("The run doesn't end".to_string());
+    }
+    if sim.score != submission.score {
+        return Err(format!("The run scores {}, not {}", sim.score, submission.score));
+    }
+    Ok(Entry {
+        name: name.to_string(),
+        score: sim.score,
+        seed: recording.seed,
+        course: recording.course.name().to_string(),
+        ticks: sim.ticks,
+        assisted: recording.assists.any(),
+    })
+}
+
+/// Whether the crab bobbing on the start screen ever goes as fast as the run starts,
+/// and whether the run starts between the highest and lowest the crab bobs to.
+fn could_start_from(atlas: &Atlas, recording: &Recording) -> bool {
+    let mut player = create_player(atlas);
+    player.assists = recording.assists.clone();
+    let mut velocities = vec![];
+    let (mut highest, mut lowest) = (player.position.y, player.position.y);
+    for _ in 0..BOB_TICKS {
+        velocities.push(player.physics.velocity.y);
+        highest = highest.min(player.position.y);
+        lowest = lowest.max(player.position.y);
+        player.update(false, &PlayState::StartScreen);
+    }
+    recording.start_y >= highest
+        && recording.start_y <= lowest
+        && velocities.iter().any(|velocity| (velocity - recording.start_velocity).abs() < BOB_VELOCITY_MARGIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::ATLAS_FILE;
+    use crate::crab::GROUND;
+    use std::path::Path;
+
+    /// How long the crab bobs on the start screen before the run starts.
+    const BOBBED_TICKS: u32 = 40;
+    /// How long the bot flies before it lets the crab fall.
+    const BOT_TICKS: u32 = 600;
+
+    fn atlas() -> Atlas {
+        Atlas::parse_atlas_json(&Path::new("resources").join(ATLAS_FILE))
+    }
+
+    /// A run recorded the way the game records one: started from the start screen
+    /// and flown by the simple bot until it gives up.
+    fn genuine_run(atlas: &Atlas) -> Submission {
+        let mut player = create_player(atlas);
+        for _ in 0..BOBBED_TICKS {
+            player.update(false, &PlayState::StartScreen);
+        }
+        let mut recording = Recording {
+            seed: 3,
+            start_y: player.position.y,
+            start_velocity: player.physics.velocity.y,
+            ..Recording::default()
+        };
+        let mut sim = Simulation::new(
+            atlas,
+            &recording.course,
+            recording.seed,
+            recording.start_y,
+            recording.start_velocity,
+            &recording.assists,
+        );
+        loop {
+            let flap = sim.ticks < BOT_TICKS && Bot::Simple.flap(&sim);
+            if flap {
+                recording.flaps.push(sim.ticks);
+            }
+            if !sim.step(flap) {
+                break;
+            }
+        }
+        Submission {
+            name: "crab".to_string(),
+            score: sim.score,
+            recording,
+        }
+    }
+
+    #[test]
+    fn genuine_runs_are_listed() {
+        let atlas = atlas();
+        let submission = genuine_run(&atlas);
+        let entry = check(&atlas, &submission).unwrap();
+        assert_eq!(entry.score, submission.score);
+        assert_eq!(entry.seed, 3);
+        assert!(!entry.assisted);
+    }
+
+    #[test]
+    fn inflated_scores_are_rejected() {
+        let atlas = atlas();
+        let mut submission = genuine_run(&atlas);
+        submission.score += 1;
+        assert!(check(&atlas, &submission).is_err());
+    }
+
+    #[test]
+    fn runs_start_where_the_crab_bobs() {
+        let atlas = atlas();
+        let genuine = genuine_run(&atlas);
+        let started_low = GROUND - 1.0;
+        let started_fast = -10.0;
+
+        let mut submission = genuine.clone();
+        submission.recording.start_y = started_low;
+        assert_eq!(check(&atlas, &submission).unwrap_err(), "The crab can't start a run from there");
+        let mut submission = genuine;
+        submission.recording.start_velocity = started_fast;
+        assert_eq!(check(&atlas, &submission).unwrap_err(), "The crab can't start a run from there");
+    }
+
+    #[test]
+    fn flaps_are_in_order() {
+        let atlas = atlas();
+        let genuine = genuine_run(&atlas);
+        assert!(genuine.recording.flaps.len() >= 2);
+
+        let mut submission = genuine.clone();
+        submission.recording.flaps.swap(0, 1);
+        assert_eq!(check(&atlas, &submission).unwrap_err(), "The run's flaps are out of order");
+        let mut submission = genuine;
+        let first = submission.recording.flaps[0];
+        submission.recording.flaps.insert(0, first);
+        assert_eq!(check(&atlas, &submission).unwrap_err(), "The run's flaps are out of order");
+    }
+
+    #[test]
+    fn requests_and_responses_round_trip() {
+        let requests = vec![
+            Request::Submit(genuine_run(&atlas())),
+            Request::Top { count: 10, assisted: true },
+        ];
+        for request in requests {
+            let json = serde_json::to_string(&request).unwrap();
+            let read: Request = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&read).unwrap(), json);
+        }
+        // Older games don't say whether they want the assisted runs.
+        match serde_json::from_str(r#"{"type":"top","count":5}"#).unwrap() {
+            Request::Top { count, assisted } => assert_eq!((count, assisted), (5, false)),
+            other => panic!("Read the wrong request: {:?}", other),
+        }
+
+        let entry = Entry {
+            name: "crab".to_string(),
+            score: 12,
+            seed: 3,
+            course: "perlin".to_string(),
+            ticks: 900,
+            assisted: false,
+        };
+        let responses = vec![
+            Response::Accepted { place: 1 },
+            Response::Rejected { reason: "The run doesn't end".to_string() },
+            Response::Top { entries: vec![entry] },
+        ];
+        for response in responses {
+            let json = serde_json::to_string(&response).unwrap();
+            let read: Response = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&read).unwrap(), json);
+        }
+    }
+}
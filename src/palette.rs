@@ -0,0 +1,230 @@
+//! Other colours for the game, for colour blind players or anyone who wants more contrast.
+//! The colours are baked into the texture atlas, so a palette makes a recoloured copy of it
+//! when the game starts rather than changing anything at draw time.
+
+use crate::crab::PlayerEntity;
+use crate::window::{SCALE, WORLD_HEIGHT};
+use crate::world::World;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawParam, FilterMode, Image};
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+type Matrix = [[f32; 3]; 3];
+
+const IDENTITY: Matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+/// How colours look without red cones, green cones and blue cones.
+const PROTANOPIA: Matrix = [[0.567, 0.433, 0.0], [0.558, 0.442, 0.0], [0.0, 0.242, 0.758]];
+const DEUTERANOPIA: Matrix = [[0.625, 0.375, 0.0], [0.7, 0.3, 0.0], [0.0, 0.3, 0.7]];
+const TRITANOPIA: Matrix = [[0.95, 0.05, 0.0], [0.0, 0.433, 0.567], [0.0, 0.475, 0.525]];
+/// Where the colour that can't be seen is moved to: into green and blue for red-green
+/// colour blindness, and into red and green for blue-yellow.
+const RED_GREEN_SHIFT: Matrix = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+const BLUE_YELLOW_SHIFT: Matrix = [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]];
+/// How far high contrast pushes colours away from grey.
+const HIGH_CONTRAST: f32 = 1.6;
+const HIGH_CONTRAST_SKY: (u8, u8, u8) = (10, 10, 40);
+const HIGH_CONTRAST_OUTLINE: (u8, u8, u8) = (255, 230, 0);
+/// How thick outlines are, in world units.
+const OUTLINE_WIDTH: f32 = 0.5;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteName {
+    Normal,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+impl Default for PaletteName {
+    fn default() -> Self {
+        PaletteName::Normal
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PaletteSettings {
+    pub name: PaletteName,
+    /// Draws a line around the crab and the pipes. High contrast always has them.
+    pub outlines: bool,
+}
+
+/// How every colour in the game is changed.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub name: PaletteName,
+    matrix: Matrix,
+    contrast: f32,
+    /// The sky, if the palette has its own instead of recolouring the usual one.
+    sky: Option<Color>,
+    pub outline: Option<Color>,
+}
+
+impl Palette {
+    pub fn new(settings: &PaletteSettings) -> Self {
+        let (matrix, contrast, sky) = match settings.name {
+            PaletteName::Normal => (IDENTITY, 1.0, None),
+            PaletteName::Deuteranopia => (daltonize(&DEUTERANOPIA, &RED_GREEN_SHIFT), 1.0, None),
+            PaletteName::Protanopia => (daltonize(&PROTANOPIA, &RED_GREEN_SHIFT), 1.0, None),
+            PaletteName::Tritanopia => (daltonize(&TRITANOPIA, &BLUE_YELLOW_SHIFT), 1.0, None),
+            PaletteName::HighContrast => (IDENTITY, HIGH_CONTRAST, Some(Color::from(HIGH_CONTRAST_SKY))),
+        };
+        let outline = match settings.name {
+            PaletteName::HighContrast => Some(Color::from(HIGH_CONTRAST_OUTLINE)),
+            _ if settings.outlines => Some(graphics::BLACK),
+            _ => None,
+        };
+        Self {
+            name: settings.name,
+            matrix,
+            contrast,
+            sky,
+            outline,
+        }
+    }
+
+    /// The colour in this palette.
+    pub fn colour(&self, colour: Color) -> Color {
+        let rgb = [colour.r, colour.g, colour.b];
+        let mut out = [0.0; 3];
+        for (i, row) in self.matrix.iter().enumerate() {
+            let mixed: f32 = row.iter().zip(&rgb).map(|(m, c)| m * c).sum();
+            out[i] = ((mixed - 0.5) * self.contrast + 0.5).max(0.0).min(1.0);
+        }
+        Color::new(out[0], out[1], out[2], colour.a)
+    }
+
+    /// The colour to clear the screen with, given the sky the game wants.
+    pub fn sky(&self, sky: Color) -> Color {
+        self.sky.unwrap_or_else(|| self.colour(sky))
+    }
+
+    /// Swaps the batch's image for a recoloured copy of the atlas image at `path`.
+    /// If that can't be done the usual colours are kept.
+    pub fn recolour(&self, ctx: &mut Context, batch: &mut SpriteBatch, path: &str) {
+        if self.name == PaletteName::Normal {
+            return;
+        }
+        match self.atlas_image(ctx, path, |colour| self.colour(colour)) {
+            Ok(image) => {
+                batch.set_image(image);
+            }
+            Err(e) => println!("Couldn't recolour the sprites, keeping the usual colours: {}", e),
+        }
+    }
+
+    /// The atlas image at `path` in this palette's colours.
+    pub fn image(&self, ctx: &mut Context, path: &str) -> GameResult<Image> {
+        if self.name == PaletteName::Normal {
+            let mut image = Image::new(ctx, path)?;
+            image.set_filter(FilterMode::Nearest);
+            return Ok(image);
+        }
+        self.atlas_image(ctx, path, |colour| self.colour(colour))
+    }
+
+    /// The outlines of the sprites in the atlas image at `path`, if this palette has them.
+    pub fn outline_image(&self, ctx: &mut Context, path: &str) -> Option<Image> {
+        let outline = self.outline?;
+        match self.atlas_image(ctx, path, |colour| Color::new(outline.r, outline.g, outline.b, colour.a)) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                println!("Couldn't make the outlines: {}", e);
+                None
+            }
+        }
+    }
+
+    /// A copy of the atlas image at `path` with every pixel changed by `recolour`.
+    fn atlas_image(&self, ctx: &mut Context, path: &str, recolour: impl Fn(Color) -> Color) -> GameResult<Image> {
+        let atlas = Image::new(ctx, path)?;
+        let mut pixels = atlas.to_rgba8(ctx)?;
+        for pixel in pixels.chunks_mut(4) {
+            let colour = recolour(Color::from_rgba(pixel[0], pixel[1], pixel[2], pixel[3]));
+            let (r, g, b, a) = colour.to_rgba();
+            pixel.copy_from_slice(&[r, g, b, a]);
+        }
+        let mut image = Image::from_rgba8(ctx, atlas.width(), atlas.height(), &pixels)?;
+        image.set_filter(FilterMode::Nearest);
+        Ok(image)
+    }
+}
+
+/// Lines around the crab and the pipes, drawn as the same sprites in one colour
+/// a little way off in each direction, behind the real ones.
+pub struct Outlines {
+    batch: SpriteBatch,
+}
+
+impl Outlines {
+    pub fn new(image: Image) -> Self {
+        Self {
+            batch: SpriteBatch::new(image),
+        }
+    }
+
+    /// Draws the outlines of another atlas image's sprites from now on.
+    pub fn set_image(&mut self, image: Image) {
+        self.batch.set_image(image);
+    }
+
+    fn offsets() -> [(f32, f32); 4] {
+        [(-OUTLINE_WIDTH, 0.0), (OUTLINE_WIDTH, 0.0), (0.0, -OUTLINE_WIDTH), (0.0, OUTLINE_WIDTH)]
+    }
+
+    pub fn add_crab(&mut self, crab: &PlayerEntity) {
+        let param = crab.draw_param();
+        for (x, y) in Outlines::offsets().iter() {
+            self.batch.add(param.dest(Point2::new(param.dest.x + x, param.dest.y + y)));
+        }
+    }
+
+    pub fn add_pipes(&mut self, world: &World) {
+        for entity in world.entities() {
+            if let (Some(transform), Some(pipe_pair)) = (&world.transforms[entity], &world.pipe_pairs[entity]) {
+                for (x, y) in Outlines::offsets().iter() {
+                    let position = Point2::new(transform.position.x + x, transform.position.y + y);
+                    pipe_pair.draw(position, WORLD_HEIGHT, &mut self.batch, graphics::WHITE);
+                }
+            }
+        }
+    }
+
+    /// Draws everything added since last time, where the world is drawn.
+    pub fn draw(&mut self, ctx: &mut Context, offset: Point2<f32>) -> GameResult {
+        let param = DrawParam::new().dest(offset).scale(Vector2::new(SCALE, SCALE));
+        graphics::draw(ctx, &self.batch, param)?;
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// Daltonizing: adds back the difference between a colour and how it looks to a colour blind eye,
+/// moved into colours that eye can see. As one matrix, that's `I + shift * (I - simulated)`.
+fn daltonize(simulated: &Matrix, shift: &Matrix) -> Matrix {
+    let mut lost = IDENTITY;
+    for (i, row) in lost.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell -= simulated[i][j];
+        }
+    }
+    let mut matrix = multiply(shift, &lost);
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[i] += 1.0;
+    }
+    matrix
+}
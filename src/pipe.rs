@@ -1,166 +1,153 @@
-use crate::entity::{PipeEntity, Scroll};
+use crate::components::{PipePair, Recycler, Scorer, Transform, Velocity};
+use crate::world::{Entity, World};
 use crate::Sprite;
-use noise::NoiseFn;
-use noise::Perlin;
-use std::collections::VecDeque;
+use ggez::nalgebra::Vector2;
+use crate::course::{self, CourseGenerator};
+use crate::settings::CourseSettings;
+use serde::{Deserialize, Serialize};
 
 const NUM_PIPES: usize = 4;
-const SEGMENTS: usize = 4;
-/// Count total segments. The pipe lengths and tops.
 
 const PIPE_SPEED: f32 = 1.0;
 /// Distance between pipes relative to their width.
 const SPACE_MULTIPLIER: f32 = 1.5;
 
-const VERTICAL_GAP: f32 = 57.0;
+pub const VERTICAL_GAP: f32 = 57.0;
 pub const VERTICAL_GAP_DEVIANCE: f32 = 0.6;
+/// The seed used for the course unless another one is asked for.
+pub const DEFAULT_SEED: u32 = 0;
 
-#[derive(Debug)]
+/// Saved as the settings and the gaps handed out so far.
+/// Loading makes the generator again and runs it forward past those gaps.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "CourseState", into = "CourseState")]
 pub struct PipeTracker {
-    pipes_seen: usize,
-    top: VecDeque<f32>,
-    time: f32,
-    random_fn: Perlin,
+    generator: Box<dyn CourseGenerator>,
+    settings: CourseSettings,
+    pub seed: u32,
+    /// How far apart the pipes are, once they have been spawned.
+    pub spacing: Option<f32>,
+    /// Every gap handed out so far, so the course can be replayed.
+    pub history: Vec<f32>,
+    /// How tall the gaps are.
+    pub gap_height: f32,
 }
 
 impl PipeTracker {
-    pub fn new() -> Self {
+    /// The same seed, settings and gap height always make the same course.
+    pub fn new(seed: u32, settings: &CourseSettings, gap_height: f32) -> Self {
         PipeTracker {
-            pipes_seen: 1,
-            top: VecDeque::new(),
-            time: 0.0,
-            random_fn: Perlin::new(),
+            generator: course::create_generator(settings, seed, gap_height),
+            settings: settings.clone(),
+            seed,
+            spacing: None,
+            history: vec![],
+            gap_height,
         }
     }
 
-    fn get_pipe_top(&mut self) -> f32 {
-        self.pipes_seen += 1;
-        let noise = self.random_fn.get([self.time as f64, self.time as f64]) as f32 + 1.0;
-        (VERTICAL_GAP + 5.0) + noise * ((600.0 / 4.0) - (VERTICAL_GAP * 2.0))
-    }
-
-    fn init_get_pipe_top(&mut self) -> f32 {
-        self.time += VERTICAL_GAP_DEVIANCE;
-        let result = self.get_pipe_top();
-        self.pipes_seen = 0;
-        self.top.push_back(result);
-        result
+    /// Where the middle of the next pipe's gap goes.
+    /// Whatever the generator comes up with is kept reachable from the gap before.
+    pub fn next_gap_centre(&mut self) -> f32 {
+        let gap = self.generator.next_gap();
+        let gap = course::keep_reachable(self.history.last().cloned(), gap, self.spacing, self.gap_height);
+        self.history.push(gap);
+        gap
     }
+}
 
-    // Returns the direction pipe has to move.
-    pub fn get_pipe_difference(&mut self) -> f32 {
-        // need to go back by the number of integers of other pipes.
+/// What gets saved of a `PipeTracker`.
+#[derive(Serialize, Deserialize)]
+struct CourseState {
+    seed: u32,
+    settings: CourseSettings,
+    spacing: Option<f32>,
+    history: Vec<f32>,
+    #[serde(default = "default_gap_height")]
+    gap_height: f32,
+}
 
-        let last_pos = self.top.front().expect("Pipe wasn't placed!").clone();
-        let now_pos = self.get_pipe_top();
+fn default_gap_height() -> f32 {
+    VERTICAL_GAP
+}
 
-        if self.pipes_seen == 10 {
-            self.top.pop_front();
-            self.top.push_back(now_pos);
-            self.pipes_seen = 0;
-            self.time += VERTICAL_GAP_DEVIANCE;
+impl From<PipeTracker> for CourseState {
+    fn from(tracker: PipeTracker) -> Self {
+        Self {
+            seed: tracker.seed,
+            settings: tracker.settings,
+            spacing: tracker.spacing,
+            history: tracker.history,
+            gap_height: tracker.gap_height,
         }
-        now_pos - last_pos
     }
 }
 
-fn create_pipe_bottom(
-    sprite_base: Sprite,
-    sprite_top: Sprite,
-    x: f32,
-    top: f32,
-    total_dist: f32,
-) -> Vec<Box<PipeEntity>> {
-    let top_height = sprite_top.height;
-
-    let pipe_tip = create_pipe_tip(sprite_top, x, top, total_dist);
-    let segments = SEGMENTS;
-    let mut p = (0..segments)
-        .into_iter()
-        .map(|i| make_pipe_body(&sprite_base, x, top, total_dist, top_height, 1.0 *(i as f32)))
-        .collect::<Vec<Box<PipeEntity>>>();
-
-    p.push(Box::new(pipe_tip));
-
-    p
+impl From<CourseState> for PipeTracker {
+    fn from(state: CourseState) -> Self {
+        let mut tracker = PipeTracker::new(state.seed, &state.settings, state.gap_height);
+        for _ in 0..state.history.len() {
+            tracker.generator.next_gap();
+        }
+        tracker.spacing = state.spacing;
+        tracker.history = state.history;
+        tracker
+    }
 }
 
-pub fn create_pipes(
+pub fn spawn_pipes(
+    world: &mut World,
     sprite_base: Sprite,
     sprite_top: Sprite,
     pipe_tracker: &mut PipeTracker,
     x: f32,
-) -> Vec<Box<PipeEntity>> {
+) -> Vec<Entity> {
     let number_of_pipes = NUM_PIPES;
-    let width = sprite_top.width;
-    let space_width = width * SPACE_MULTIPLIER;
-    let total_dist = (width + space_width) * (number_of_pipes as f32);
+    let spacing = pipe_spacing(sprite_top.width);
+    let total_dist = spacing * (number_of_pipes as f32);
+    pipe_tracker.spacing = Some(spacing);
 
-    let gap = VERTICAL_GAP;
     (0..number_of_pipes)
         .into_iter()
-        .flat_map(|i| {
-            let top = pipe_tracker.init_get_pipe_top();
-            let pipe_x = x + (space_width + width) * (i as f32);
-            let mut bottom = create_pipe_bottom(
-                sprite_base.clone(),
-                sprite_top.clone(),
-                pipe_x,
-                top,
-                total_dist,
-            );
-            bottom.extend(create_pipe_top(
-                sprite_base.clone(),
-                sprite_top.clone(),
-                pipe_x,
-                top - gap,
-                total_dist,
-            ));
-
-            bottom
+        .map(|i| {
+            let pipe_x = x + spacing * (i as f32);
+            let pipe_pair = PipePair::new(pipe_tracker.gap_height, sprite_base.clone(), sprite_top.clone());
+            spawn_pipe_pair(world, pipe_pair, pipe_x, pipe_tracker.next_gap_centre(), total_dist)
         })
         .collect()
 }
 
-fn create_pipe_top(
-    sprite_base: Sprite,
-    sprite_top: Sprite,
-    x: f32,
-    top: f32,
-    total_dist: f32,
-) -> Vec<Box<PipeEntity>> {
-    use crate::entity::ScoringPipe;
-    let top_height = -1.0 * sprite_top.height;
-    let mut sprite_top = sprite_top;
-    sprite_top.scale.y = -1.0;
-
-    let mut pipe_tip = create_pipe_tip(sprite_top, x, top, total_dist);
-
-    pipe_tip.scoring_pipe = ScoringPipe::ReadyToScore;
-
-    let segments = SEGMENTS;
-    let mut p = (0..segments)
-        .into_iter()
-        .map(|i|
-            make_pipe_body(&sprite_base, x, top, total_dist, top_height, -1.0 *(i as f32))
-       )
-        .collect::<Vec<Box<PipeEntity>>>();
-
-    p.push(Box::new(pipe_tip));
-    p
+/// How far apart the left edges of neighbouring pipes are.
+pub fn pipe_spacing(width: f32) -> f32 {
+    width + width * SPACE_MULTIPLIER
 }
 
 pub fn pipe_velocity() -> f32 {
     -PIPE_SPEED
 }
 
-fn make_pipe_body(sprite_base: &Sprite, x: f32, top: f32, total_dist: f32, top_height: f32, i: f32) -> Box<PipeEntity> {
-    let top = top + top_height + (sprite_base.height * i);
-    let mut pipe_body = PipeEntity::new_pipe(sprite_base.clone(), x, top, total_dist);
-
-    Box::new(pipe_body)
+/// A pipe pair moves towards the crab, kills it, scores once it's passed
+/// and comes around again with a new gap.
+fn spawn_pipe_pair(world: &mut World, pipe_pair: PipePair, x: f32, gap_centre: f32, scroll: f32) -> Entity {
+    world
+        .spawn()
+        .with_transform(Transform::new(x, gap_centre))
+        .with_velocity(Velocity(Vector2::new(pipe_velocity(), 0.0)))
+        .with_collider(pipe_pair.collider())
+        .with_scorer(Scorer::ReadyToScore)
+        .with_recycler(Recycler { jump_distance: scroll })
+        .with_pipe_pair(pipe_pair)
+        .build()
 }
 
-fn create_pipe_tip(sprite_top: Sprite, x: f32, top: f32, scroll : f32) -> PipeEntity {
-    PipeEntity::new_pipe(sprite_top, x, top, scroll)
+/// A pipe pair for a hand made level. It only comes once, so it doesn't come around again.
+pub fn spawn_level_pipe_pair(world: &mut World, pipe_pair: PipePair, x: f32, gap_centre: f32) -> Entity {
+    world
+        .spawn()
+        .with_transform(Transform::new(x, gap_centre))
+        .with_velocity(Velocity(Vector2::new(pipe_velocity(), 0.0)))
+        .with_collider(pipe_pair.collider())
+        .with_scorer(Scorer::ReadyToScore)
+        .with_pipe_pair(pipe_pair)
+        .build()
 }
@@ -0,0 +1,106 @@
+use crate::snapshot::{RewindBuffer, Snapshot};
+use crate::window::{SCALE, SCREEN_WIDTH};
+use ggez::graphics;
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{Context, GameResult};
+use std::collections::VecDeque;
+
+/// How far the course moves between points of a trail.
+const TRAIL_STEP: f32 = 2.0;
+/// How many earlier attempts are drawn.
+const MAX_GHOSTS: usize = 5;
+const GHOST_WIDTH: f32 = 1.0;
+
+/// Everything practice mode keeps track of on top of a normal run.
+/// The course comes from the same seed every attempt, so the ghost trails line up with the pipes.
+#[derive(Default)]
+pub struct Practice {
+    pub rewind: RewindBuffer,
+    /// The run as it was when the crab last got past a pipe.
+    last_pipe: Option<Snapshot>,
+    /// Where the crab goes back to when it dies.
+    pub checkpoint: Option<Snapshot>,
+    /// Where the crab has been this attempt, as how far along the course it was and its height.
+    trail: Vec<(f32, f32)>,
+    /// The trails of earlier attempts, newest last.
+    ghosts: VecDeque<Vec<(f32, f32)>>,
+}
+
+impl Practice {
+    pub fn new() -> Self {
+        Practice::default()
+    }
+
+    /// Forgets everything about the course, for when a different one is being played.
+    pub fn forget(&mut self) {
+        *self = Practice::new();
+    }
+
+    /// Adds to the trail of this attempt.
+    pub fn record(&mut self, distance: f32, y: f32) {
+        let moved = self.trail.last().map_or(true, |(last, _)| distance - last >= TRAIL_STEP);
+        if moved {
+            self.trail.push((distance, y));
+        }
+    }
+
+    pub fn passed_pipe(&mut self, snapshot: Snapshot) {
+        self.last_pipe = Some(snapshot);
+    }
+
+    /// Makes the last pipe passed the checkpoint. Returns false if no pipe has been passed yet.
+    pub fn set_checkpoint(&mut self) -> bool {
+        match &self.last_pipe {
+            Some(snapshot) => {
+                self.checkpoint = Some(snapshot.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Turns this attempt's trail into a ghost.
+    pub fn end_attempt(&mut self) {
+        let trail = std::mem::replace(&mut self.trail, vec![]);
+        if trail.len() > 1 {
+            self.ghosts.push_back(trail);
+        }
+        if self.ghosts.len() > MAX_GHOSTS {
+            self.ghosts.pop_front();
+        }
+    }
+
+    /// Called when the run goes back to `distance`, so the trail doesn't run on past it.
+    pub fn went_back(&mut self, distance: f32) {
+        self.trail.retain(|(d, _)| *d <= distance);
+        if self.last_pipe.as_ref().map_or(false, |s| s.distance > distance) {
+            self.last_pipe = None;
+        }
+    }
+
+    /// Draws the earlier attempts as faint lines, older ones fainter.
+    /// `distance` is how far along the course the run is, and `crab_x` is where the crab is on screen.
+    pub fn draw_ghosts(&self, ctx: &mut Context, distance: f32, crab_x: f32) -> GameResult {
+        let screen_width = SCREEN_WIDTH / SCALE;
+        let mut builder = graphics::MeshBuilder::new();
+        let mut lines = 0;
+        for (age, ghost) in self.ghosts.iter().rev().enumerate() {
+            let points: Vec<Point2<f32>> = ghost
+                .iter()
+                .map(|(d, y)| Point2::new(crab_x + d - distance, *y))
+                .filter(|point| point.x >= 0.0 && point.x <= screen_width)
+                .collect();
+            if points.len() < 2 {
+                continue;
+            }
+            let alpha = 0.5 / (age + 1) as f32;
+            builder.line(&points, GHOST_WIDTH, graphics::Color::new(1.0, 1.0, 1.0, alpha))?;
+            lines += 1;
+        }
+        if lines == 0 {
+            return Ok(());
+        }
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new().scale(Vector2::new(SCALE, SCALE)))
+    }
+}
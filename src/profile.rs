@@ -0,0 +1,294 @@
+//! Player profiles. Each one is a folder of its own inside the save folder, holding its settings,
+//! stats, best runs, achievements and pearls, so several people can share the game.
+//! A profile can be exported to a single file and imported again, on this computer or another.
+
+use crate::settings::SETTINGS_FILE;
+use crate::snapshot::SUSPENDED_RUN;
+use crate::storage::{self, SAVE_DIR};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// The profile the saves from before there were profiles are moved into.
+pub const DEFAULT_PROFILE: &str = "default";
+/// The version of the profile format. Bump it and add a step to `MIGRATIONS` when the format changes.
+pub const PROFILE_VERSION: u32 = 1;
+const PROFILES_DIR: &str = "profiles";
+/// Which version of the format the profile's files are in, kept alongside them.
+const INFO_FILE: &str = "profile.json";
+/// The profile played last, kept in the profiles folder.
+const LAST_PROFILE_FILE: &str = "last_profile.txt";
+pub const MAX_NAME_LENGTH: usize = 16;
+
+/// A profile's files by name, with what's in them.
+type Files = BTreeMap<String, String>;
+
+/// Each step brings a profile's files from one version to the next: `MIGRATIONS[0]` takes version 0 to 1.
+const MIGRATIONS: [fn(&mut Files); PROFILE_VERSION as usize] = [from_shared_saves];
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ProfileInfo {
+    version: u32,
+}
+
+/// A whole profile in one file.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProfileExport {
+    pub version: u32,
+    pub name: String,
+    files: Files,
+}
+
+/// Version 0 is the save folder from before there were profiles.
+/// A suspended run is left behind, as there's no telling whose it was.
+fn from_shared_saves(files: &mut Files) {
+    files.remove(SUSPENDED_RUN);
+}
+
+/// Brings files in the given version of the format up to this one.
+fn migrate(files: &mut Files, version: u32) -> Result<(), String> {
+    if version > PROFILE_VERSION {
+        return Err(format!(
+            "The profile is version {}, made by a newer version of the game than this one ({})",
+            version, PROFILE_VERSION
+        ));
+    }
+    for step in &MIGRATIONS[version as usize..] {
+        step(files);
+    }
+    Ok(())
+}
+
+fn profiles_dir() -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(PROFILES_DIR)
+}
+
+/// The folder a profile's files are kept in.
+pub fn dir(name: &str) -> PathBuf {
+    profiles_dir().join(name)
+}
+
+/// The profile's own settings file, if it has one.
+pub fn settings_file(name: &str) -> Option<PathBuf> {
+    Some(dir(name).join(SETTINGS_FILE)).filter(|file| file.exists())
+}
+
+/// Names can't be empty or too long, and only have letters, numbers, spaces, `-` and `_`
+/// so they make good folder names.
+pub fn check_name(name: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_';
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        Err(format!("Profile names need to be 1 to {} letters long", MAX_NAME_LENGTH))
+    } else if name.trim() != name || !name.chars().all(allowed) {
+        Err(format!("{} can only have letters, numbers, spaces, - and _ in it", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// The names of every profile, in order.
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = match std::fs::read_dir(profiles_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+}
+
+/// The profile played last, or the default one.
+pub fn last() -> String {
+    std::fs::read_to_string(profiles_dir().join(LAST_PROFILE_FILE))
+        .map(|name| name.trim().to_string())
+        .ok()
+        .filter(|name| dir(name).is_dir())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Starts using a profile, making it if it doesn't exist yet. A new profile gets a copy of `settings`.
+/// A profile saved by an older version of the game is brought up to date first.
+pub fn open(name: &str, settings: &Path) -> Result<(), String> {
+    check_name(name)?;
+    let dir = dir(name);
+    if !dir.is_dir() {
+        let mut files = Files::new();
+        if let Ok(text) = std::fs::read_to_string(settings) {
+            files.insert(SETTINGS_FILE.to_string(), text);
+        }
+        write_files(&dir, &files)?;
+    } else {
+        let version = read_info(&dir)?.version;
+        if version != PROFILE_VERSION {
+            let mut files = read_files(&dir)?;
+            let before: Vec<String> = files.keys().cloned().collect();
+            migrate(&mut files, version)?;
+            for gone in before.iter().filter(|name| !files.contains_key(*name)) {
+                std::fs::remove_file(dir.join(gone)).map_err(|e| format!("Couldn't remove {}: {}", gone, e))?;
+            }
+            write_files(&dir, &files)?;
+            println!("Brought profile {} up to date from version {}", name, version);
+        }
+    }
+    if let Err(e) = std::fs::write(profiles_dir().join(LAST_PROFILE_FILE), name) {
+        println!("Couldn't remember the profile played last: {}", e);
+    }
+    storage::use_profile_dir(dir);
+    Ok(())
+}
+
+/// Moves the saves from before there were profiles into the default profile, the first time the game runs with them.
+pub fn adopt_shared_saves() {
+    if profiles_dir().exists() {
+        return;
+    }
+    let shared = PathBuf::from(SAVE_DIR);
+    let mut files = match read_files(&shared) {
+        Ok(files) if !files.is_empty() => files,
+        _ => return,
+    };
+    let names: Vec<String> = files.keys().cloned().collect();
+    let moved = migrate(&mut files, 0).and_then(|_| write_files(&dir(DEFAULT_PROFILE), &files));
+    match moved {
+        Ok(()) => {
+            for name in &names {
+                if let Err(e) = std::fs::remove_file(shared.join(name)) {
+                    println!("Couldn't remove {} after moving it into the default profile: {}", name, e);
+                }
+            }
+            println!("Moved your saves into the profile {}", DEFAULT_PROFILE);
+        }
+        Err(e) => println!("Couldn't move your saves into the profile {}: {}", DEFAULT_PROFILE, e),
+    }
+}
+
+/// Writes every file of a profile into one file.
+pub fn export(name: &str, file: &Path) -> Result<(), String> {
+    let dir = dir(name);
+    if !dir.is_dir() {
+        return Err(format!("There's no profile called {}", name));
+    }
+    let export = ProfileExport {
+        version: read_info(&dir)?.version,
+        name: name.to_string(),
+        files: read_files(&dir)?,
+    };
+    let text = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+    std::fs::write(file, text).map_err(|e| format!("Couldn't write {}: {}", file.display(), e))
+}
+
+/// Makes a profile from an exported one, called `name` or whatever it was called before.
+/// Never replaces a profile that's already here. Returns the new profile's name.
+pub fn import(file: &Path, name: Option<String>) -> Result<String, String> {
+    let text = std::fs::read_to_string(file).map_err(|e| format!("Couldn't read {}: {}", file.display(), e))?;
+    let mut export: ProfileExport =
+        serde_json::from_str(&text).map_err(|e| format!("{} isn't an exported profile: {}", file.display(), e))?;
+    let name = name.unwrap_or(export.name);
+    check_name(&name)?;
+    if dir(&name).exists() {
+        return Err(format!("There's already a profile called {}", name));
+    }
+    // Exported files are only ever a name, never a path, so they can't end up outside the profile.
+    if let Some(bad) = export.files.keys().find(|file| Path::new(file).file_name() != Some(OsStr::new(file))) {
+        return Err(format!("The profile has a file called {}, which isn't allowed", bad));
+    }
+    export.files.remove(INFO_FILE);
+    migrate(&mut export.files, export.version)?;
+    write_files(&dir(&name), &export.files)?;
+    Ok(name)
+}
+
+fn read_info(dir: &Path) -> Result<ProfileInfo, String> {
+    let text = std::fs::read_to_string(dir.join(INFO_FILE))
+        .map_err(|e| format!("Couldn't read the profile in {}: {}", dir.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Couldn't read the profile in {}: {}", dir.display(), e))
+}
+
+/// Every file in the folder, leaving out the profile's info and any folders.
+fn read_files(dir: &Path) -> Result<Files, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Couldn't read {}: {}", dir.display(), e))?;
+    let mut files = Files::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if !entry.path().is_file() || name == INFO_FILE {
+            continue;
+        }
+        let text = std::fs::read_to_string(entry.path()).map_err(|e| format!("Couldn't read {}: {}", name, e))?;
+        files.insert(name, text);
+    }
+    Ok(files)
+}
+
+/// Writes the files into the folder, along with the profile's info in this version of the format.
+fn write_files(dir: &Path, files: &Files) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Couldn't make {}: {}", dir.display(), e))?;
+    for (name, text) in files {
+        std::fs::write(dir.join(name), text).map_err(|e| format!("Couldn't write {}: {}", name, e))?;
+    }
+    let info = ProfileInfo {
+        version: PROFILE_VERSION,
+    };
+    let text = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(INFO_FILE), text).map_err(|e| format!("Couldn't write {}: {}", INFO_FILE, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A name no one plays as, so the tests never find or leave a profile of that name.
+    const UNUSED_NAME: &str = "profile tests";
+
+    fn files(names: &[&str]) -> Files {
+        names.iter().map(|name| (name.to_string(), "{}".to_string())).collect()
+    }
+
+    /// Exports a profile with these files to a file of its own and imports it again.
+    fn import_export(test: &str, version: u32, files: Files) -> Result<String, String> {
+        let export = ProfileExport {
+            version,
+            name: UNUSED_NAME.to_string(),
+            files,
+        };
+        let file = std::env::temp_dir().join(format!("crab_profile_{}.json", test));
+        std::fs::write(&file, serde_json::to_string(&export).unwrap()).unwrap();
+        let imported = import(&file, None);
+        std::fs::remove_file(&file).unwrap();
+        assert!(!dir(UNUSED_NAME).exists());
+        imported
+    }
+
+    #[test]
+    fn newer_profiles_are_refused() {
+        let mut files = files(&[SETTINGS_FILE]);
+        assert!(migrate(&mut files, PROFILE_VERSION + 1).is_err());
+        let error = import_export("newer", PROFILE_VERSION + 1, files).unwrap_err();
+        assert!(error.contains("newer version of the game"), "{}", error);
+    }
+
+    #[test]
+    fn imported_files_cant_be_paths() {
+        for bad in &["../settings.json", "saves/stats.json", "/tmp/stats.json", "..", ""] {
+            let error = import_export("paths", PROFILE_VERSION, files(&[SETTINGS_FILE, bad])).unwrap_err();
+            assert!(error.ends_with("which isn't allowed"), "{}: {}", bad, error);
+        }
+    }
+
+    #[test]
+    fn the_suspended_run_is_left_behind_from_version_0() {
+        let mut files = files(&[SETTINGS_FILE, SUSPENDED_RUN]);
+        migrate(&mut files, PROFILE_VERSION).unwrap();
+        assert!(files.contains_key(SUSPENDED_RUN));
+
+        migrate(&mut files, 0).unwrap();
+        assert!(!files.contains_key(SUSPENDED_RUN));
+        assert!(files.contains_key(SETTINGS_FILE));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::cli::Options;
+use crate::create_batch_sprite;
+use crate::game_over;
+use crate::game_state::{GameState, DEFAULT_BACKGROUND};
+use crate::profile::{self, MAX_NAME_LENGTH};
+use ggez::event::{self, EventHandler, KeyCode, KeyMods, MouseButton};
+use ggez::graphics;
+use ggez::{Context, GameResult};
+
+/// How many profiles the list shows at once.
+const SHOWN_PROFILES: usize = 8;
+
+/// The screen the game opens on, asking who's playing.
+struct ProfileMenu {
+    names: Vec<String>,
+    selected: usize,
+    /// The name of a new profile, while it's being typed.
+    typing: Option<String>,
+    /// The N that starts a new name also comes through as a letter, which shouldn't end up in it.
+    skip_letter: bool,
+    error: Option<String>,
+}
+
+impl ProfileMenu {
+    fn new() -> Self {
+        let names = profile::list();
+        let last = profile::last();
+        Self {
+            selected: names.iter().position(|name| *name == last).unwrap_or(0),
+            typing: if names.is_empty() { Some(String::new()) } else { None },
+            names,
+            skip_letter: false,
+            error: None,
+        }
+    }
+
+    /// The profile to play, once one has been picked.
+    fn key(&mut self, ctx: &mut Context, keycode: KeyCode) -> Option<String> {
+        if let Some(name) = &mut self.typing {
+            match keycode {
+                KeyCode::Return => return Some(name.clone()),
+                KeyCode::Back => {
+                    name.pop();
+                }
+                KeyCode::Escape if !self.names.is_empty() => self.typing = None,
+                KeyCode::Escape => event::quit(ctx),
+                _ => {}
+            }
+            return None;
+        }
+        match keycode {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.names.len().saturating_sub(1)),
+            KeyCode::Return | KeyCode::Space => return self.names.get(self.selected).cloned(),
+            KeyCode::N => {
+                self.typing = Some(String::new());
+                self.skip_letter = true;
+            }
+            KeyCode::Escape => event::quit(ctx),
+            _ => {}
+        }
+        None
+    }
+
+    fn letter(&mut self, character: char) {
+        if std::mem::replace(&mut self.skip_letter, false) {
+            return;
+        }
+        if let Some(name) = &mut self.typing {
+            if !character.is_control() && name.chars().count() < MAX_NAME_LENGTH {
+                name.push(character);
+            }
+        }
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, graphics::Color::from(DEFAULT_BACKGROUND));
+        let mut lines = vec!["WHO'S PLAYING?".to_string()];
+        match &self.typing {
+            Some(name) => {
+                lines.push(format!("New profile: {}_", name));
+                lines.push("Return: play   Escape: back".to_string());
+            }
+            None => {
+                let first = self.selected.saturating_sub(SHOWN_PROFILES - 1);
+                for (i, name) in self.names.iter().enumerate().skip(first).take(SHOWN_PROFILES) {
+                    let marker = if i == self.selected { ">" } else { " " };
+                    lines.push(format!("{} {}", marker, name));
+                }
+                lines.push("Return: play   N: new profile".to_string());
+            }
+        }
+        if let Some(error) = &self.error {
+            lines.push(error.clone());
+        }
+        game_over::draw_results(ctx, &lines)?;
+        graphics::present(ctx)
+    }
+}
+
+/// Asks who's playing, then plays as them.
+pub struct Launcher {
+    options: Options,
+    menu: ProfileMenu,
+    game: Option<GameState>,
+}
+
+impl Launcher {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            options: options.clone(),
+            menu: ProfileMenu::new(),
+            game: None,
+        }
+    }
+
+    fn start(&mut self, ctx: &mut Context, name: String) {
+        if let Err(e) = profile::open(&name, &self.options.default_settings_file()) {
+            self.menu.error = Some(e);
+            return;
+        }
+        self.options.profile = Some(name);
+        let batch = create_batch_sprite(ctx);
+        let mut game = GameState::new(ctx, batch, &self.options, None);
+        game.sound_player.begin();
+        self.game = Some(game);
+    }
+}
+
+impl EventHandler for Launcher {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        match &mut self.game {
+            Some(game) => game.update(ctx),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        match &mut self.game {
+            Some(game) => game.draw(ctx),
+            None => self.menu.draw(ctx),
+        }
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        if let Some(game) = &mut self.game {
+            game.key_down_event(ctx, keycode, keymods, repeat);
+        } else if let Some(name) = self.menu.key(ctx, keycode) {
+            self.start(ctx, name);
+        }
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+        if self.game.is_none() {
+            self.menu.letter(character);
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if let Some(game) = &mut self.game {
+            game.mouse_button_down_event(ctx, button, x, y);
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if let Some(game) = &mut self.game {
+            game.mouse_button_up_event(ctx, button, x, y);
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, dx: f32, dy: f32) {
+        if let Some(game) = &mut self.game {
+            game.mouse_motion_event(ctx, x, y, dx, dy);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        if let Some(game) = &mut self.game {
+            game.mouse_wheel_event(ctx, x, y);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::course::FLAP_INTERVAL;
+use crate::crab::{self, CRAB_X, GROUND, SCREEN_TOP};
+use crate::pipe::{pipe_velocity, PipeTracker};
+use crate::settings::CourseSettings;
+
+/// How many updates since the last flap are told apart. The crab is falling flat out long before this.
+const MAX_TICKS_SINCE_FLAP: usize = 64;
+/// How many of the worst courses a scan reports.
+const WORST_CASES: usize = 5;
+
+/// The pipes of a course, as the crab meets them.
+pub struct Course {
+    /// The middle of each pipe's gap, in order.
+    pub gaps: Vec<f32>,
+    pub gap_height: f32,
+    /// How far apart the left edges of the pipes are.
+    pub spacing: f32,
+    pub pipe_width: f32,
+    /// Where the first pipe starts.
+    pub first_pipe_x: f32,
+}
+
+/// The first pipe of a course the crab can't get through.
+#[derive(Debug, Clone)]
+pub struct Blocked {
+    pub pipe: usize,
+    pub gap: f32,
+    /// The gap of the pipe before it, if there is one.
+    pub previous_gap: Option<f32>,
+}
+
+impl Blocked {
+    /// How far above the gap before it the blocked gap is.
+    pub fn climb(&self) -> f32 {
+        self.previous_gap.map_or(0.0, |previous| previous - self.gap)
+    }
+}
+
+/// The velocity the crab has each update after a flap, by how many updates it's been.
+fn velocities() -> Vec<f32> {
+    std::iter::successors(Some(crab::step_velocity(0.0, true)), |v| Some(crab::step_velocity(*v, false)))
+        .take(MAX_TICKS_SINCE_FLAP + 1)
+        .collect()
+}
+
+/// Where the crab can be, as stretches from highest to lowest, top first and none of them overlapping.
+/// Kept as separate stretches rather than one from the highest to the lowest,
+/// so heights in between that the crab can't get to don't count as somewhere it can be.
+type Heights = Vec<(f32, f32)>;
+
+/// Cuts the heights down to `allowed`, drops the stretches left empty and merges the ones that overlap.
+fn tidy(heights: &mut Heights, allowed: (f32, f32)) {
+    let mut stretches = heights
+        .iter()
+        // The crab can't go above the top of the screen.
+        .map(|(low, high)| (low.max(SCREEN_TOP).max(allowed.0), high.max(SCREEN_TOP).min(allowed.1)))
+        .filter(|(low, high)| low <= high)
+        .collect::<Heights>();
+    stretches.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    heights.clear();
+    for (low, high) in stretches {
+        match heights.last_mut() {
+            Some(last) if low <= last.1 => last.1 = last.1.max(high),
+            _ => heights.push((low, high)),
+        }
+    }
+}
+
+/// Plays the course with every way of flapping at once, flapping at most every `FLAP_INTERVAL` updates.
+/// For each number of updates since the last flap it keeps every stretch of heights the crab can be at,
+/// and stops when there's nowhere left the crab can be.
+/// Only the pipes are checked, not the things that turn up between them.
+pub fn check(course: &Course, crab_width: f32, crab_height: f32) -> Result<(), Blocked> {
+    let speed = -pipe_velocity();
+    let velocities = velocities();
+    // The crab starts at rest anywhere on screen, free to flap.
+    let mut envelope: Vec<Heights> = vec![vec![]; MAX_TICKS_SINCE_FLAP + 1];
+    envelope[FLAP_INTERVAL as usize] = vec![(SCREEN_TOP, GROUND)];
+
+    let last_pipe_x = course.first_pipe_x + course.spacing * course.gaps.len().saturating_sub(1) as f32;
+    let ticks = ((last_pipe_x + course.pipe_width - CRAB_X) / speed).ceil().max(0.0) as u32;
+    for tick in 1..=ticks {
+        let mut next: Vec<Heights> = vec![vec![]; MAX_TICKS_SINCE_FLAP + 1];
+        for (since_flap, heights) in envelope.iter().enumerate() {
+            let later = (since_flap + 1).min(MAX_TICKS_SINCE_FLAP);
+            let v = velocities[later];
+            next[later].extend(heights.iter().map(|(low, high)| (low + v, high + v)));
+            if since_flap + 1 >= FLAP_INTERVAL as usize {
+                let v = velocities[0];
+                next[0].extend(heights.iter().map(|(low, high)| (low + v, high + v)));
+            }
+        }
+
+        // Where the crab has to be once the pipes have moved: above the ground, and in the gap of any pipe it's level with.
+        let mut allowed = (SCREEN_TOP, GROUND);
+        let mut blocking = None;
+        // Only the pipes around the crab can be level with it.
+        let travelled = speed * tick as f32 + CRAB_X - course.first_pipe_x;
+        let nearest = (travelled / course.spacing).floor().max(0.0) as usize;
+        let nearby = nearest.saturating_sub(1)..(nearest + 2).min(course.gaps.len());
+        for (pipe, gap) in course.gaps.iter().enumerate().skip(nearby.start).take(nearby.len()) {
+            let x = course.first_pipe_x + course.spacing * pipe as f32 - speed * tick as f32;
+            if x < CRAB_X + crab_width && x + course.pipe_width > CRAB_X {
+                allowed.0 = allowed.0.max(gap - course.gap_height / 2.0);
+                allowed.1 = allowed.1.min(gap + course.gap_height / 2.0 - crab_height);
+                blocking = Some(pipe);
+            }
+        }
+
+        let mut alive = false;
+        for heights in next.iter_mut() {
+            tidy(heights, allowed);
+            alive |= !heights.is_empty();
+        }
+        if !alive {
+            let pipe = blocking.unwrap_or(0);
+            return Err(Blocked {
+                pipe,
+                gap: course.gaps[pipe],
+                previous_gap: if pipe > 0 { Some(course.gaps[pipe - 1]) } else { None },
+            });
+        }
+        envelope = next;
+    }
+    Ok(())
+}
+
+/// What scanning a lot of seeds found.
+pub struct ScanReport {
+    pub seeds: u32,
+    pub pipes: usize,
+    /// Every seed whose course can't be finished, and where.
+    pub unfair: Vec<(u32, Blocked)>,
+}
+
+impl ScanReport {
+    pub fn unfair_fraction(&self) -> f32 {
+        self.unfair.len() as f32 / self.seeds.max(1) as f32
+    }
+
+    /// The unfair courses with the biggest climbs.
+    pub fn worst_cases(&self) -> Vec<&(u32, Blocked)> {
+        let mut worst = self.unfair.iter().collect::<Vec<&(u32, Blocked)>>();
+        worst.sort_by(|a, b| b.1.climb().partial_cmp(&a.1.climb()).unwrap_or(std::cmp::Ordering::Equal));
+        worst.truncate(WORST_CASES);
+        worst
+    }
+}
+
+impl std::fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Scanned {} seeds, {} pipes each: {} unfair ({:.1}%)",
+            self.seeds,
+            self.pipes,
+            self.unfair.len(),
+            self.unfair_fraction() * 100.0
+        )?;
+        for (seed, blocked) in self.worst_cases() {
+            writeln!(
+                f,
+                "  seed {}: pipe {} with its gap at {:.1} can't be reached from {:.1} (a climb of {:.1})",
+                seed,
+                blocked.pipe,
+                blocked.gap,
+                blocked.previous_gap.unwrap_or(blocked.gap),
+                blocked.climb()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates the first `pipes` gaps of the course for seeds 0 to `seeds` and checks each one.
+/// `pipe_width` and the crab's size come from the sprites, and `gap_height` from the assists.
+pub fn scan(
+    settings: &CourseSettings,
+    gap_height: f32,
+    seeds: u32,
+    pipes: usize,
+    pipe_width: f32,
+    crab_width: f32,
+    crab_height: f32,
+) -> ScanReport {
+    let spacing = crate::pipe::pipe_spacing(pipe_width);
+    let mut unfair = vec![];
+    for seed in 0..seeds {
+        let mut tracker = PipeTracker::new(seed, settings, gap_height);
+        tracker.spacing = Some(spacing);
+        let course = Course {
+            gaps: (0..pipes).map(|_| tracker.next_gap_centre()).collect(),
+            gap_height: tracker.gap_height,
+            spacing,
+            pipe_width,
+            first_pipe_x: crate::game_state::FIRST_PIPE_X,
+        };
+        if let Err(blocked) = check(&course, crab_width, crab_height) {
+            unfair.push((seed, blocked));
+        }
+    }
+    ScanReport { seeds, pipes, unfair }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::course::max_climb;
+    use crate::pipe::{pipe_spacing, VERTICAL_GAP};
+
+    /// The size of the pipe and crab sprites in the atlas.
+    const PIPE_WIDTH: f32 = 32.0;
+    const CRAB_WIDTH: f32 = 16.0;
+    const CRAB_HEIGHT: f32 = 14.0;
+
+    fn course(gaps: Vec<f32>, gap_height: f32, spacing: f32) -> Course {
+        Course {
+            gaps,
+            gap_height,
+            spacing,
+            pipe_width: PIPE_WIDTH,
+            first_pipe_x: crate::game_state::FIRST_PIPE_X,
+        }
+    }
+
+    #[test]
+    fn level_gaps_are_fair() {
+        let course = course(vec![80.0; 20], VERTICAL_GAP, pipe_spacing(PIPE_WIDTH));
+        assert!(check(&course, CRAB_WIDTH, CRAB_HEIGHT).is_ok());
+    }
+
+    #[test]
+    fn gentle_waves_are_fair() {
+        let gaps = (0..20).map(|pipe| 80.0 + 20.0 * (pipe as f32 / 3.0).sin()).collect();
+        let course = course(gaps, VERTICAL_GAP, pipe_spacing(PIPE_WIDTH));
+        assert!(check(&course, CRAB_WIDTH, CRAB_HEIGHT).is_ok());
+    }
+
+    #[test]
+    fn climbing_further_than_the_crab_can_is_blocked() {
+        // Pipes close together, so the crab can't climb far between them.
+        let spacing = 48.0;
+        let low_gap = 125.0;
+        // Even from the top of the low gap to the bottom of the high one, it's further than the crab can climb.
+        let high_gap = low_gap - max_climb(spacing) - VERTICAL_GAP - 10.0;
+        let course = course(vec![low_gap, low_gap, high_gap, high_gap], VERTICAL_GAP, spacing);
+
+        let blocked = check(&course, CRAB_WIDTH, CRAB_HEIGHT).unwrap_err();
+        assert_eq!(blocked.pipe, 2);
+        assert_eq!(blocked.previous_gap, Some(low_gap));
+        assert!(blocked.climb() > max_climb(spacing));
+    }
+
+    #[test]
+    fn tidy_keeps_stretches_apart() {
+        let mut heights = vec![(50.0, 60.0), (0.0, 10.0), (5.0, 20.0), (55.0, 70.0)];
+        tidy(&mut heights, (SCREEN_TOP, GROUND));
+        assert_eq!(heights, vec![(0.0, 20.0), (50.0, 70.0)]);
+
+        tidy(&mut heights, (15.0, 52.0));
+        assert_eq!(heights, vec![(15.0, 20.0), (50.0, 52.0)]);
+        tidy(&mut heights, (25.0, 45.0));
+        assert!(heights.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+use crate::assists::Assists;
+use crate::settings::CourseSettings;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where the last endless run is saved, so it can be replayed.
+pub const LAST_RUN: &str = "last_run.json";
+
+/// Everything needed to play a run again: its course and when the flap key was down.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Recording {
+    pub seed: u32,
+    pub course: CourseSettings,
+    /// Where the crab was when the run started, since it bounces around on the start screen.
+    pub start_y: f32,
+    pub start_velocity: f32,
+    /// The updates, counting from the start of the run, that the flap key was down for.
+    pub flaps: Vec<u32>,
+    pub assists: Assists,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Whether the flap key was down on this update of the run.
+    pub fn flap_held(&self, tick: u32) -> bool {
+        self.flaps.binary_search(&tick).is_ok()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::assists::Assists;
+use crate::daylight::DaylightSettings;
+use crate::input::Controls;
+use crate::online::OnlineSettings;
+use crate::palette::PaletteSettings;
+use crate::theme::ThemeSettings;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The settings file in the resources folder, unless another one is asked for.
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EffectSettings {
+    /// How far the screen shakes on death. 0.0 turns it off.
+    pub screen_shake: f32,
+    /// How bright the flash is on death. 0.0 turns it off.
+    pub flash: f32,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            screen_shake: 1.0,
+            flash: 0.8,
+        }
+    }
+}
+
+/// Which generator lays out the endless course.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "generator", rename_all = "snake_case")]
+pub enum CourseSettings {
+    /// Gaps that wander up and down smoothly. The original course.
+    Perlin,
+    /// Random gaps, each at most `max_delta` from the one before.
+    Uniform { max_delta: f32 },
+    /// Gaps that go up and down in a wave `period` pipes long.
+    Sine { amplitude: f32, period: f32 },
+    /// The gaps in a JSON list, like the one saved after every run, in the profile's folder.
+    Replay { file: String },
+}
+
+impl CourseSettings {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CourseSettings::Perlin => "perlin",
+            CourseSettings::Uniform { .. } => "uniform",
+            CourseSettings::Sine { .. } => "sine",
+            CourseSettings::Replay { .. } => "replay",
+        }
+    }
+}
+
+impl Default for CourseSettings {
+    fn default() -> Self {
+        CourseSettings::Perlin
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub effects: EffectSettings,
+    pub course: CourseSettings,
+    /// Which keys each player flaps with.
+    pub controls: Controls,
+    /// Ways of making the game easier.
+    pub assists: Assists,
+    /// The colours the game is drawn in.
+    pub palette: PaletteSettings,
+    /// Which theme runs start in, and how often the themes take turns.
+    pub theme: ThemeSettings,
+    /// How the light changes from day to night over a run.
+    pub daylight: DaylightSettings,
+    /// The online leaderboard endless runs are sent to, if there is one.
+    pub online: Option<OnlineSettings>,
+}
+
+impl Settings {
+    /// Loads the settings file, falling back to the defaults if it doesn't exist.
+    /// Panics if the file exists but can't be parsed.
+    pub fn load(settings_file: &Path) -> Self {
+        use std::fs::File;
+        use std::io::BufReader;
+
+        match File::open(settings_file) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))
+                .expect("Couldn't parse the settings file"),
+            Err(_) => Settings::default(),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Saving and loading for the ggez and nalgebra types used in the world,
+//! which don't know how to save themselves.
+//! Use them with `#[serde(with = "crate::shapes::point")]` and so on.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A `Point2<f32>` as `[x, y]`.
+pub mod point {
+    use super::*;
+    use ggez::nalgebra::Point2;
+
+    pub fn serialize<S: Serializer>(point: &Point2<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        (point.x, point.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point2<f32>, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Point2::new(x, y))
+    }
+}
+
+/// A `Vector2<f32>` as `[x, y]`.
+pub mod vector {
+    use super::*;
+    use ggez::nalgebra::Vector2;
+
+    pub fn serialize<S: Serializer>(vector: &Vector2<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        (vector.x, vector.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vector2<f32>, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Vector2::new(x, y))
+    }
+}
+
+/// A `Rect` as `[x, y, w, h]`.
+pub mod rect {
+    use super::*;
+    use ggez::graphics::Rect;
+
+    pub fn serialize<S: Serializer>(rect: &Rect, serializer: S) -> Result<S::Ok, S::Error> {
+        (rect.x, rect.y, rect.w, rect.h).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rect, D::Error> {
+        let (x, y, w, h) = <(f32, f32, f32, f32)>::deserialize(deserializer)?;
+        Ok(Rect::new(x, y, w, h))
+    }
+}
+
+/// A list of `Rect`s, each as `[x, y, w, h]`.
+pub mod rects {
+    use super::*;
+    use ggez::graphics::Rect;
+
+    pub fn serialize<S: Serializer>(rects: &[Rect], serializer: S) -> Result<S::Ok, S::Error> {
+        let rects: Vec<(f32, f32, f32, f32)> = rects.iter().map(|r| (r.x, r.y, r.w, r.h)).collect();
+        rects.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Rect>, D::Error> {
+        let rects = <Vec<(f32, f32, f32, f32)>>::deserialize(deserializer)?;
+        Ok(rects.into_iter().map(|(x, y, w, h)| Rect::new(x, y, w, h)).collect())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::assists::Assists;
+use crate::atlas::Atlas;
+use crate::collectibles::{Collectible, PowerUps};
+use crate::crab::{create_player, PlayerEntity};
+use crate::entity::PlayState;
+use crate::events::DeathCause;
+use crate::game_state::GameState;
+use crate::obstacles::{ObstacleGenerator, ObstacleSprites};
+use crate::pipe::PipeTracker;
+use crate::recording::Recording;
+use crate::settings::CourseSettings;
+use crate::step::Run;
+use crate::window::WORLD_HEIGHT;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where the crab starts when nothing says otherwise, about where it bounces on the start screen.
+pub const START_Y: f32 = 75.0;
+/// How far below the middle of the gap the simple bot lets the crab fall before flapping.
+const SIMPLE_BOT_AIM: f32 = 6.0;
+
+/// A run of the endless course without a window, sound or a clock.
+/// Each step is one update of the game, played through the same `Run::step` as the game's own update.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Simulation {
+    pub world: World,
+    pub player: Box<PlayerEntity>,
+    pipe_tracker: PipeTracker,
+    obstacles: ObstacleGenerator,
+    power_ups: PowerUps,
+    pub score: i64,
+    pub pearls: u32,
+    pub flaps: u32,
+    /// How many updates the run has had.
+    pub ticks: u32,
+    /// What killed the crab, once something has.
+    pub cause: Option<DeathCause>,
+    /// How far the course has moved.
+    #[serde(default)]
+    pub distance: f32,
+    #[serde(default)]
+    pub assists: Assists,
+}
+
+impl Simulation {
+    pub fn new(
+        atlas: &Atlas,
+        course: &CourseSettings,
+        seed: u32,
+        start_y: f32,
+        start_velocity: f32,
+        assists: &Assists,
+    ) -> Self {
+        let mut pipe_tracker = PipeTracker::new(seed, course, assists.gap_height());
+        let mut player = create_player(atlas);
+        player.position.y = start_y;
+        player.physics.velocity.y = start_velocity;
+        player.assists = assists.clone();
+        Self {
+            world: GameState::create_world(atlas, &mut pipe_tracker),
+            player,
+            obstacles: ObstacleGenerator::new(ObstacleSprites::new(atlas), seed),
+            pipe_tracker,
+            power_ups: PowerUps::new(),
+            score: 0,
+            pearls: 0,
+            flaps: 0,
+            ticks: 0,
+            cause: None,
+            distance: 0.0,
+            assists: assists.clone(),
+        }
+    }
+
+    /// A run for the bot to play. A recorded run brings its own course, starting point and assists.
+    pub fn for_bot(atlas: &Atlas, course: &CourseSettings, seed: u32, bot: &Bot) -> Self {
+        match bot {
+            Bot::Replay(recording) => Simulation::new(
+                atlas,
+                &recording.course,
+                recording.seed,
+                recording.start_y,
+                recording.start_velocity,
+                &recording.assists,
+            ),
+            _ => Simulation::new(atlas, course, seed, START_Y, 0.0, &Assists::default()),
+        }
+    }
+
+    /// Moves the run on by one update. Returns false once the crab is dead.
+    pub fn step(&mut self, flap_held: bool) -> bool {
+        if self.cause.is_some() {
+            return false;
+        }
+        let run = Run {
+            world: &mut self.world,
+            player: &mut self.player,
+            pipe_tracker: &mut self.pipe_tracker,
+            obstacles: &mut self.obstacles,
+            power_ups: &mut self.power_ups,
+            assists: &self.assists,
+            distance: &mut self.distance,
+            score: self.score as i128,
+        };
+        let tick = run.step(flap_held, &PlayState::Play, 1.0);
+        if self.player.flapped {
+            self.flaps += 1;
+        }
+        self.score += tick.points as i64;
+        self.pearls += tick.collected.iter().filter(|c| **c == Collectible::Pearl).count() as u32;
+        self.cause = tick.death;
+
+        self.ticks += 1;
+        self.cause.is_none()
+    }
+
+    /// Lets the bot play until the crab dies or `max_ticks` updates have gone by.
+    pub fn play(&mut self, bot: &Bot, max_ticks: u32) {
+        while self.ticks < max_ticks {
+            let flap = bot.flap(self);
+            if !self.step(flap) {
+                break;
+            }
+        }
+    }
+
+    /// Saves the run, loads it again and lets the bot play both on for `ticks` updates.
+    /// They should end up exactly the same. If they don't, something isn't being saved.
+    pub fn check_snapshot(&self, bot: &Bot, ticks: u32) -> Result<(), String> {
+        let saved = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let mut loaded: Simulation = serde_json::from_str(&saved).map_err(|e| e.to_string())?;
+        let mut original = self.clone();
+        let end = self.ticks.saturating_add(ticks);
+        original.play(bot, end);
+        loaded.play(bot, end);
+
+        let original = serde_json::to_string(&original).map_err(|e| e.to_string())?;
+        let loaded = serde_json::to_string(&loaded).map_err(|e| e.to_string())?;
+        if original == loaded {
+            Ok(())
+        } else {
+            Err(format!("the run went differently after being saved at update {}", self.ticks))
+        }
+    }
+
+    /// The middle of the gap of the next pipe the crab has to get through.
+    pub fn next_gap_centre(&self) -> Option<f32> {
+        let crab_left = self.player.position.x;
+        self.world
+            .entities()
+            .filter_map(|entity| match (&self.world.transforms[entity], &self.world.pipe_pairs[entity]) {
+                (Some(transform), Some(pipe_pair)) => Some((transform.position, pipe_pair.width())),
+                _ => None,
+            })
+            .filter(|(position, width)| position.x + width > crab_left)
+            .min_by(|(a, _), (b, _)| a.x.partial_cmp(&b.x).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(position, _)| position.y)
+    }
+}
+
+/// Who decides when the crab flaps in a simulation.
+#[derive(Clone)]
+pub enum Bot {
+    /// Flaps whenever the crab falls below the middle of the next gap.
+    Simple,
+    /// Never flaps.
+    Idle,
+    /// Flaps when a recorded run did.
+    Replay(Recording),
+}
+
+impl Bot {
+    /// `simple`, `idle`, or the path of a recorded run.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "simple" => Ok(Bot::Simple),
+            "idle" => Ok(Bot::Idle),
+            file => Recording::load(Path::new(file)).map(Bot::Replay),
+        }
+    }
+
+    /// Whether the flap key is down this update.
+    pub fn flap(&self, sim: &Simulation) -> bool {
+        match self {
+            Bot::Simple => {
+                let target = sim.next_gap_centre().unwrap_or(WORLD_HEIGHT / 2.0);
+                let falling = sim.player.physics.velocity.y >= 0.0;
+                falling && sim.player.get_bounds().bottom() > target + SIMPLE_BOT_AIM
+            }
+            Bot::Idle => false,
+            Bot::Replay(recording) => recording.flap_held(sim.ticks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::ATLAS_FILE;
+
+    const SEEDS: u32 = 10;
+    /// How long the runs go before they're saved, and after.
+    const BEFORE_SAVE: u32 = 400;
+    const AFTER_SAVE: u32 = 600;
+
+    fn atlas() -> Atlas {
+        Atlas::parse_atlas_json(&Path::new("resources").join(ATLAS_FILE))
+    }
+
+    fn state(sim: &Simulation) -> String {
+        serde_json::to_string(sim).unwrap()
+    }
+
+    /// Saves the run, loads it again and plays both on. They should end up exactly the same.
+    fn check_saved_run(assists: &Assists) {
+        let atlas = atlas();
+        for seed in 0..SEEDS {
+            let mut original = Simulation::new(&atlas, &CourseSettings::Perlin, seed, START_Y, 0.0, assists);
+            original.play(&Bot::Simple, BEFORE_SAVE);
+            let mut loaded: Simulation = serde_json::from_str(&state(&original)).unwrap();
+            assert_eq!(state(&original), state(&loaded), "seed {}: the loaded run isn't the saved one", seed);
+
+            let end = original.ticks + AFTER_SAVE;
+            original.play(&Bot::Simple, end);
+            loaded.play(&Bot::Simple, end);
+            assert_eq!(state(&original), state(&loaded), "seed {}: the runs went differently after loading", seed);
+        }
+    }
+
+    #[test]
+    fn saved_runs_carry_on_the_same() {
+        check_saved_run(&Assists::default());
+    }
+
+    #[test]
+    fn saved_runs_that_never_end_carry_on_the_same() {
+        // The crab can't die, so every run gets saved partway through and played on for the whole time.
+        let assists = Assists {
+            invincible: true,
+            ..Assists::default()
+        };
+        check_saved_run(&assists);
+
+        let atlas = atlas();
+        let mut sim = Simulation::new(&atlas, &CourseSettings::Perlin, 0, START_Y, 0.0, &assists);
+        sim.play(&Bot::Idle, BEFORE_SAVE + AFTER_SAVE);
+        assert_eq!(sim.ticks, BEFORE_SAVE + AFTER_SAVE);
+    }
+}
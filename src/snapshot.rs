@@ -0,0 +1,166 @@
+use crate::collectibles::PowerUps;
+use crate::crab::PlayerEntity;
+use crate::entity::PlayState;
+use crate::obstacles::ObstacleGenerator;
+use crate::pipe::PipeTracker;
+use crate::recording::Recording;
+use crate::stats::RunStats;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Where a run is kept when the game is quit in the middle of it.
+pub const SUSPENDED_RUN: &str = "suspended_run.json";
+/// How far back the rewind key goes, in updates.
+const REWIND_TICKS: u32 = 180;
+/// How often a snapshot is taken to rewind to, in updates.
+const SNAPSHOT_INTERVAL: u32 = 10;
+/// How many snapshots are kept. Enough to rewind a few times in a row.
+const MAX_SNAPSHOTS: usize = 60;
+
+/// Everything about an endless run that decides how it plays out from here.
+/// Sound, screen effects and menus are left out.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub world: World,
+    pub player: Box<PlayerEntity>,
+    pub pipe_tracker: PipeTracker,
+    pub obstacles: ObstacleGenerator,
+    pub power_ups: PowerUps,
+    pub score: i64,
+    pub run_pearls: u64,
+    pub closest_clearance: f32,
+    pub on_ceiling: bool,
+    pub run_ticks: u32,
+    pub recording: Recording,
+    pub run: RunStats,
+    /// How far the course has moved.
+    #[serde(default)]
+    pub distance: f32,
+}
+
+/// The last few seconds of snapshots, for rewinding in practice mode.
+#[derive(Default)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl RewindBuffer {
+    pub fn new() -> Self {
+        RewindBuffer::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Whether a snapshot should be taken on this update of the run.
+    pub fn wants(run_ticks: u32) -> bool {
+        run_ticks % SNAPSHOT_INTERVAL == 0
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.snapshots.push_back(snapshot);
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// The snapshot from about three seconds before `run_ticks`, or the oldest one there is.
+    /// Everything after it is forgotten, so rewinding again goes further back.
+    /// Nothing once the crab has died, so a run that's over can't be brought back.
+    pub fn rewind(&mut self, state: &PlayState, run_ticks: u32) -> Option<Snapshot> {
+        if !state.is_playing() {
+            return None;
+        }
+        let target = run_ticks.saturating_sub(REWIND_TICKS);
+        while self.snapshots.len() > 1 && self.snapshots.back().map_or(false, |s| s.run_ticks > target) {
+            self.snapshots.pop_back();
+        }
+        self.snapshots.back().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::{Atlas, ATLAS_FILE};
+    use crate::crab::create_player;
+    use crate::obstacles::ObstacleSprites;
+    use crate::pipe::VERTICAL_GAP;
+    use crate::settings::CourseSettings;
+    use std::path::Path;
+
+    fn snapshot(atlas: &Atlas, run_ticks: u32) -> Snapshot {
+        Snapshot {
+            world: World::new(),
+            player: create_player(atlas),
+            pipe_tracker: PipeTracker::new(0, &CourseSettings::Perlin, VERTICAL_GAP),
+            obstacles: ObstacleGenerator::new(ObstacleSprites::new(atlas), 0),
+            power_ups: PowerUps::new(),
+            score: 0,
+            run_pearls: 0,
+            closest_clearance: std::f32::MAX,
+            on_ceiling: false,
+            run_ticks,
+            recording: Recording::default(),
+            run: RunStats {
+                seed: 0,
+                score: 0,
+                duration_secs: 0.0,
+                flaps: 0,
+                closest_clearance: None,
+                cause_of_death: None,
+                death_pipe: 0,
+                pearls: 0,
+                assisted: false,
+            },
+            distance: run_ticks as f32,
+        }
+    }
+
+    /// A buffer with a snapshot every time one is wanted, up to `run_ticks`.
+    fn filled(run_ticks: u32) -> RewindBuffer {
+        let atlas = Atlas::parse_atlas_json(&Path::new("resources").join(ATLAS_FILE));
+        let mut buffer = RewindBuffer::new();
+        for tick in (0..=run_ticks).filter(|tick| RewindBuffer::wants(*tick)) {
+            buffer.push(snapshot(&atlas, tick));
+        }
+        buffer
+    }
+
+    #[test]
+    fn rewinds_to_an_earlier_update() {
+        let mut buffer = filled(500);
+        let first = buffer.rewind(&PlayState::Play, 500).unwrap();
+        assert_eq!(first.run_ticks, 500 - REWIND_TICKS);
+        // Rewinding again from there goes further back.
+        let second = buffer.rewind(&PlayState::Play, first.run_ticks).unwrap();
+        assert_eq!(second.run_ticks, 500 - 2 * REWIND_TICKS);
+        // It can't go back past the start of the run.
+        assert_eq!(buffer.rewind(&PlayState::Play, second.run_ticks).unwrap().run_ticks, 0);
+        assert_eq!(buffer.rewind(&PlayState::Play, 0).unwrap().run_ticks, 0);
+    }
+
+    #[test]
+    fn forgets_the_oldest_snapshots() {
+        let mut buffer = filled(2000);
+        let oldest = 2000 - (MAX_SNAPSHOTS as u32 - 1) * SNAPSHOT_INTERVAL;
+        assert_eq!(buffer.rewind(&PlayState::Play, 0).unwrap().run_ticks, oldest);
+    }
+
+    #[test]
+    fn nothing_to_rewind_to() {
+        assert!(RewindBuffer::new().rewind(&PlayState::Play, 100).is_none());
+    }
+
+    #[test]
+    fn nothing_to_rewind_to_once_the_crab_has_died() {
+        let mut buffer = filled(500);
+        let dead = PlayState::Dead { time: std::time::Duration::from_secs(1) };
+        assert!(buffer.rewind(&dead, 500).is_none());
+        assert!(buffer.rewind(&PlayState::GameOver, 500).is_none());
+        // Nothing was forgotten on the way.
+        assert_eq!(buffer.rewind(&PlayState::Play, 500).unwrap().run_ticks, 500 - REWIND_TICKS);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::collectibles::Collectible;
+use crate::events::{DeathCause, GameEvent};
+use crate::storage;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const LIFETIME_FILE: &str = "lifetime_stats.json";
+const RUNS_CSV: &str = "runs.csv";
+const RUNS_JSONL: &str = "runs.jsonl";
+const CSV_HEADER: &str =
+    "seed,score,duration_secs,flaps,closest_clearance,cause_of_death,death_pipe,pearls,assisted";
+
+/// Everything recorded about a single run.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RunStats {
+    pub seed: u32,
+    pub score: i64,
+    pub duration_secs: f32,
+    pub flaps: u32,
+    /// The closest the crab came to a pipe it got past.
+    pub closest_clearance: Option<f32>,
+    pub cause_of_death: Option<DeathCause>,
+    /// Which pipe the crab was trying to get past when it died, counting from 0.
+    pub death_pipe: i64,
+    #[serde(default)]
+    pub pearls: u32,
+    /// Whether any assists that change how the game plays were on.
+    #[serde(default)]
+    pub assisted: bool,
+}
+
+impl RunStats {
+    fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            score: 0,
+            duration_secs: 0.0,
+            flaps: 0,
+            closest_clearance: None,
+            cause_of_death: None,
+            death_pipe: 0,
+            pearls: 0,
+            assisted: false,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{:.3},{},{},{},{},{},{}",
+            self.seed,
+            self.score,
+            self.duration_secs,
+            self.flaps,
+            self.closest_clearance.map_or(String::new(), |c| format!("{:.2}", c)),
+            self.cause_of_death.map_or("", |c| c.name()),
+            self.death_pipe,
+            self.pearls,
+            self.assisted,
+        )
+    }
+}
+
+/// Totals over every run ever played. This is what gets saved.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub runs: u32,
+    pub best_score: i64,
+    pub total_score: i64,
+    pub total_flaps: u32,
+    pub total_secs: f32,
+    /// How many times each thing has killed the crab, by name.
+    pub deaths: HashMap<String, u32>,
+}
+
+/// Records every run and keeps the lifetime totals.
+pub struct Stats {
+    pub run: RunStats,
+    pub lifetime: LifetimeStats,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            run: RunStats::new(0),
+            lifetime: storage::load(LIFETIME_FILE),
+        }
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted { seed } => self.run = RunStats::new(*seed),
+            GameEvent::Flapped => self.run.flaps += 1,
+            GameEvent::Scored(score) => self.run.score = *score as i64,
+            GameEvent::PipeCleared { clearance } => {
+                let closest = self.run.closest_clearance.map_or(*clearance, |c| c.min(*clearance));
+                self.run.closest_clearance = Some(closest);
+            }
+            GameEvent::Survived(time) => self.run.duration_secs = time.as_secs_f32(),
+            GameEvent::Died(cause) => {
+                self.run.cause_of_death = Some(*cause);
+                self.run.death_pipe = self.run.score;
+            }
+            GameEvent::Collected(Collectible::Pearl) => self.run.pearls += 1,
+            GameEvent::RunEnded(_) => self.finish_run(),
+            GameEvent::Collected(_) | GameEvent::HitCeiling => {}
+        }
+    }
+
+    /// Writes the run out to the CSV and JSON lines logs and adds it to the totals.
+    fn finish_run(&mut self) {
+        storage::append_line(RUNS_CSV, Some(CSV_HEADER), &self.run.to_csv());
+        match serde_json::to_string(&self.run) {
+            Ok(json) => storage::append_line(RUNS_JSONL, None, &json),
+            Err(e) => println!("Couldn't record run: {}", e),
+        }
+
+        let lifetime = &mut self.lifetime;
+        lifetime.runs += 1;
+        lifetime.best_score = lifetime.best_score.max(self.run.score);
+        lifetime.total_score += self.run.score;
+        lifetime.total_flaps += self.run.flaps;
+        lifetime.total_secs += self.run.duration_secs;
+        if let Some(cause) = self.run.cause_of_death {
+            *lifetime.deaths.entry(cause.name().to_string()).or_insert(0) += 1;
+        }
+        storage::save(LIFETIME_FILE, lifetime);
+    }
+
+    /// The best runs in the run log, best first. Lines that can't be read are skipped.
+    pub fn best_runs(count: usize) -> Vec<RunStats> {
+        let mut runs: Vec<RunStats> = storage::read_lines(RUNS_JSONL)
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        runs.sort_by(|a, b| b.score.cmp(&a.score));
+        runs.truncate(count);
+        runs
+    }
+
+    pub fn draw_lifetime(&self, ctx: &mut Context) -> GameResult {
+        let lifetime = &self.lifetime;
+        let runs = lifetime.runs.max(1) as f32;
+        let mut lines = vec![
+            format!("Runs played: {}", lifetime.runs),
+            format!("Best score: {}", lifetime.best_score),
+            format!("Average score: {:.1}", lifetime.total_score as f32 / runs),
+            format!("Total flaps: {}", lifetime.total_flaps),
+            format!("Time played: {:.0}s", lifetime.total_secs),
+            format!("Average run: {:.1}s", lifetime.total_secs / runs),
+        ];
+        for (cause, count) in &lifetime.deaths {
+            lines.push(format!("Killed by {}: {}", cause, count));
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            let text = Text::new(line.as_str());
+            graphics::draw(
+                ctx,
+                &text,
+                (Point2::new(40.0, 80.0 + 24.0 * i as f32), graphics::WHITE),
+            )?;
+        }
+        Ok(())
+    }
+}
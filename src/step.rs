@@ -0,0 +1,96 @@
+use crate::assists::Assists;
+use crate::collectibles::{Collectible, PowerUp, PowerUps};
+use crate::crab::{PlayerEntity, GROUND};
+use crate::entity::PlayState;
+use crate::events::DeathCause;
+use crate::obstacles::ObstacleGenerator;
+use crate::pipe::{pipe_velocity, PipeTracker};
+use crate::systems;
+use crate::world::World;
+
+/// The parts of a run that every update moves on.
+/// The game and `Simulation` both step their runs through here, so a simulated run plays out like one in the window.
+pub struct Run<'a> {
+    pub world: &'a mut World,
+    pub player: &'a mut PlayerEntity,
+    pub pipe_tracker: &'a mut PipeTracker,
+    pub obstacles: &'a mut ObstacleGenerator,
+    pub power_ups: &'a mut PowerUps,
+    pub assists: &'a Assists,
+    /// How far the course has moved.
+    pub distance: &'a mut f32,
+    /// The score before this update.
+    pub score: i128,
+}
+
+/// What happened in an update, for the game or the simulation to count up and react to.
+pub struct Tick {
+    /// The state the crab's update left the run in. Flapping on the start screen starts the run.
+    pub state: PlayState,
+    /// How fast the world moved.
+    pub speed: f32,
+    /// How many pipes were scored.
+    pub points: u32,
+    /// Everything picked up. Power ups have already been switched on.
+    pub collected: Vec<Collectible>,
+    /// What killed the crab, if anything did.
+    pub death: Option<DeathCause>,
+}
+
+impl<'a> Run<'a> {
+    /// Moves the run on by one update. `section_speed` is how fast the level's section goes, or 1.0 outside levels.
+    pub fn step(self, flap_held: bool, state: &PlayState, section_speed: f32) -> Tick {
+        let state = self.player.update(flap_held, state);
+        let playing = state.is_playing();
+        if playing {
+            self.power_ups.update();
+        }
+        self.player.size = self.power_ups.crab_size();
+        let speed = self.power_ups.world_speed() * section_speed * self.assists.speed;
+        systems::movement(self.world, &state, speed);
+        if playing {
+            *self.distance += -pipe_velocity() * speed;
+        }
+        systems::scripts(self.world, &state);
+        systems::animate(self.world);
+        for pipe in systems::recycle(self.world, self.pipe_tracker) {
+            self.obstacles.pipe_recycled(self.world, pipe, self.score, self.pipe_tracker.gap_height);
+        }
+
+        let mut tick = Tick {
+            state,
+            speed,
+            points: 0,
+            collected: vec![],
+            death: None,
+        };
+        if !playing {
+            return tick;
+        }
+        tick.points = systems::scoring(self.world, &tick.state);
+
+        let player_rect = self.player.get_bounds();
+        if self.power_ups.is_active(PowerUp::Magnet) {
+            systems::magnet(self.world, self.player.position);
+        }
+        tick.collected = systems::collect(self.world, &player_rect);
+        for collectible in &tick.collected {
+            if let Collectible::PowerUp(power_up) = collectible {
+                self.power_ups.activate(*power_up);
+            }
+        }
+
+        if let Some(cause) = systems::hit(self.world, &player_rect) {
+            if !self.assists.invincible && !self.power_ups.absorb_hit() {
+                tick.death = Some(cause);
+            }
+        } else if self.player.position.y > GROUND {
+            if self.assists.invincible {
+                self.player.bounce();
+            } else {
+                tick.death = Some(DeathCause::Ground);
+            }
+        }
+        tick
+    }
+}
@@ -0,0 +1,133 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Where everything that outlives a run is kept.
+pub const SAVE_DIR: &str = "saves";
+
+/// The folder of the profile being played, if one has been picked.
+/// Until then saves go straight into the save folder.
+static PROFILE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Keeps every save from now on in the profile's folder.
+pub fn use_profile_dir(dir: PathBuf) {
+    *PROFILE_DIR.lock().expect("The profile lock was poisoned") = Some(dir);
+}
+
+fn save_dir() -> PathBuf {
+    let profile = PROFILE_DIR.lock().expect("The profile lock was poisoned");
+    profile.clone().unwrap_or_else(|| PathBuf::from(SAVE_DIR))
+}
+
+fn save_path(name: &str) -> PathBuf {
+    save_dir().join(name)
+}
+
+/// Loads a save file, or the default if there isn't one yet.
+/// A broken save file is reported and replaced by the default rather than crashing the game.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let file = match File::open(save_path(name)) {
+        Ok(file) => file,
+        Err(_) => return T::default(),
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+        println!("Couldn't read save file {}: {}", name, e);
+        T::default()
+    })
+}
+
+/// Loads a save file, leaving it where it is. Returns None if there isn't one or it can't be read.
+/// An absolute path is read from wherever it points.
+pub fn read<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let text = std::fs::read_to_string(save_path(name)).ok()?;
+    serde_json::from_str(&text)
+        .map_err(|e| println!("Couldn't read save file {}: {}", name, e))
+        .ok()
+}
+
+/// Loads a save file and deletes it, so it's only ever used once.
+/// Returns None if there isn't one or it can't be read.
+pub fn take<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let path = save_path(name);
+    let text = std::fs::read_to_string(&path).ok()?;
+    if let Err(e) = std::fs::remove_file(&path) {
+        println!("Couldn't remove {}: {}", name, e);
+    }
+    serde_json::from_str(&text)
+        .map_err(|e| println!("Couldn't read save file {}: {}", name, e))
+        .ok()
+}
+
+/// Writes a save file. Failing to save is reported but never fatal.
+pub fn save<T: Serialize>(name: &str, value: &T) {
+    let result = std::fs::create_dir_all(save_dir())
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string_pretty(value).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(save_path(name), json).map_err(|e| e.to_string()));
+
+    if let Err(e) = result {
+        println!("Couldn't save {}: {}", name, e);
+    }
+}
+
+/// Reads every line of a save file, or nothing if there isn't one yet.
+pub fn read_lines(name: &str) -> Vec<String> {
+    match std::fs::read_to_string(save_path(name)) {
+        Ok(text) => text.lines().map(String::from).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Adds a line to the end of a save file, starting it with `header` if the file is new.
+/// A file that starts with a different header was written by an older version, so it's moved
+/// out of the way and a new one is started, rather than mixing up the columns.
+pub fn append_line(name: &str, header: Option<&str>, line: &str) {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let path = save_path(name);
+    if let Some(header) = header {
+        set_aside_if_outdated(name, header);
+    }
+    let is_new = !path.exists();
+    let result = std::fs::create_dir_all(save_dir())
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| {
+            if let (true, Some(header)) = (is_new, header) {
+                writeln!(file, "{}", header)?;
+            }
+            writeln!(file, "{}", line)
+        });
+
+    if let Err(e) = result {
+        println!("Couldn't write to {}: {}", name, e);
+    }
+}
+
+/// Renames the file to the first free `name.1`, `name.2`... if its first line isn't `header`.
+fn set_aside_if_outdated(name: &str, header: &str) {
+    let first_line = match std::fs::read_to_string(save_path(name)) {
+        Ok(text) => text.lines().next().unwrap_or("").to_string(),
+        Err(_) => return,
+    };
+    if first_line == header {
+        return;
+    }
+
+    let old_path = (1..)
+        .map(|n| save_path(&format!("{}.{}", name, n)))
+        .find(|path| !path.exists())
+        .expect("There's always a free number");
+    match std::fs::rename(save_path(name), &old_path) {
+        Ok(()) => println!(
+            "{} had different columns, so it was moved to {}",
+            name,
+            old_path.display()
+        ),
+        Err(e) => println!("Couldn't move the old {} out of the way: {}", name, e),
+    }
+}
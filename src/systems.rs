@@ -0,0 +1,260 @@
+use crate::collectibles::{Collectible, MAGNET_PULL, MAGNET_RADIUS};
+use crate::components::{Motion, Scorer};
+use crate::daylight::Daylight;
+use crate::entity::PlayState;
+use crate::events::DeathCause;
+use crate::pipe::PipeTracker;
+use crate::window::SCALE;
+use crate::world::{Entity, World};
+use ggez::graphics;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+const DEBUG: bool = false;
+/// Pipes score once they get this far left.
+const SCORE_LINE: f32 = 20.0;
+
+/// Moves everything with a velocity, unless the game hasn't started yet.
+/// `speed` slows everything down, or speeds it up.
+pub fn movement(world: &mut World, state: &PlayState, speed: f32) {
+    if PlayState::StartScreen == *state {
+        return;
+    }
+
+    for entity in world.slots() {
+        if let (Some(transform), Some(velocity)) =
+            (&mut world.transforms[entity], &world.velocities[entity])
+        {
+            transform.position += velocity.0 * speed;
+        }
+    }
+}
+
+/// Pulls the pearls near `target` towards it.
+pub fn magnet(world: &mut World, target: Point2<f32>) {
+    for entity in world.slots() {
+        if world.collectibles[entity] != Some(Collectible::Pearl) {
+            continue;
+        }
+        if let Some(transform) = &mut world.transforms[entity] {
+            let to_target = target - transform.position;
+            let distance = to_target.norm();
+            if distance < MAGNET_RADIUS && distance > 0.0 {
+                transform.position += to_target * (MAGNET_PULL / distance).min(1.0);
+            }
+        }
+    }
+}
+
+/// Picks up everything the rect touches.
+pub fn collect(world: &mut World, rect: &graphics::Rect) -> Vec<Collectible> {
+    let mut collected = vec![];
+    for entity in world.slots() {
+        let touching = match (&world.transforms[entity], &world.sprites[entity], &world.collectibles[entity]) {
+            (Some(transform), Some(sprite), Some(_)) => {
+                let mut bounds = sprite.get_bound_box();
+                bounds.move_to(transform.position);
+                bounds.overlaps(rect)
+            }
+            _ => false,
+        };
+        if touching {
+            if let Some(collectible) = world.collectibles[entity] {
+                collected.push(collectible);
+            }
+            world.despawn(entity);
+        }
+    }
+    collected
+}
+
+/// Plays every motion script one update forward.
+pub fn scripts(world: &mut World, state: &PlayState) {
+    if PlayState::StartScreen == *state {
+        return;
+    }
+
+    for entity in world.slots() {
+        let (transform, script) = match (&mut world.transforms[entity], &mut world.motion_scripts[entity]) {
+            (Some(t), Some(s)) => (t, s),
+            _ => continue,
+        };
+        script.ticks += 1;
+        let t = script.ticks as f32;
+
+        match script.motion {
+            Motion::Bob { amplitude, speed } => {
+                let offset = amplitude * (t * speed).sin();
+                transform.position.y += offset - script.offset;
+                script.offset = offset;
+            }
+            Motion::Swoop { depth, duration } => {
+                let progress = (t / duration).min(1.0);
+                let offset = depth * (progress * std::f32::consts::PI).sin();
+                transform.position.y += offset - script.offset;
+                script.offset = offset;
+            }
+            Motion::Jaw { open_gap, closed_gap, speed } => {
+                if let Some(pipe_pair) = &mut world.pipe_pairs[entity] {
+                    let closed = ((t * speed).sin() + 1.0) / 2.0;
+                    pipe_pair.gap_height = open_gap - (open_gap - closed_gap) * closed;
+                    world.colliders[entity] = Some(pipe_pair.collider());
+                }
+            }
+        }
+    }
+}
+
+pub fn animate(world: &mut World) {
+    for entity in world.slots() {
+        if let (Some(sprite), Some(animation)) = (&mut world.sprites[entity], &mut world.animations[entity]) {
+            animation.ticks += 1;
+            let frame = (animation.ticks / animation.ticks_per_frame) as usize % animation.frames.len();
+            *sprite = animation.frames[frame].clone();
+        }
+    }
+}
+
+/// When things go off the left side,
+/// we put them back at the right side to come again.
+/// Pipes get a new gap when they come around.
+/// Anything that doesn't come around again is despawned.
+/// Returns the pipes that came around.
+pub fn recycle(world: &mut World, pipe_tracker: &mut PipeTracker) -> Vec<Entity> {
+    let mut recycled = vec![];
+    for entity in world.slots() {
+        if world.recyclers[entity].is_none() {
+            let off_screen = match (&world.transforms[entity], world.width(entity)) {
+                (Some(transform), Some(width)) => width + transform.position.x < 0.0,
+                _ => false,
+            };
+            if off_screen && world.velocities[entity].is_some() {
+                world.despawn(entity);
+            }
+            continue;
+        }
+
+        let width = match world.width(entity) {
+            Some(width) => width,
+            None => continue,
+        };
+        let (transform, recycler) = match (&mut world.transforms[entity], &world.recyclers[entity]) {
+            (Some(t), Some(r)) => (t, r),
+            _ => continue,
+        };
+
+        if width + transform.position.x >= 0.0 {
+            continue;
+        }
+        transform.position.x += recycler.jump_distance;
+        if world.pipe_pairs[entity].is_some() {
+            transform.position.y = pipe_tracker.next_gap_centre();
+            recycled.push(entity);
+        }
+
+        if let Some(scorer) = &mut world.scorers[entity] {
+            if *scorer == Scorer::Scored {
+                *scorer = Scorer::ReadyToScore;
+            }
+        }
+    }
+    recycled
+}
+
+/// Returns how many points were scored this update.
+pub fn scoring(world: &mut World, state: &PlayState) -> u32 {
+    if !state.is_playing() {
+        return 0;
+    }
+
+    let mut points = 0;
+    for entity in world.slots() {
+        if let (Some(transform), Some(scorer)) =
+            (&world.transforms[entity], &mut world.scorers[entity])
+        {
+            if transform.position.x <= SCORE_LINE && *scorer == Scorer::ReadyToScore {
+                *scorer = Scorer::Scored;
+                points += 1;
+            }
+        }
+    }
+    points
+}
+
+/// Every collider, moved to where its entity is.
+pub fn colliders(world: &World) -> Vec<graphics::Rect> {
+    world
+        .entities()
+        .filter_map(|entity| {
+            match (&world.transforms[entity], &world.colliders[entity]) {
+                (Some(transform), Some(collider)) => Some(collider.at(transform.position)),
+                _ => None,
+            }
+        })
+        .flatten()
+        .collect()
+}
+
+/// The colliders of the pipes still waiting to be scored, so how close the crab comes to them
+/// counts towards the pipe it's getting past, not a hazard or a pipe it has already scored.
+pub fn scoring_colliders(world: &World) -> Vec<graphics::Rect> {
+    world
+        .entities()
+        .filter(|entity| world.scorers[*entity] == Some(Scorer::ReadyToScore))
+        .filter_map(|entity| match (&world.transforms[entity], &world.colliders[entity]) {
+            (Some(transform), Some(collider)) => Some(collider.at(transform.position)),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/// What the rect hits, if it overlaps anything solid.
+pub fn hit(world: &World, rect: &graphics::Rect) -> Option<DeathCause> {
+    world.entities().find_map(|entity| match (&world.transforms[entity], &world.colliders[entity]) {
+        (Some(transform), Some(collider)) if collider.at(transform.position).iter().any(|r| r.overlaps(rect)) => {
+            Some(collider.cause)
+        }
+        _ => None,
+    })
+}
+
+/// Draws everything in the world that can be seen, down to `world_height`.
+pub fn render(
+    ctx: &mut Context,
+    world: &World,
+    world_height: f32,
+    batch: &mut SpriteBatch,
+    light: &Daylight,
+) -> GameResult {
+    for entity in world.entities() {
+        let transform = match &world.transforms[entity] {
+            Some(transform) => transform,
+            None => continue,
+        };
+        if let Some(sprite) = &world.sprites[entity] {
+            batch.add(sprite.draw_params(transform.position).color(light.colour(sprite)));
+        }
+        if let Some(pipe_pair) = &world.pipe_pairs[entity] {
+            pipe_pair.draw(transform.position, world_height, batch, light.colour(&pipe_pair.body));
+        }
+    }
+
+    if !DEBUG {
+        return Ok(());
+    }
+
+    for rect in colliders(world) {
+        let mesh = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::stroke(1.0),
+            rect,
+            graphics::BLACK,
+        )?;
+        let p = graphics::DrawParam::new().scale(Vector2::new(SCALE, SCALE));
+        graphics::draw(ctx, &mesh, p)?;
+    }
+
+    Ok(())
+}
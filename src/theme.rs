@@ -0,0 +1,284 @@
+//! Themes change how the endless course looks and sounds: the atlas image, the sky, the layers of
+//! scenery behind the pipes, the music and the sound effects. They're listed in a manifest in the
+//! resources folder. A run can start in any of them, and they can take turns as the score goes up.
+
+use crate::atlas::{Atlas, Sprite, ATLAS_FILE, ATLAS_IMAGE};
+use crate::daylight::Daylight;
+use crate::game_state::DEFAULT_BACKGROUND;
+use crate::palette::Palette;
+use crate::window::{SCALE, SCREEN_WIDTH};
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{Color, Image};
+use ggez::nalgebra::Point2;
+use ggez::Context;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The theme manifest in the resources folder.
+pub const THEMES_FILE: &str = "themes.json";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Sounds {
+    pub score: String,
+    pub ouch: String,
+    pub begin: String,
+}
+
+impl Default for Sounds {
+    fn default() -> Self {
+        Self {
+            score: "/score_point.wav".to_string(),
+            ouch: "/ouch.wav".to_string(),
+            begin: "/begin_game.wav".to_string(),
+        }
+    }
+}
+
+/// Scenery repeated across the screen behind the pipes.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Layer {
+    pub sprite: String,
+    /// How far down the screen it is.
+    pub y: f32,
+    /// How far apart the sprites are.
+    pub spacing: f32,
+    /// How fast it scrolls compared to the pipes. Further back is slower.
+    pub parallax: f32,
+    #[serde(default = "opaque")]
+    pub alpha: f32,
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// The atlas image, as ggez finds it in the resources folder.
+    #[serde(default = "default_image")]
+    pub image: String,
+    /// The description of the atlas in the resources folder. Every theme's atlas has to have
+    /// the same sprites in the same places, so runs play out the same whatever the theme.
+    #[serde(default = "default_atlas")]
+    pub atlas: String,
+    #[serde(default = "default_sky")]
+    pub sky: [u8; 3],
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    /// Played on a loop while the theme is showing.
+    #[serde(default)]
+    pub music: Option<String>,
+    #[serde(default)]
+    pub sounds: Sounds,
+}
+
+fn default_image() -> String {
+    ATLAS_IMAGE.to_string()
+}
+
+fn default_atlas() -> String {
+    ATLAS_FILE.to_string()
+}
+
+fn default_sky() -> [u8; 3] {
+    let (r, g, b) = DEFAULT_BACKGROUND;
+    [r, g, b]
+}
+
+impl Theme {
+    pub fn sky(&self) -> Color {
+        let [r, g, b] = self.sky;
+        Color::from_rgb(r, g, b)
+    }
+}
+
+/// The theme manifest.
+#[derive(Deserialize, Debug)]
+pub struct ThemeList {
+    pub themes: Vec<Theme>,
+}
+
+impl ThemeList {
+    /// Reads the manifest, or makes do with the game's own atlas and sounds if there isn't one.
+    pub fn load(resources: &Path) -> Result<Self, String> {
+        let file = resources.join(THEMES_FILE);
+        if !file.exists() {
+            return Ok(ThemeList {
+                themes: vec![Theme {
+                    name: "reef".to_string(),
+                    image: default_image(),
+                    atlas: default_atlas(),
+                    sky: default_sky(),
+                    layers: vec![],
+                    music: None,
+                    sounds: Sounds::default(),
+                }],
+            });
+        }
+        let text = std::fs::read_to_string(&file).map_err(|e| e.to_string())?;
+        let list: ThemeList = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        if list.themes.is_empty() {
+            return Err("There are no themes".to_string());
+        }
+        if let Some(layer) = list.themes.iter().flat_map(|theme| &theme.layers).find(|layer| layer.spacing <= 0.0) {
+            return Err(format!("The {} layer needs a spacing above 0", layer.sprite));
+        }
+        Ok(list)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ThemeSettings {
+    /// The theme runs start in. The first in the manifest if left out.
+    pub name: Option<String>,
+    /// Moves on to the next theme every this many points. Left out, a run stays in the theme it started in.
+    pub rotate_every: Option<i64>,
+}
+
+/// A theme's images and layer sprites, ready to draw.
+struct Look {
+    image: Image,
+    outline: Option<Image>,
+    layers: Vec<(Layer, Sprite)>,
+}
+
+/// Every theme, and which one is showing.
+pub struct Themes {
+    list: Vec<Theme>,
+    looks: Vec<Look>,
+    /// The theme runs start in.
+    start: usize,
+    /// The theme showing now.
+    pub current: usize,
+    rotate_every: Option<i64>,
+}
+
+impl Themes {
+    /// Loads every theme in the manifest. Themes that can't be loaded are left out.
+    /// Panics if none of them can be, as there's nothing to draw the game with.
+    pub fn load(ctx: &mut Context, resources: &Path, settings: &ThemeSettings, palette: &Palette) -> Self {
+        let themes = ThemeList::load(resources).unwrap_or_else(|e| panic!("Couldn't load {}: {}", THEMES_FILE, e));
+        let mut list = vec![];
+        let mut looks = vec![];
+        let mut layout: Option<Atlas> = None;
+        for theme in themes.themes {
+            match Themes::look(ctx, resources, &theme, palette, &mut layout) {
+                Ok(look) => {
+                    list.push(theme);
+                    looks.push(look);
+                }
+                Err(e) => println!("Couldn't load the {} theme: {}", theme.name, e),
+            }
+        }
+        if list.is_empty() {
+            panic!("None of the themes in {} could be loaded", THEMES_FILE);
+        }
+        let start = match &settings.name {
+            Some(name) => list.iter().position(|theme| theme.name == *name).unwrap_or_else(|| {
+                println!("There's no theme called {}, starting in {}", name, list[0].name);
+                0
+            }),
+            None => 0,
+        };
+        Self {
+            list,
+            looks,
+            start,
+            current: start,
+            rotate_every: settings.rotate_every.filter(|&every| every > 0),
+        }
+    }
+
+    /// Loads a theme's images. Its atlas has to match the first theme's, which `layout` keeps.
+    fn look(
+        ctx: &mut Context,
+        resources: &Path,
+        theme: &Theme,
+        palette: &Palette,
+        layout: &mut Option<Atlas>,
+    ) -> Result<Look, String> {
+        let atlas = Atlas::load(&resources.join(&theme.atlas))?;
+        if let Some(first) = layout {
+            if !atlas.same_layout(first) {
+                let problem = "doesn't have the same sprites in the same places as the first theme's";
+                return Err(format!("{} {}", theme.atlas, problem));
+            }
+        }
+        if let Some(layer) = theme.layers.iter().find(|layer| !atlas.has_sprite(&layer.sprite)) {
+            return Err(format!("{} has no sprite called {}", theme.atlas, layer.sprite));
+        }
+        let image = palette.image(ctx, &theme.image).map_err(|e| format!("{}: {}", theme.image, e))?;
+        let look = Look {
+            image,
+            outline: palette.outline_image(ctx, &theme.image),
+            layers: theme.layers.iter().map(|layer| (layer.clone(), atlas.create_sprite(&layer.sprite))).collect(),
+        };
+        if layout.is_none() {
+            *layout = Some(atlas);
+        }
+        Ok(look)
+    }
+
+    /// The description of the atlas every theme's sprites are cut from.
+    pub fn atlas_file(&self, resources: &Path) -> PathBuf {
+        resources.join(&self.list[0].atlas)
+    }
+
+    pub fn list(&self) -> &[Theme] {
+        &self.list
+    }
+
+    /// The theme showing now.
+    pub fn theme(&self) -> &Theme {
+        &self.list[self.current]
+    }
+
+    pub fn image(&self) -> Image {
+        self.looks[self.current].image.clone()
+    }
+
+    pub fn outline_image(&self) -> Option<Image> {
+        self.looks[self.current].outline.clone()
+    }
+
+    /// Starts runs in the next theme from now on, and shows it.
+    pub fn next_start(&mut self) {
+        self.start = (self.start + 1) % self.list.len();
+        self.current = self.start;
+    }
+
+    /// Goes back to the theme runs start in.
+    pub fn restart(&mut self) {
+        self.current = self.start;
+    }
+
+    /// Moves on to the theme for this score, if the themes take turns. Returns true if it changed.
+    pub fn for_score(&mut self, score: i128) -> bool {
+        let every = match self.rotate_every {
+            Some(every) => every as i128,
+            None => return false,
+        };
+        let turns = (score.max(0) / every) as usize;
+        let theme = (self.start + turns) % self.list.len();
+        let changed = theme != self.current;
+        self.current = theme;
+        changed
+    }
+
+    /// Adds the scenery behind the pipes to the batch, scrolled along by how far the course has moved.
+    pub fn draw_layers(&self, batch: &mut SpriteBatch, distance: f32, light: &Daylight) {
+        let width = SCREEN_WIDTH / SCALE;
+        for (layer, sprite) in &self.looks[self.current].layers {
+            let mut x = -((distance * layer.parallax) % layer.spacing);
+            while x < width {
+                let colour = light.colour(sprite);
+                let param = sprite.draw_params(Point2::new(x, layer.y)).color(Color { a: layer.alpha, ..colour });
+                batch.add(param);
+                x += layer.spacing;
+            }
+        }
+    }
+}
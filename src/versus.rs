@@ -0,0 +1,191 @@
+use crate::assists::Assists;
+use crate::atlas::Atlas;
+use crate::daylight::Daylight;
+use crate::game_over;
+use crate::game_state::DEFAULT_BACKGROUND;
+use crate::input::InputMap;
+use crate::net::{Connection, Message};
+use crate::palette::Palette;
+use crate::settings::CourseSettings;
+use crate::sim::{Simulation, START_Y};
+use crate::systems;
+use crate::window::{SCALE, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT};
+use ggez::event::EventHandler;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Text};
+use ggez::nalgebra::{Point2, Vector2};
+use ggez::{timer, Context, GameResult};
+
+/// The race runs at a fixed rate, whatever the frame rate, so both games step the same way.
+const UPDATES_PER_SECOND: u32 = 60;
+/// How long the countdown before the race is, in updates.
+const COUNTDOWN: u32 = 3 * UPDATES_PER_SECOND;
+
+/// Racing another game over the network. This crab and the other player's
+/// are each a `Simulation` of the same course. This one is moved by the keyboard
+/// and the other by the flaps that come in from the other game.
+pub struct Versus {
+    connection: Connection,
+    sprite_batch: SpriteBatch,
+    input: InputMap,
+    palette: Palette,
+    me: Simulation,
+    them: Simulation,
+    /// Whether the other game has opened its window.
+    they_are_ready: bool,
+    /// Updates left before the race starts.
+    countdown: u32,
+    /// Set when the other game's run came out differently here, which means the games don't agree.
+    mismatch: Option<String>,
+}
+
+impl Versus {
+    pub fn new(
+        mut connection: Connection,
+        sprite_batch: SpriteBatch,
+        atlas: &Atlas,
+        seed: u32,
+        course: &CourseSettings,
+        input: InputMap,
+        palette: Palette,
+    ) -> Self {
+        connection.send(&Message::Ready);
+        Self {
+            connection,
+            sprite_batch,
+            input,
+            palette,
+            me: Simulation::new(atlas, course, seed, START_Y, 0.0, &Assists::default()),
+            them: Simulation::new(atlas, course, seed, START_Y, 0.0, &Assists::default()),
+            they_are_ready: false,
+            countdown: COUNTDOWN,
+            mismatch: None,
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Ready => self.they_are_ready = true,
+            Message::Flap { tick, held } => {
+                if tick == self.them.ticks && self.them.cause.is_none() {
+                    self.them.step(held);
+                } else if tick != self.them.ticks {
+                    self.mismatch = Some(format!("Missed their flaps before update {}", tick));
+                }
+            }
+            Message::Died { tick, score } => {
+                if self.them.cause.is_none() || self.them.ticks != tick || self.them.score != score {
+                    self.mismatch = Some(format!(
+                        "They scored {} in {} updates, but here they scored {} in {}",
+                        score, tick, self.them.score, self.them.ticks
+                    ));
+                }
+            }
+            other => println!("Didn't expect {:?} in the middle of a race", other),
+        }
+    }
+
+    /// One update of the race.
+    fn step(&mut self, ctx: &Context) {
+        for message in self.connection.received() {
+            self.handle(message);
+        }
+        if !self.they_are_ready {
+            return;
+        }
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+        if self.me.cause.is_none() {
+            let held = self.input.flap_held(ctx, 0);
+            self.connection.send(&Message::Flap { tick: self.me.ticks, held });
+            if !self.me.step(held) {
+                self.connection.send(&Message::Died {
+                    tick: self.me.ticks,
+                    score: self.me.score,
+                });
+            }
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.me.cause.is_some() && (self.them.cause.is_some() || self.connection.closed)
+    }
+
+    fn results(&self) -> Vec<String> {
+        let me = (self.me.score, self.me.ticks);
+        let them = (self.them.score, self.them.ticks);
+        let outcome = if self.them.cause.is_none() {
+            "They left"
+        } else if me > them {
+            "You win!"
+        } else if me < them {
+            "They win"
+        } else {
+            "It's a draw"
+        };
+        let mut lines = vec![
+            outcome.to_string(),
+            format!("You: {}", self.me.score),
+            format!("Them: {}", self.them.score),
+        ];
+        if let Some(mismatch) = &self.mismatch {
+            lines.push("The games didn't agree:".to_string());
+            lines.push(mismatch.clone());
+        }
+        lines.push("Press escape to quit".to_string());
+        lines
+    }
+}
+
+impl EventHandler for Versus {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        while timer::check_update_time(ctx, UPDATES_PER_SECOND) {
+            self.step(ctx);
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, self.palette.sky(graphics::Color::from(DEFAULT_BACKGROUND)));
+
+        // Once this crab is dead, the screen follows theirs.
+        let watching_them = self.me.cause.is_some() && self.them.cause.is_none() && !self.connection.closed;
+        let (world, distance) = if watching_them {
+            (&self.them.world, self.them.distance)
+        } else {
+            (&self.me.world, self.me.distance)
+        };
+        if !self.connection.closed {
+            let them = &self.them.player;
+            let position = Point2::new(them.position.x + self.them.distance - distance, them.position.y);
+            self.me.player.draw_ghost(&mut self.sprite_batch, position, them.physics.velocity.y);
+        }
+        if !watching_them {
+            self.me.player.draw(&mut self.sprite_batch)?;
+        }
+        systems::render(ctx, world, WORLD_HEIGHT, &mut self.sprite_batch, &Daylight::default())?;
+        let scale = graphics::DrawParam::new().scale(Vector2::new(SCALE, SCALE));
+        graphics::draw(ctx, &self.sprite_batch, scale)?;
+        self.sprite_batch.clear();
+
+        let scores = Text::new(format!("You: {}   Them: {}", self.me.score, self.them.score));
+        graphics::draw(ctx, &scores, (Point2::new(10.0, 10.0), graphics::WHITE))?;
+
+        if self.is_over() {
+            game_over::draw_results(ctx, &self.results())?;
+        } else if !self.they_are_ready {
+            let waiting = Text::new("Waiting for the other game...");
+            let position = Point2::new(SCREEN_WIDTH / 2.0 - 120.0, SCREEN_HEIGHT / 2.0);
+            graphics::draw(ctx, &waiting, (position, graphics::WHITE))?;
+        } else if self.countdown > 0 {
+            let count = Text::new(format!("{}", (self.countdown - 1) / UPDATES_PER_SECOND + 1));
+            graphics::draw(ctx, &count, (Point2::new(SCREEN_WIDTH / 2.0, SCREEN_HEIGHT / 2.0), graphics::WHITE))?;
+        }
+
+        graphics::present(ctx)?;
+        std::thread::yield_now();
+        Ok(())
+    }
+}
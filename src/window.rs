@@ -1,19 +1,49 @@
+use crate::cli::Options;
 use ggez::ContextBuilder;
-use std::path::PathBuf;
+use ggez::Context;
+use ggez::graphics;
+use ggez::conf::FullscreenType;
 use ggez::conf::WindowMode;
 use ggez::conf::WindowSetup;
 use ggez::conf::NumSamples;
 
-pub fn build_window(resource_dir: PathBuf) -> ContextBuilder {
+pub const SCREEN_WIDTH: f32 = 800.0;
+pub const SCREEN_HEIGHT: f32 = 600.0;
+/// How much bigger everything in the world is drawn on screen.
+pub const SCALE: f32 = 4.0;
+/// How tall the screen is in world units.
+pub const WORLD_HEIGHT: f32 = SCREEN_HEIGHT / SCALE;
+
+pub fn build_window(options: &Options) -> ContextBuilder {
+    let (width, height) = options.window;
+    let fullscreen = if options.fullscreen {
+        FullscreenType::Desktop
+    } else {
+        FullscreenType::Windowed
+    };
 
     let cb: ContextBuilder = ggez::ContextBuilder::new("FlappyCrab", "youCodeThings")
-        .add_resource_path(resource_dir)
+        .add_resource_path(options.resources.clone())
         .window_setup(
             WindowSetup::default()
                 .title("Flappy Crab (/)(;,,;)(/)!!!")
                 .samples(NumSamples::Zero)
                 .vsync(true),
         )
-        .window_mode(WindowMode::default().dimensions(800.0, 600.0));
+        .window_mode(WindowMode::default().dimensions(width, height).fullscreen_type(fullscreen));
     cb
-}
\ No newline at end of file
+}
+
+/// Whatever size the window is, everything is drawn as if it were 800x600.
+pub fn fit_screen(ctx: &mut Context) {
+    let screen = graphics::Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT);
+    if let Err(e) = graphics::set_screen_coordinates(ctx, screen) {
+        println!("Couldn't fit the game to the window: {}", e);
+    }
+}
+
+/// Turns a position in the window into a position on the 800x600 screen.
+pub fn to_screen(ctx: &Context, x: f32, y: f32) -> (f32, f32) {
+    let (width, height) = graphics::drawable_size(ctx);
+    (x * SCREEN_WIDTH / width, y * SCREEN_HEIGHT / height)
+}
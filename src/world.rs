@@ -0,0 +1,153 @@
+use crate::atlas::Sprite;
+use crate::collectibles::Collectible;
+use crate::components::{
+    Animation, Collider, MotionScript, PipePair, Recycler, Scorer, Transform, Velocity,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// An entity is just an index into the component lists of the `World`.
+pub type Entity = usize;
+
+/// Holds every entity's components.
+/// Each list has one slot per entity, which is `None` if the entity doesn't have that component.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct World {
+    alive: Vec<bool>,
+    pub transforms: Vec<Option<Transform>>,
+    pub sprites: Vec<Option<Sprite>>,
+    pub velocities: Vec<Option<Velocity>>,
+    pub colliders: Vec<Option<Collider>>,
+    pub scorers: Vec<Option<Scorer>>,
+    pub recyclers: Vec<Option<Recycler>>,
+    pub pipe_pairs: Vec<Option<PipePair>>,
+    pub motion_scripts: Vec<Option<MotionScript>>,
+    pub animations: Vec<Option<Animation>>,
+    pub collectibles: Vec<Option<Collectible>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        World::default()
+    }
+
+    /// Starts a new entity, reusing the slot of a despawned one if there is one.
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        let entity = match self.alive.iter().position(|alive| !alive) {
+            Some(entity) => entity,
+            None => {
+                self.alive.push(false);
+                self.transforms.push(None);
+                self.sprites.push(None);
+                self.velocities.push(None);
+                self.colliders.push(None);
+                self.scorers.push(None);
+                self.recyclers.push(None);
+                self.pipe_pairs.push(None);
+                self.motion_scripts.push(None);
+                self.animations.push(None);
+                self.collectibles.push(None);
+                self.alive.len() - 1
+            }
+        };
+        self.alive[entity] = true;
+
+        EntityBuilder { world: self, entity }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.alive[entity] = false;
+        self.transforms[entity] = None;
+        self.sprites[entity] = None;
+        self.velocities[entity] = None;
+        self.colliders[entity] = None;
+        self.scorers[entity] = None;
+        self.recyclers[entity] = None;
+        self.pipe_pairs[entity] = None;
+        self.motion_scripts[entity] = None;
+        self.animations[entity] = None;
+        self.collectibles[entity] = None;
+    }
+
+    /// How wide an entity is drawn, if it is drawn at all.
+    pub fn width(&self, entity: Entity) -> Option<f32> {
+        match (&self.sprites[entity], &self.pipe_pairs[entity]) {
+            (Some(sprite), _) => Some(sprite.width),
+            (None, Some(pipe_pair)) => Some(pipe_pair.width()),
+            (None, None) => None,
+        }
+    }
+
+    /// Every entity that hasn't been despawned.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive.iter().enumerate().filter(|(_, alive)| **alive).map(|(entity, _)| entity)
+    }
+
+    /// Every slot an entity could be in, for systems that change the world as they go through it.
+    /// Despawned slots have no components, so they're skipped like any entity missing one.
+    pub fn slots(&self) -> Range<Entity> {
+        0..self.alive.len()
+    }
+}
+
+/// Adds components to a freshly spawned entity.
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityBuilder<'a> {
+    pub fn with_transform(self, transform: Transform) -> Self {
+        self.world.transforms[self.entity] = Some(transform);
+        self
+    }
+
+    pub fn with_sprite(self, sprite: Sprite) -> Self {
+        self.world.sprites[self.entity] = Some(sprite);
+        self
+    }
+
+    pub fn with_velocity(self, velocity: Velocity) -> Self {
+        self.world.velocities[self.entity] = Some(velocity);
+        self
+    }
+
+    pub fn with_collider(self, collider: Collider) -> Self {
+        self.world.colliders[self.entity] = Some(collider);
+        self
+    }
+
+    pub fn with_scorer(self, scorer: Scorer) -> Self {
+        self.world.scorers[self.entity] = Some(scorer);
+        self
+    }
+
+    pub fn with_recycler(self, recycler: Recycler) -> Self {
+        self.world.recyclers[self.entity] = Some(recycler);
+        self
+    }
+
+    pub fn with_pipe_pair(self, pipe_pair: PipePair) -> Self {
+        self.world.pipe_pairs[self.entity] = Some(pipe_pair);
+        self
+    }
+
+    pub fn with_motion_script(self, motion_script: MotionScript) -> Self {
+        self.world.motion_scripts[self.entity] = Some(motion_script);
+        self
+    }
+
+    pub fn with_animation(self, animation: Animation) -> Self {
+        self.world.animations[self.entity] = Some(animation);
+        self
+    }
+
+    pub fn with_collectible(self, collectible: Collectible) -> Self {
+        self.world.collectibles[self.entity] = Some(collectible);
+        self
+    }
+
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}